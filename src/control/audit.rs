@@ -0,0 +1,457 @@
+use super::role::SessionRole;
+use crate::server::auth::SessionIdentity;
+use crate::server::config::AuditSection;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// 审计事件，每条写为一行 JSON
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent<'a> {
+    /// 认证失败，传输连接未建立
+    AccessDenied {
+        transport: &'static str,
+        peer_addr: SocketAddr,
+        reason: String,
+    },
+    /// 主机端拒绝了连接请求
+    ConsentRejected {
+        transport: &'static str,
+        peer_addr: SocketAddr,
+        identity: &'a SessionIdentity,
+    },
+    SessionConnect {
+        session: u64,
+        transport: &'static str,
+        peer_addr: SocketAddr,
+        role: SessionRole,
+        identity: &'a SessionIdentity,
+    },
+    SessionDisconnect {
+        session: u64,
+        reason: &'a str,
+        duration_secs: u64,
+    },
+    RoleChange {
+        session: u64,
+        from: SessionRole,
+        to: SessionRole,
+    },
+    MonitorSwitch {
+        session: u64,
+        from: u32,
+        to: u32,
+    },
+    EncodingChange {
+        session: u64,
+        codec: &'static str,
+        fps: u32,
+        bitrate: usize,
+        keyframe_interval: u32,
+        simulcast: bool,
+    },
+    /// 一段时间内注入的输入次数
+    InputSummary {
+        session: u64,
+        period_secs: u64,
+        #[serde(flatten)]
+        activity: InputActivity,
+    },
+    /// 逐条按键记录（仅在配置启用时写入）
+    Keystroke {
+        session: u64,
+        key_code: u16,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        code: Option<&'a str>,
+        down: bool,
+    },
+    /// 逐条文本输入记录（与按键记录共用开关）
+    TextInput {
+        session: u64,
+        text: &'a str,
+        paste: bool,
+    },
+}
+
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    /// Unix 毫秒时间戳
+    ts: u64,
+    #[serde(flatten)]
+    event: &'a AuditEvent<'a>,
+}
+
+/// 注入的输入次数（按下计数，不含鼠标移动）
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct InputActivity {
+    pub keys: u64,
+    /// 以文本方式输入的字符数
+    pub text_chars: u64,
+    pub clicks: u64,
+    pub wheel: u64,
+}
+
+impl InputActivity {
+    fn is_empty(&self) -> bool {
+        self.keys == 0 && self.text_chars == 0 && self.clicks == 0 && self.wheel == 0
+    }
+
+    fn add(&mut self, other: InputActivity) {
+        self.keys += other.keys;
+        self.text_chars += other.text_chars;
+        self.clicks += other.clicks;
+        self.wheel += other.wheel;
+    }
+}
+
+/// 只追加写入的 JSON lines 审计日志
+pub struct AuditLog {
+    file: Option<Mutex<File>>,
+    log_keystrokes: bool,
+    input_summary_interval: Duration,
+}
+
+impl AuditLog {
+    pub fn open(section: &AuditSection) -> Result<Self, String> {
+        let file = match &section.path {
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| format!("打开审计日志 {} 失败: {}", path.display(), e))?;
+                log::info!(
+                    "审计日志: {}{}",
+                    path.display(),
+                    if section.log_keystrokes {
+                        "（含逐条按键）"
+                    } else {
+                        ""
+                    }
+                );
+                Some(Mutex::new(file))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            file,
+            log_keystrokes: section.log_keystrokes,
+            input_summary_interval: Duration::from_secs(section.input_summary_interval),
+        })
+    }
+
+    pub fn record(&self, event: AuditEvent<'_>) {
+        let Some(file) = &self.file else {
+            return;
+        };
+
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        let mut line = match serde_json::to_vec(&AuditRecord { ts, event: &event }) {
+            Ok(line) => line,
+            Err(e) => {
+                log::warn!("序列化审计记录失败: {}", e);
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        // 整行一次写入，避免多个会话线程的记录交错
+        let Ok(mut file) = file.lock() else {
+            return;
+        };
+        if let Err(e) = file.write_all(&line) {
+            log::warn!("写入审计日志失败: {}", e);
+        }
+    }
+
+    /// 将已写入的记录落盘（退出前调用）
+    pub fn flush(&self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        if let Ok(file) = file.lock() {
+            if let Err(e) = file.sync_data() {
+                log::warn!("审计日志落盘失败: {}", e);
+            }
+        }
+    }
+}
+
+/// 单个会话的审计记录：累计输入次数并定期写入汇总，drop 时写入断开记录
+pub struct SessionAudit {
+    log: Arc<AuditLog>,
+    session: u64,
+    started_at: Instant,
+    role: SessionRole,
+    input: InputActivity,
+    input_since: Instant,
+    end_reason: Option<String>,
+}
+
+impl SessionAudit {
+    pub fn start(
+        log: Arc<AuditLog>,
+        session: u64,
+        transport: &'static str,
+        peer_addr: SocketAddr,
+        role: SessionRole,
+        identity: &SessionIdentity,
+    ) -> Self {
+        log.record(AuditEvent::SessionConnect {
+            session,
+            transport,
+            peer_addr,
+            role,
+            identity,
+        });
+        let now = Instant::now();
+        Self {
+            log,
+            session,
+            started_at: now,
+            role,
+            input: InputActivity::default(),
+            input_since: now,
+            end_reason: None,
+        }
+    }
+
+    pub fn session(&self) -> u64 {
+        self.session
+    }
+
+    pub fn record(&self, event: AuditEvent<'_>) {
+        self.log.record(event);
+    }
+
+    /// 记录会话角色变化（角色可能由其他会话或管理接口修改），与上次记录的角色相同时忽略
+    pub fn observe_role(&mut self, role: SessionRole) {
+        if role == self.role {
+            return;
+        }
+        self.log.record(AuditEvent::RoleChange {
+            session: self.session,
+            from: self.role,
+            to: role,
+        });
+        self.role = role;
+    }
+
+    pub fn keystroke(&self, key_code: u16, code: Option<&str>, down: bool) {
+        if self.log.log_keystrokes {
+            self.log.record(AuditEvent::Keystroke {
+                session: self.session,
+                key_code,
+                code,
+                down,
+            });
+        }
+    }
+
+    pub fn text_input(&self, text: &str, paste: bool) {
+        if self.log.log_keystrokes {
+            self.log.record(AuditEvent::TextInput {
+                session: self.session,
+                text,
+                paste,
+            });
+        }
+    }
+
+    /// 累计输入次数，到达汇总间隔时写入一条记录
+    pub fn add_input(&mut self, activity: InputActivity) {
+        self.input.add(activity);
+        if self.input_since.elapsed() >= self.log.input_summary_interval {
+            self.flush_input();
+        }
+    }
+
+    pub fn set_end_reason(&mut self, reason: impl Into<String>) {
+        self.end_reason = Some(reason.into());
+    }
+
+    fn flush_input(&mut self) {
+        let activity = std::mem::take(&mut self.input);
+        let period = self.input_since.elapsed();
+        self.input_since = Instant::now();
+        if !activity.is_empty() {
+            self.log.record(AuditEvent::InputSummary {
+                session: self.session,
+                period_secs: period.as_secs(),
+                activity,
+            });
+        }
+    }
+}
+
+impl Drop for SessionAudit {
+    fn drop(&mut self) {
+        self.flush_input();
+        self.log.record(AuditEvent::SessionDisconnect {
+            session: self.session,
+            reason: self.end_reason.as_deref().unwrap_or("closed"),
+            duration_secs: self.started_at.elapsed().as_secs(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::path::{Path, PathBuf};
+
+    fn temp_log_path(label: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "webdisplay-audit-{}-{}.jsonl",
+            label,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn open_log(path: &Path, log_keystrokes: bool, input_summary_interval: u64) -> Arc<AuditLog> {
+        let section = AuditSection {
+            path: Some(path.to_path_buf()),
+            log_keystrokes,
+            input_summary_interval,
+        };
+        Arc::new(AuditLog::open(&section).unwrap())
+    }
+
+    fn read_events(path: &Path) -> Vec<Value> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn start_session(log: &Arc<AuditLog>) -> SessionAudit {
+        SessionAudit::start(
+            Arc::clone(log),
+            7,
+            "WebSocket",
+            SocketAddr::from(([192, 168, 1, 30], 40000)),
+            SessionRole::Controller,
+            &SessionIdentity::Credential {
+                name: "alice".to_string(),
+            },
+        )
+    }
+
+    #[test]
+    fn records_session_lifecycle() {
+        let path = temp_log_path("lifecycle");
+        let log = open_log(&path, false, 60);
+
+        let mut audit = start_session(&log);
+        // 角色未变化时不记录
+        audit.observe_role(SessionRole::Controller);
+        audit.observe_role(SessionRole::Viewer);
+        audit.keystroke(0x1E, Some("KeyA"), true);
+        audit.text_input("你好", false);
+        audit.add_input(InputActivity {
+            keys: 2,
+            clicks: 1,
+            ..InputActivity::default()
+        });
+        audit.set_end_reason("kicked");
+        drop(audit);
+
+        let events = read_events(&path);
+        let kinds: Vec<_> = events
+            .iter()
+            .map(|e| e["event"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "session_connect",
+                "role_change",
+                "input_summary",
+                "session_disconnect"
+            ]
+        );
+        assert!(events.iter().all(|e| e["ts"].as_u64().unwrap() > 0));
+        assert_eq!(events[0]["session"], 7);
+        assert_eq!(events[0]["peer_addr"], "192.168.1.30:40000");
+        assert_eq!(events[0]["identity"]["kind"], "credential");
+        assert_eq!(events[0]["identity"]["name"], "alice");
+        assert_eq!(events[2]["keys"], 2);
+        assert_eq!(events[2]["clicks"], 1);
+        assert_eq!(events[3]["reason"], "kicked");
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn records_keystrokes_when_enabled() {
+        let path = temp_log_path("keystrokes");
+        let log = open_log(&path, true, 60);
+
+        let audit = start_session(&log);
+        audit.keystroke(0x1E, Some("KeyA"), true);
+        audit.keystroke(0x1E, None, false);
+        audit.text_input("你好", true);
+        drop(audit);
+
+        let events = read_events(&path);
+        assert_eq!(events[1]["event"], "keystroke");
+        assert_eq!(events[1]["code"], "KeyA");
+        assert!(events[2].get("code").is_none());
+        assert_eq!(events[3]["event"], "text_input");
+        assert_eq!(events[3]["text"], "你好");
+        // 没有输入时不写汇总
+        assert_eq!(events[4]["event"], "session_disconnect");
+        assert_eq!(events[4]["reason"], "closed");
+        assert_eq!(events.len(), 5);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn summarizes_input_per_interval() {
+        let path = temp_log_path("summary");
+        let log = open_log(&path, false, 0);
+
+        let mut audit = start_session(&log);
+        for _ in 0..2 {
+            audit.add_input(InputActivity {
+                wheel: 3,
+                ..InputActivity::default()
+            });
+        }
+        drop(audit);
+
+        let summaries: Vec<_> = read_events(&path)
+            .into_iter()
+            .filter(|e| e["event"] == "input_summary")
+            .collect();
+        assert_eq!(summaries.len(), 2);
+        assert!(summaries.iter().all(|e| e["wheel"] == 3));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn disabled_log_ignores_events() {
+        let log = Arc::new(AuditLog::open(&AuditSection::default()).unwrap());
+        let audit = start_session(&log);
+        audit.record(AuditEvent::MonitorSwitch {
+            session: 7,
+            from: 0,
+            to: 1,
+        });
+        log.flush();
+    }
+}
@@ -0,0 +1,427 @@
+use super::role::SessionRole;
+use crate::server::auth::constant_time_eq;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 通过 HTTP 接口确认时，确认码输错该次数后直接拒绝该连接请求
+const MAX_CODE_ATTEMPTS: u32 = 3;
+
+/// 主机端确认方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ConsentMode {
+    /// 不需要确认
+    Off,
+    /// 通过本机 HTTP 接口确认
+    Http,
+    /// 通过服务端控制台 stdin 确认
+    Stdin,
+    /// 两种方式均可
+    Both,
+}
+
+impl FromStr for ConsentMode {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "off" | "none" | "" => Ok(Self::Off),
+            "http" => Ok(Self::Http),
+            "stdin" => Ok(Self::Stdin),
+            "both" | "on" => Ok(Self::Both),
+            _ => Err(format!(
+                "未知的确认方式 '{}'（可用: off, http, stdin, both）",
+                raw
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for ConsentMode {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        raw.parse()
+    }
+}
+
+impl ConsentMode {
+    pub fn allows_http(self) -> bool {
+        matches!(self, Self::Http | Self::Both)
+    }
+
+    pub fn allows_stdin(self) -> bool {
+        matches!(self, Self::Stdin | Self::Both)
+    }
+}
+
+/// 等待确认的连接信息
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsentRequest {
+    pub id: u64,
+    pub transport: &'static str,
+    pub peer_addr: SocketAddr,
+    pub role: SessionRole,
+    /// 剩余等待秒数
+    pub expires_in: u64,
+}
+
+struct PendingConsent {
+    transport: &'static str,
+    peer_addr: SocketAddr,
+    role: SessionRole,
+    deadline: Instant,
+    responder: SyncSender<bool>,
+    /// 只打印在服务端控制台的确认码，HTTP 接口确认时必须提供
+    code: String,
+    code_attempts: u32,
+}
+
+/// 通过 HTTP 接口确认的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteDecision {
+    Applied,
+    NotFound,
+    /// 确认码错误；次数用尽时请求已被拒绝
+    WrongCode,
+}
+
+/// 新会话接入前的主机端确认
+pub struct ConsentGate {
+    mode: ConsentMode,
+    timeout: Duration,
+    next_id: AtomicU64,
+    pending: Mutex<BTreeMap<u64, PendingConsent>>,
+    rng: SystemRandom,
+}
+
+impl ConsentGate {
+    pub fn new(mode: ConsentMode, timeout: Duration) -> Self {
+        if mode != ConsentMode::Off {
+            log::info!(
+                "已启用主机端连接确认: {:?}, 超时 {} 秒",
+                mode,
+                timeout.as_secs()
+            );
+        }
+        Self {
+            mode,
+            timeout,
+            next_id: AtomicU64::new(1),
+            pending: Mutex::new(BTreeMap::new()),
+            rng: SystemRandom::new(),
+        }
+    }
+
+    pub fn mode(&self) -> ConsentMode {
+        self.mode
+    }
+
+    pub fn is_off(&self) -> bool {
+        self.mode == ConsentMode::Off
+    }
+
+    /// 启用 stdin 确认时启动控制台读取线程
+    pub fn spawn_stdin_prompt(self: &Arc<Self>) {
+        if !self.mode.allows_stdin() {
+            return;
+        }
+
+        let gate = Arc::clone(self);
+        let spawn_result = std::thread::Builder::new()
+            .name("consent-stdin".to_string())
+            .spawn(move || {
+                for line in std::io::stdin().lock().lines() {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    gate.handle_stdin_command(line.trim());
+                }
+                log::warn!("stdin 已关闭，控制台确认不可用");
+            });
+
+        if let Err(e) = spawn_result {
+            log::error!("启动控制台确认线程失败: {}", e);
+        }
+    }
+
+    /// 阻塞等待主机端确认（在会话线程中调用），未启用时直接放行
+    pub fn wait_for_approval(
+        &self,
+        transport: &'static str,
+        peer_addr: SocketAddr,
+        role: SessionRole,
+    ) -> bool {
+        if self.mode == ConsentMode::Off {
+            return true;
+        }
+
+        let mut random = [0u8; 4];
+        if self.rng.fill(&mut random).is_err() {
+            log::error!("生成确认码失败，已拒绝来自 {} 的连接", peer_addr);
+            return false;
+        }
+        let code = format!("{:06}", u32::from_le_bytes(random) % 1_000_000);
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (responder, receiver) = mpsc::sync_channel(1);
+        match self.pending.lock() {
+            Ok(mut pending) => {
+                pending.insert(
+                    id,
+                    PendingConsent {
+                        transport,
+                        peer_addr,
+                        role,
+                        deadline: Instant::now() + self.timeout,
+                        responder,
+                        code: code.clone(),
+                        code_attempts: 0,
+                    },
+                );
+            }
+            Err(_) => return false,
+        }
+
+        log::warn!(
+            "连接请求 #{} 等待确认: {} {}, 角色 {}（{} 秒内未确认将拒绝）",
+            id,
+            transport,
+            peer_addr,
+            role,
+            self.timeout.as_secs()
+        );
+        if self.mode.allows_http() {
+            log::warn!(
+                "连接请求 #{} 的确认码: {}（通过 HTTP 接口确认时需提供）",
+                id,
+                code
+            );
+        }
+        if self.mode.allows_stdin() {
+            println!("输入 `y {}` 允许或 `n {}` 拒绝该连接", id, id);
+        }
+
+        let approved = match receiver.recv_timeout(self.timeout) {
+            Ok(approved) => approved,
+            Err(_) => {
+                log::warn!("连接请求 #{} 确认超时，已拒绝", id);
+                false
+            }
+        };
+
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&id);
+        }
+        approved
+    }
+
+    pub fn pending(&self) -> Vec<ConsentRequest> {
+        let Ok(pending) = self.pending.lock() else {
+            return Vec::new();
+        };
+
+        let now = Instant::now();
+        pending
+            .iter()
+            .map(|(&id, p)| ConsentRequest {
+                id,
+                transport: p.transport,
+                peer_addr: p.peer_addr,
+                role: p.role,
+                expires_in: p.deadline.saturating_duration_since(now).as_secs(),
+            })
+            .collect()
+    }
+
+    /// 对等待中的连接做出决定，请求不存在时返回 false
+    pub fn decide(&self, id: u64, approved: bool) -> bool {
+        let Some(request) = self.pending.lock().ok().and_then(|mut p| p.remove(&id)) else {
+            return false;
+        };
+
+        log::info!(
+            "连接请求 #{} ({}) 已{}",
+            id,
+            request.peer_addr,
+            if approved { "允许" } else { "拒绝" }
+        );
+        request.responder.try_send(approved).is_ok()
+    }
+
+    /// HTTP 接口的确认：需提供控制台打印的确认码，输错过多时直接拒绝
+    pub fn decide_with_code(&self, id: u64, code: &str, approved: bool) -> RemoteDecision {
+        let Ok(mut pending) = self.pending.lock() else {
+            return RemoteDecision::NotFound;
+        };
+        let Some(request) = pending.get_mut(&id) else {
+            return RemoteDecision::NotFound;
+        };
+
+        if !constant_time_eq(code.trim().as_bytes(), request.code.as_bytes()) {
+            request.code_attempts += 1;
+            log::warn!(
+                "连接请求 #{} 的确认码错误（第 {} 次）",
+                id,
+                request.code_attempts
+            );
+            if request.code_attempts >= MAX_CODE_ATTEMPTS {
+                drop(pending);
+                log::warn!("连接请求 #{} 确认码错误次数过多", id);
+                self.decide(id, false);
+            }
+            return RemoteDecision::WrongCode;
+        }
+
+        drop(pending);
+        if self.decide(id, approved) {
+            RemoteDecision::Applied
+        } else {
+            RemoteDecision::NotFound
+        }
+    }
+
+    /// 控制台命令：`y [id]` / `n [id]` / `list`，省略 id 时处理最早的请求
+    fn handle_stdin_command(&self, line: &str) {
+        let mut parts = line.split_whitespace();
+        let Some(command) = parts.next() else {
+            return;
+        };
+
+        let approved = match command.to_ascii_lowercase().as_str() {
+            "y" | "yes" => true,
+            "n" | "no" => false,
+            "list" | "ls" => {
+                for request in self.pending() {
+                    println!(
+                        "#{} {} {} 角色 {}，剩余 {} 秒",
+                        request.id,
+                        request.transport,
+                        request.peer_addr,
+                        request.role,
+                        request.expires_in
+                    );
+                }
+                return;
+            }
+            _ => {
+                println!("未知命令: {}（可用: y [id] / n [id] / list）", command);
+                return;
+            }
+        };
+
+        let id = match parts.next() {
+            Some(raw) => raw.trim_start_matches('#').parse().ok(),
+            None => self
+                .pending
+                .lock()
+                .ok()
+                .and_then(|p| p.keys().next().copied()),
+        };
+
+        match id {
+            Some(id) if self.decide(id, approved) => {}
+            _ => println!("没有匹配的待确认连接"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::JoinHandle;
+
+    fn peer() -> SocketAddr {
+        SocketAddr::from(([192, 168, 1, 30], 40000))
+    }
+
+    /// 在后台线程发起确认请求，返回请求编号、确认码与等待结果的线程
+    fn start_request(gate: &Arc<ConsentGate>) -> (u64, String, JoinHandle<bool>) {
+        let waiting = Arc::clone(gate);
+        let handle = std::thread::spawn(move || {
+            waiting.wait_for_approval("WebSocket", peer(), SessionRole::Controller)
+        });
+
+        loop {
+            let pending = gate.pending.lock().unwrap();
+            if let Some((&id, request)) = pending.iter().next() {
+                return (id, request.code.clone(), handle);
+            }
+            drop(pending);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!("off".parse::<ConsentMode>(), Ok(ConsentMode::Off));
+        assert_eq!("".parse::<ConsentMode>(), Ok(ConsentMode::Off));
+        assert_eq!("HTTP".parse::<ConsentMode>(), Ok(ConsentMode::Http));
+        assert_eq!(" stdin ".parse::<ConsentMode>(), Ok(ConsentMode::Stdin));
+        assert_eq!("on".parse::<ConsentMode>(), Ok(ConsentMode::Both));
+        assert!("maybe".parse::<ConsentMode>().is_err());
+
+        assert!(ConsentMode::Both.allows_http() && ConsentMode::Both.allows_stdin());
+        assert!(ConsentMode::Http.allows_http() && !ConsentMode::Http.allows_stdin());
+        assert!(!ConsentMode::Off.allows_http() && !ConsentMode::Off.allows_stdin());
+    }
+
+    #[test]
+    fn off_mode_approves_immediately() {
+        let gate = ConsentGate::new(ConsentMode::Off, Duration::from_secs(1));
+        assert!(gate.wait_for_approval("WebSocket", peer(), SessionRole::Viewer));
+        assert!(gate.pending().is_empty());
+    }
+
+    #[test]
+    fn correct_code_approves() {
+        let gate = Arc::new(ConsentGate::new(ConsentMode::Http, Duration::from_secs(10)));
+        let (id, code, handle) = start_request(&gate);
+        assert_eq!(code.len(), 6);
+        assert_eq!(gate.pending()[0].peer_addr, peer());
+
+        assert_eq!(
+            gate.decide_with_code(id, &code, true),
+            RemoteDecision::Applied
+        );
+        assert!(handle.join().unwrap());
+        assert_eq!(
+            gate.decide_with_code(id, &code, true),
+            RemoteDecision::NotFound
+        );
+    }
+
+    #[test]
+    fn repeated_wrong_codes_reject_the_request() {
+        let gate = Arc::new(ConsentGate::new(ConsentMode::Http, Duration::from_secs(10)));
+        let (id, code, handle) = start_request(&gate);
+        let wrong = if code == "000000" { "000001" } else { "000000" };
+
+        for _ in 0..MAX_CODE_ATTEMPTS {
+            assert_eq!(
+                gate.decide_with_code(id, wrong, true),
+                RemoteDecision::WrongCode
+            );
+        }
+        assert!(!handle.join().unwrap());
+        assert_eq!(
+            gate.decide_with_code(id, &code, true),
+            RemoteDecision::NotFound
+        );
+    }
+
+    #[test]
+    fn unanswered_requests_time_out() {
+        let gate = ConsentGate::new(ConsentMode::Stdin, Duration::from_millis(20));
+        assert!(!gate.wait_for_approval("WebRTC", peer(), SessionRole::Viewer));
+        assert!(gate.pending().is_empty());
+    }
+}
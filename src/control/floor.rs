@@ -0,0 +1,293 @@
+use super::role::SessionRole;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 输入仲裁模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FloorMode {
+    /// 不仲裁，所有控制者的输入直接注入
+    Free,
+    /// 同一时刻只有一个控制者的输入会被注入
+    Exclusive,
+}
+
+/// 多控制者之间的输入控制权（floor）仲裁
+pub struct InputFloor {
+    mode: FloorMode,
+    /// 持有者超过该时长无输入时，其他控制者的输入可直接接管
+    idle_timeout: Duration,
+    /// 持有者变化时递增，会话据此向客户端推送最新持有者
+    generation: AtomicU64,
+    state: Mutex<FloorState>,
+}
+
+struct FloorState {
+    holder: Option<u64>,
+    last_activity: Instant,
+}
+
+/// 输入帧的仲裁结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloorDecision {
+    Granted,
+    /// 控制权由其他会话持有
+    Busy,
+}
+
+/// 客户端发起的控制权请求
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum FloorRequest {
+    Request,
+    Release,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct FloorSnapshot {
+    pub mode: FloorMode,
+    pub holder: Option<u64>,
+}
+
+/// 会话存活期间持有，drop 时释放该会话持有的控制权
+pub struct FloorGuard {
+    floor: Arc<InputFloor>,
+    session_id: u64,
+}
+
+impl Drop for FloorGuard {
+    fn drop(&mut self) {
+        self.floor.release(self.session_id);
+    }
+}
+
+impl FromStr for FloorMode {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.trim() {
+            "free" => Ok(Self::Free),
+            "exclusive" => Ok(Self::Exclusive),
+            other => Err(format!(
+                "未知的输入仲裁模式 '{}'（可用: free, exclusive）",
+                other
+            )),
+        }
+    }
+}
+
+impl InputFloor {
+    pub fn new(mode: FloorMode, idle_timeout: Duration) -> Self {
+        Self {
+            mode,
+            idle_timeout,
+            generation: AtomicU64::new(0),
+            state: Mutex::new(FloorState {
+                holder: None,
+                last_activity: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn guard(self: &Arc<Self>, session_id: u64) -> FloorGuard {
+        FloorGuard {
+            floor: self.clone(),
+            session_id,
+        }
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    pub fn snapshot(&self) -> FloorSnapshot {
+        let holder = self.state.lock().ok().and_then(|state| state.holder);
+        FloorSnapshot {
+            mode: self.mode,
+            holder,
+        }
+    }
+
+    /// 控制者注入输入前调用：空闲时自动获得控制权，持有者空闲超时则直接接管
+    pub fn try_use(&self, session_id: u64) -> FloorDecision {
+        if self.mode == FloorMode::Free {
+            return FloorDecision::Granted;
+        }
+
+        let Ok(mut state) = self.state.lock() else {
+            return FloorDecision::Granted;
+        };
+
+        match state.holder {
+            Some(holder) if holder == session_id => {}
+            Some(_) if state.last_activity.elapsed() < self.idle_timeout => {
+                return FloorDecision::Busy;
+            }
+            previous => {
+                if let Some(previous) = previous {
+                    log::info!(
+                        "会话 #{} 空闲超时，控制权由会话 #{} 接管",
+                        previous,
+                        session_id
+                    );
+                } else {
+                    log::info!("会话 #{} 获得输入控制权", session_id);
+                }
+                state.holder = Some(session_id);
+                self.bump_generation();
+            }
+        }
+
+        state.last_activity = Instant::now();
+        FloorDecision::Granted
+    }
+
+    /// 处理显式的请求/释放消息；管理员的请求可抢占
+    pub fn apply_request(
+        &self,
+        session_id: u64,
+        role: SessionRole,
+        request: FloorRequest,
+    ) -> Result<(), String> {
+        if self.mode == FloorMode::Free {
+            return Ok(());
+        }
+
+        match request {
+            FloorRequest::Request => {
+                if !role.can_control() {
+                    return Err("观看者无法请求控制权".to_string());
+                }
+
+                let mut state = self.state.lock().map_err(|e| e.to_string())?;
+                match state.holder {
+                    Some(holder) if holder == session_id => return Ok(()),
+                    Some(holder)
+                        if role != SessionRole::Admin
+                            && state.last_activity.elapsed() < self.idle_timeout =>
+                    {
+                        return Err(format!("控制权由会话 #{} 持有", holder));
+                    }
+                    _ => {}
+                }
+
+                state.holder = Some(session_id);
+                state.last_activity = Instant::now();
+                self.bump_generation();
+                log::info!("会话 #{} 请求并获得输入控制权", session_id);
+                Ok(())
+            }
+            FloorRequest::Release => {
+                self.release(session_id);
+                Ok(())
+            }
+        }
+    }
+
+    /// 若该会话持有控制权则释放（断开、失去控制角色时调用）
+    pub fn release(&self, session_id: u64) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+
+        if state.holder == Some(session_id) {
+            state.holder = None;
+            self.bump_generation();
+            log::info!("会话 #{} 释放输入控制权", session_id);
+        }
+    }
+
+    fn bump_generation(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDLE: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn free_mode_always_grants() {
+        let floor = InputFloor::new(FloorMode::Free, IDLE);
+        assert_eq!(floor.try_use(1), FloorDecision::Granted);
+        assert_eq!(floor.try_use(2), FloorDecision::Granted);
+        assert_eq!(floor.snapshot().holder, None);
+    }
+
+    #[test]
+    fn exclusive_mode_grants_first_user_and_blocks_others() {
+        let floor = InputFloor::new(FloorMode::Exclusive, Duration::from_secs(60));
+        let generation = floor.generation();
+
+        assert_eq!(floor.try_use(1), FloorDecision::Granted);
+        assert_eq!(floor.snapshot().holder, Some(1));
+        assert_ne!(floor.generation(), generation);
+
+        assert_eq!(floor.try_use(2), FloorDecision::Busy);
+        assert_eq!(floor.try_use(1), FloorDecision::Granted);
+    }
+
+    #[test]
+    fn idle_holder_is_taken_over() {
+        let floor = InputFloor::new(FloorMode::Exclusive, IDLE);
+        assert_eq!(floor.try_use(1), FloorDecision::Granted);
+        std::thread::sleep(IDLE * 2);
+        assert_eq!(floor.try_use(2), FloorDecision::Granted);
+        assert_eq!(floor.snapshot().holder, Some(2));
+    }
+
+    #[test]
+    fn release_only_affects_holder() {
+        let floor = InputFloor::new(FloorMode::Exclusive, Duration::from_secs(60));
+        floor.try_use(1);
+
+        floor.release(2);
+        assert_eq!(floor.snapshot().holder, Some(1));
+
+        let generation = floor.generation();
+        floor.release(1);
+        assert_eq!(floor.snapshot().holder, None);
+        assert_ne!(floor.generation(), generation);
+        assert_eq!(floor.try_use(2), FloorDecision::Granted);
+    }
+
+    #[test]
+    fn admin_request_preempts_active_holder() {
+        let floor = InputFloor::new(FloorMode::Exclusive, Duration::from_secs(60));
+        floor.try_use(1);
+
+        assert!(
+            floor
+                .apply_request(2, SessionRole::Controller, FloorRequest::Request)
+                .is_err()
+        );
+        assert!(
+            floor
+                .apply_request(3, SessionRole::Viewer, FloorRequest::Request)
+                .is_err()
+        );
+        assert!(
+            floor
+                .apply_request(4, SessionRole::Admin, FloorRequest::Request)
+                .is_ok()
+        );
+        assert_eq!(floor.snapshot().holder, Some(4));
+
+        floor
+            .apply_request(4, SessionRole::Admin, FloorRequest::Release)
+            .unwrap();
+        assert_eq!(floor.snapshot().holder, None);
+    }
+
+    #[test]
+    fn parses_floor_mode() {
+        assert_eq!("free".parse::<FloorMode>(), Ok(FloorMode::Free));
+        assert_eq!(" exclusive ".parse::<FloorMode>(), Ok(FloorMode::Exclusive));
+        assert!("shared".parse::<FloorMode>().is_err());
+    }
+}
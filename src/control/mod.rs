@@ -0,0 +1,5 @@
+pub mod audit;
+pub mod consent;
+pub mod floor;
+pub mod registry;
+pub mod role;
@@ -0,0 +1,687 @@
+use super::role::SessionRole;
+use crate::input::win32::HeldInputs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 所有传输共享的在线会话登记表
+pub struct SessionRegistry {
+    next_id: AtomicU64,
+    /// 会话集合或角色发生变化时递增，会话据此判断是否需要向客户端推送新状态
+    generation: AtomicU64,
+    sessions: Mutex<HashMap<u64, Arc<SessionEntry>>>,
+}
+
+pub struct SessionEntry {
+    id: u64,
+    transport: &'static str,
+    peer_addr: SocketAddr,
+    /// 通过分享链接接入时的链接编号
+    share_id: Option<u64>,
+    /// 会话开始时间（Unix 秒）
+    started_at: u64,
+    role: AtomicU8,
+    /// 令牌或分享链接授予的最高角色，角色变更不能超出该范围
+    max_role: SessionRole,
+    media: Mutex<SessionMedia>,
+    frames_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    /// 管理接口下发、等待会话线程处理的指令
+    commands: Mutex<Vec<SessionCommand>>,
+    /// 会话已按下的按键与按钮，退出时会话线程未结束则由主线程松开
+    held_inputs: Mutex<Option<Arc<HeldInputs>>>,
+}
+
+/// 会话当前的显示器与编码参数（由会话线程更新）
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SessionMedia {
+    pub monitor: u32,
+    pub codec: &'static str,
+    pub fps: u32,
+    pub bitrate: u32,
+    pub simulcast: bool,
+}
+
+/// 管理接口对会话的编码参数修改（未指定的字段保持不变）
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EncodingUpdate {
+    #[serde(default)]
+    pub codec: Option<String>,
+    #[serde(default)]
+    pub fps: Option<u32>,
+    #[serde(default)]
+    pub bitrate: Option<u32>,
+    #[serde(default)]
+    pub keyframe_interval: Option<u32>,
+    #[serde(default)]
+    pub simulcast: Option<bool>,
+}
+
+/// 由会话线程在主循环中执行的指令
+#[derive(Debug, Clone)]
+pub enum SessionCommand {
+    SelectMonitor(u32),
+    UpdateEncoding(EncodingUpdate),
+    Disconnect {
+        reason: String,
+    },
+    /// 服务端即将退出
+    Shutdown,
+}
+
+impl SessionEntry {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn role(&self) -> SessionRole {
+        SessionRole::from_u8(self.role.load(Ordering::Acquire))
+    }
+
+    fn details(&self) -> SessionDetails {
+        SessionDetails {
+            id: self.id,
+            transport: self.transport,
+            peer_addr: self.peer_addr,
+            share_id: self.share_id,
+            role: self.role(),
+            started_at: self.started_at,
+            media: self.media.lock().map(|m| m.clone()).unwrap_or_default(),
+            frames_sent: self.frames_sent.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+        }
+    }
+
+    fn push_command(&self, command: SessionCommand) {
+        if let Ok(mut commands) = self.commands.lock() {
+            commands.push(command);
+        }
+    }
+}
+
+/// 会话存活期间持有的登记句柄，drop 时自动注销
+pub struct SessionHandle {
+    registry: Arc<SessionRegistry>,
+    entry: Arc<SessionEntry>,
+}
+
+impl SessionHandle {
+    pub fn id(&self) -> u64 {
+        self.entry.id
+    }
+
+    pub fn role(&self) -> SessionRole {
+        self.entry.role()
+    }
+
+    pub fn update_media(&self, media: SessionMedia) {
+        if let Ok(mut current) = self.entry.media.lock() {
+            *current = media;
+        }
+    }
+
+    pub fn record_frame_sent(&self, bytes: usize) {
+        self.entry.frames_sent.fetch_add(1, Ordering::Relaxed);
+        self.entry
+            .bytes_sent
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// 登记会话的输入注入状态，供退出时强制松开
+    pub fn attach_held_inputs(&self, held: Arc<HeldInputs>) {
+        if let Ok(mut current) = self.entry.held_inputs.lock() {
+            *current = Some(held);
+        }
+    }
+
+    /// 取出管理接口下发的全部待处理指令
+    pub fn take_commands(&self) -> Vec<SessionCommand> {
+        self.entry
+            .commands
+            .lock()
+            .map(|mut commands| std::mem::take(&mut *commands))
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for SessionHandle {
+    fn drop(&mut self) {
+        self.registry.unregister(self.entry.id);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSummary {
+    pub id: u64,
+    pub transport: &'static str,
+    pub role: SessionRole,
+}
+
+/// 管理接口返回的完整会话信息
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionDetails {
+    pub id: u64,
+    pub transport: &'static str,
+    pub peer_addr: SocketAddr,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share_id: Option<u64>,
+    pub role: SessionRole,
+    pub started_at: u64,
+    #[serde(flatten)]
+    pub media: SessionMedia,
+    pub frames_sent: u64,
+    pub bytes_sent: u64,
+}
+
+/// 客户端发起的角色变更请求
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RoleRequest {
+    /// 将控制权移交给目标会话，自身降为观看者（管理员保持不变）
+    HandOff { target: u64 },
+    /// 管理员直接设置目标会话的角色
+    Set { target: u64, role: String },
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(1),
+            generation: AtomicU64::new(0),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn register(
+        self: &Arc<Self>,
+        transport: &'static str,
+        peer_addr: SocketAddr,
+        role: SessionRole,
+        max_role: SessionRole,
+        share_id: Option<u64>,
+    ) -> SessionHandle {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let entry = Arc::new(SessionEntry {
+            id,
+            transport,
+            peer_addr,
+            share_id,
+            started_at,
+            role: AtomicU8::new(role.min(max_role) as u8),
+            max_role,
+            media: Mutex::new(SessionMedia::default()),
+            frames_sent: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            commands: Mutex::new(Vec::new()),
+            held_inputs: Mutex::new(None),
+        });
+
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.insert(id, entry.clone());
+        }
+        self.bump_generation();
+        log::info!(
+            "会话 #{} 已登记: {} {}, 角色 {}",
+            id,
+            transport,
+            peer_addr,
+            role
+        );
+
+        SessionHandle {
+            registry: self.clone(),
+            entry,
+        }
+    }
+
+    fn unregister(&self, id: u64) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(&id);
+        }
+        self.bump_generation();
+        log::info!("会话 #{} 已注销", id);
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    pub fn snapshot(&self) -> Vec<SessionSummary> {
+        let Ok(sessions) = self.sessions.lock() else {
+            return Vec::new();
+        };
+
+        let mut summaries: Vec<_> = sessions
+            .values()
+            .map(|entry| SessionSummary {
+                id: entry.id,
+                transport: entry.transport,
+                role: entry.role(),
+            })
+            .collect();
+        summaries.sort_by_key(|s| s.id);
+        summaries
+    }
+
+    pub fn details(&self) -> Vec<SessionDetails> {
+        let Ok(sessions) = self.sessions.lock() else {
+            return Vec::new();
+        };
+
+        let mut details: Vec<_> = sessions.values().map(|entry| entry.details()).collect();
+        details.sort_by_key(|d| d.id);
+        details
+    }
+
+    pub fn session_details(&self, id: u64) -> Option<SessionDetails> {
+        let sessions = self.sessions.lock().ok()?;
+        sessions.get(&id).map(|entry| entry.details())
+    }
+
+    /// 通过指定分享链接接入的在线会话
+    pub fn share_sessions(&self, share_id: u64) -> Vec<u64> {
+        let Ok(sessions) = self.sessions.lock() else {
+            return Vec::new();
+        };
+
+        sessions
+            .values()
+            .filter(|entry| entry.share_id == Some(share_id))
+            .map(|entry| entry.id)
+            .collect()
+    }
+
+    /// 向指定会话下发指令，会话不存在时返回 false
+    pub fn send_command(&self, id: u64, command: SessionCommand) -> bool {
+        let Ok(sessions) = self.sessions.lock() else {
+            return false;
+        };
+
+        match sessions.get(&id) {
+            Some(entry) => {
+                entry.push_command(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 向所有在线会话下发指令，返回会话数
+    pub fn broadcast_command(&self, command: SessionCommand) -> usize {
+        let Ok(sessions) = self.sessions.lock() else {
+            return 0;
+        };
+
+        for entry in sessions.values() {
+            entry.push_command(command.clone());
+        }
+        sessions.len()
+    }
+
+    /// 松开仍在线会话按下的按键与按钮（退出时会话未在宽限期内结束），返回松开的数量
+    pub fn release_held_inputs(&self) -> usize {
+        let held: Vec<_> = match self.sessions.lock() {
+            Ok(sessions) => sessions
+                .values()
+                .filter_map(|entry| {
+                    let held = entry.held_inputs.lock().ok()?.clone()?;
+                    Some((entry.id, held))
+                })
+                .collect(),
+            Err(_) => return 0,
+        };
+
+        let mut released = 0;
+        for (id, held) in held {
+            match held.release() {
+                Ok(count) => released += count,
+                Err(e) => log::warn!("松开会话 #{} 按下的按键失败: {}", id, e),
+            }
+        }
+        released
+    }
+
+    pub fn count(&self) -> usize {
+        self.sessions.lock().map(|s| s.len()).unwrap_or_default()
+    }
+
+    /// 管理接口直接设置会话角色（不经过会话间权限校验）
+    pub fn set_role(&self, id: u64, role: SessionRole) -> bool {
+        let Ok(sessions) = self.sessions.lock() else {
+            return false;
+        };
+
+        let Some(entry) = sessions.get(&id) else {
+            return false;
+        };
+        let applied = set_role(entry, role);
+        drop(sessions);
+
+        self.bump_generation();
+        log::info!("管理接口将会话 #{} 设为 {}", id, applied);
+        true
+    }
+
+    /// 处理会话发起的角色变更请求，按请求方当前角色校验权限
+    pub fn apply_role_request(
+        &self,
+        requester_id: u64,
+        request: RoleRequest,
+    ) -> Result<(), String> {
+        let sessions = self.sessions.lock().map_err(|e| e.to_string())?;
+        let requester = sessions
+            .get(&requester_id)
+            .ok_or_else(|| "请求方会话不存在".to_string())?;
+        let requester_role = requester.role();
+
+        match request {
+            RoleRequest::HandOff { target } => {
+                if !requester_role.can_control() {
+                    return Err("观看者无法移交控制权".to_string());
+                }
+                if target == requester_id {
+                    return Err("不能将控制权移交给自己".to_string());
+                }
+                let target_entry = sessions
+                    .get(&target)
+                    .ok_or_else(|| format!("会话 #{} 不存在", target))?;
+                if target_entry.max_role < SessionRole::Controller {
+                    return Err(format!("会话 #{} 仅被授权观看，无法接收控制权", target));
+                }
+
+                if target_entry.role() < SessionRole::Controller {
+                    set_role(target_entry, SessionRole::Controller);
+                }
+                if requester_role == SessionRole::Controller {
+                    set_role(requester, SessionRole::Viewer);
+                }
+                log::info!("会话 #{} 将控制权移交给会话 #{}", requester_id, target);
+            }
+            RoleRequest::Set { target, role } => {
+                if requester_role != SessionRole::Admin {
+                    return Err("仅管理员可以设置其他会话的角色".to_string());
+                }
+                if target == requester_id {
+                    return Err("不能修改自己的角色".to_string());
+                }
+                let role = SessionRole::from_client_name(&role)
+                    .ok_or_else(|| format!("未知角色: {}", role))?;
+                let target_entry = sessions
+                    .get(&target)
+                    .ok_or_else(|| format!("会话 #{} 不存在", target))?;
+
+                let applied = set_role(target_entry, role);
+                log::info!(
+                    "管理员会话 #{} 将会话 #{} 设为 {}",
+                    requester_id,
+                    target,
+                    applied
+                );
+            }
+        }
+
+        drop(sessions);
+        self.bump_generation();
+        Ok(())
+    }
+
+    fn bump_generation(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+/// 设置会话角色，超出授权范围时降到授予的最高角色，返回实际生效的角色
+fn set_role(entry: &SessionEntry, role: SessionRole) -> SessionRole {
+    let role = role.min(entry.max_role);
+    entry.role.store(role as u8, Ordering::Release);
+    role
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer() -> SocketAddr {
+        SocketAddr::from(([192, 168, 1, 20], 50000))
+    }
+
+    #[test]
+    fn handles_unregister_on_drop() {
+        let registry = Arc::new(SessionRegistry::new());
+        let generation = registry.generation();
+
+        let first = registry.register(
+            "WebSocket",
+            peer(),
+            SessionRole::Controller,
+            SessionRole::Admin,
+            None,
+        );
+        let second = registry.register(
+            "WebRTC",
+            peer(),
+            SessionRole::Viewer,
+            SessionRole::Admin,
+            Some(7),
+        );
+        assert_ne!(first.id(), second.id());
+        assert_eq!(registry.count(), 2);
+        assert!(registry.generation() > generation);
+        assert_eq!(registry.share_sessions(7), vec![second.id()]);
+
+        let ids: Vec<_> = registry.details().iter().map(|d| d.id).collect();
+        assert_eq!(ids, vec![first.id(), second.id()]);
+
+        let second_id = second.id();
+        drop(second);
+        assert_eq!(registry.count(), 1);
+        assert!(registry.session_details(second_id).is_none());
+    }
+
+    #[test]
+    fn commands_are_queued_per_session() {
+        let registry = Arc::new(SessionRegistry::new());
+        let first = registry.register(
+            "WebSocket",
+            peer(),
+            SessionRole::Controller,
+            SessionRole::Admin,
+            None,
+        );
+        let second = registry.register(
+            "WebSocket",
+            peer(),
+            SessionRole::Controller,
+            SessionRole::Admin,
+            None,
+        );
+
+        assert!(registry.send_command(first.id(), SessionCommand::SelectMonitor(1)));
+        assert!(!registry.send_command(999, SessionCommand::SelectMonitor(1)));
+        assert_eq!(registry.broadcast_command(SessionCommand::Shutdown), 2);
+
+        let commands = first.take_commands();
+        assert!(matches!(
+            commands.as_slice(),
+            [SessionCommand::SelectMonitor(1), SessionCommand::Shutdown]
+        ));
+        assert!(first.take_commands().is_empty());
+        assert!(matches!(
+            second.take_commands().as_slice(),
+            [SessionCommand::Shutdown]
+        ));
+    }
+
+    #[test]
+    fn hand_off_swaps_controller_and_viewer() {
+        let registry = Arc::new(SessionRegistry::new());
+        let controller = registry.register(
+            "WebSocket",
+            peer(),
+            SessionRole::Controller,
+            SessionRole::Admin,
+            None,
+        );
+        let viewer = registry.register(
+            "WebSocket",
+            peer(),
+            SessionRole::Viewer,
+            SessionRole::Admin,
+            None,
+        );
+
+        assert!(
+            registry
+                .apply_role_request(
+                    viewer.id(),
+                    RoleRequest::HandOff {
+                        target: controller.id()
+                    }
+                )
+                .is_err()
+        );
+        assert!(
+            registry
+                .apply_role_request(
+                    controller.id(),
+                    RoleRequest::HandOff {
+                        target: controller.id()
+                    }
+                )
+                .is_err()
+        );
+
+        registry
+            .apply_role_request(
+                controller.id(),
+                RoleRequest::HandOff {
+                    target: viewer.id(),
+                },
+            )
+            .unwrap();
+        assert_eq!(controller.role(), SessionRole::Viewer);
+        assert_eq!(viewer.role(), SessionRole::Controller);
+    }
+
+    #[test]
+    fn role_changes_stay_within_granted_role() {
+        let registry = Arc::new(SessionRegistry::new());
+        let admin = registry.register(
+            "WebSocket",
+            peer(),
+            SessionRole::Admin,
+            SessionRole::Admin,
+            None,
+        );
+        let controller = registry.register(
+            "WebSocket",
+            peer(),
+            SessionRole::Controller,
+            SessionRole::Controller,
+            None,
+        );
+        // 只读分享链接接入的会话
+        let shared = registry.register(
+            "WebRTC",
+            peer(),
+            SessionRole::Viewer,
+            SessionRole::Viewer,
+            Some(7),
+        );
+
+        assert!(
+            registry
+                .apply_role_request(
+                    controller.id(),
+                    RoleRequest::HandOff {
+                        target: shared.id(),
+                    },
+                )
+                .is_err()
+        );
+        assert_eq!(controller.role(), SessionRole::Controller);
+        assert_eq!(shared.role(), SessionRole::Viewer);
+
+        registry
+            .apply_role_request(
+                admin.id(),
+                RoleRequest::Set {
+                    target: shared.id(),
+                    role: "controller".to_string(),
+                },
+            )
+            .unwrap();
+        assert_eq!(shared.role(), SessionRole::Viewer);
+
+        assert!(registry.set_role(controller.id(), SessionRole::Admin));
+        assert_eq!(controller.role(), SessionRole::Controller);
+    }
+
+    #[test]
+    fn only_admins_set_other_roles() {
+        let registry = Arc::new(SessionRegistry::new());
+        let admin = registry.register(
+            "WebSocket",
+            peer(),
+            SessionRole::Admin,
+            SessionRole::Admin,
+            None,
+        );
+        let controller = registry.register(
+            "WebSocket",
+            peer(),
+            SessionRole::Controller,
+            SessionRole::Admin,
+            None,
+        );
+
+        let demote = |requester: u64, target: u64| {
+            registry.apply_role_request(
+                requester,
+                RoleRequest::Set {
+                    target,
+                    role: "viewer".to_string(),
+                },
+            )
+        };
+        assert!(demote(controller.id(), admin.id()).is_err());
+        assert!(demote(admin.id(), admin.id()).is_err());
+        demote(admin.id(), controller.id()).unwrap();
+        assert_eq!(controller.role(), SessionRole::Viewer);
+
+        assert!(registry.set_role(controller.id(), SessionRole::Admin));
+        assert_eq!(controller.role(), SessionRole::Admin);
+        assert!(!registry.set_role(999, SessionRole::Admin));
+    }
+
+    #[test]
+    fn releases_held_inputs_of_live_sessions() {
+        let registry = Arc::new(SessionRegistry::new());
+        let _detached = registry.register(
+            "WebSocket",
+            peer(),
+            SessionRole::Viewer,
+            SessionRole::Admin,
+            None,
+        );
+        assert_eq!(registry.release_held_inputs(), 0);
+
+        let controller = registry.register(
+            "WebSocket",
+            peer(),
+            SessionRole::Controller,
+            SessionRole::Admin,
+            None,
+        );
+        controller.attach_held_inputs(Arc::new(HeldInputs::default()));
+        // 没有按下的按键时不注入任何输入
+        assert_eq!(registry.release_held_inputs(), 0);
+    }
+}
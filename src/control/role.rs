@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 会话角色（按权限从低到高排列）
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionRole {
+    /// 仅观看，输入帧会被拒绝
+    Viewer = 0,
+    /// 可注入鼠标/键盘输入
+    Controller = 1,
+    /// 可注入输入，并可调整其他会话的角色
+    Admin = 2,
+}
+
+/// 未携带凭据的连接默认授予的角色
+pub const DEFAULT_GRANTED_ROLE: SessionRole = SessionRole::Controller;
+
+impl SessionRole {
+    pub fn from_client_name(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "viewer" | "view" => Some(Self::Viewer),
+            "controller" | "control" => Some(Self::Controller),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn as_client_name(self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Controller => "controller",
+            Self::Admin => "admin",
+        }
+    }
+
+    pub fn from_u8(raw: u8) -> Self {
+        match raw {
+            2 => Self::Admin,
+            1 => Self::Controller,
+            _ => Self::Viewer,
+        }
+    }
+
+    /// 是否允许注入远程输入
+    pub fn can_control(self) -> bool {
+        self >= Self::Controller
+    }
+
+    /// 连接时确定角色：客户端只能请求不高于授予角色的权限
+    pub fn negotiate(granted: Self, requested: Option<&str>) -> Self {
+        match requested.and_then(Self::from_client_name) {
+            Some(requested) => requested.min(granted),
+            None => granted,
+        }
+    }
+}
+
+impl fmt::Display for SessionRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_client_name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_client_names() {
+        assert_eq!(
+            SessionRole::from_client_name(" View "),
+            Some(SessionRole::Viewer)
+        );
+        assert_eq!(
+            SessionRole::from_client_name("CONTROL"),
+            Some(SessionRole::Controller)
+        );
+        assert_eq!(
+            SessionRole::from_client_name("admin"),
+            Some(SessionRole::Admin)
+        );
+        assert_eq!(SessionRole::from_client_name("root"), None);
+
+        for role in [
+            SessionRole::Viewer,
+            SessionRole::Controller,
+            SessionRole::Admin,
+        ] {
+            assert_eq!(
+                SessionRole::from_client_name(role.as_client_name()),
+                Some(role)
+            );
+            assert_eq!(SessionRole::from_u8(role as u8), role);
+        }
+        assert_eq!(SessionRole::from_u8(200), SessionRole::Viewer);
+    }
+
+    #[test]
+    fn only_controllers_and_admins_can_control() {
+        assert!(!SessionRole::Viewer.can_control());
+        assert!(SessionRole::Controller.can_control());
+        assert!(SessionRole::Admin.can_control());
+    }
+
+    #[test]
+    fn negotiation_never_exceeds_granted_role() {
+        assert_eq!(
+            SessionRole::negotiate(SessionRole::Controller, Some("admin")),
+            SessionRole::Controller
+        );
+        assert_eq!(
+            SessionRole::negotiate(SessionRole::Admin, Some("viewer")),
+            SessionRole::Viewer
+        );
+        assert_eq!(
+            SessionRole::negotiate(SessionRole::Viewer, None),
+            SessionRole::Viewer
+        );
+        // 无法识别的请求按授予角色处理
+        assert_eq!(
+            SessionRole::negotiate(SessionRole::Controller, Some("owner")),
+            SessionRole::Controller
+        );
+    }
+}
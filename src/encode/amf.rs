@@ -1,12 +1,13 @@
 use ffmpeg_next as ffmpeg;
 use ffmpeg_next::codec;
 use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling;
 use ffmpeg_next::{Dictionary, Rational};
 use std::fmt;
 use std::time::Instant;
 
 /// AMF 视频编码格式
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum VideoCodec {
     Av1,
     Avc,
@@ -14,6 +15,8 @@ pub enum VideoCodec {
 }
 
 impl VideoCodec {
+    pub const ALL: [Self; 3] = [Self::Av1, Self::Avc, Self::Hevc];
+
     pub fn from_client_name(raw: &str) -> Option<Self> {
         match raw.trim().to_ascii_lowercase().as_str() {
             "av1" => Some(Self::Av1),
@@ -62,6 +65,16 @@ pub struct AmfEncoder {
     height: u32,
     /// 复用 NV12 frame，避免每帧重新分配
     nv12_frame: ffmpeg::frame::Video,
+    /// 输入分辨率与编码分辨率不一致时使用的缩放器（simulcast 低档位）
+    scaler: Option<Nv12Scaler>,
+}
+
+/// NV12 → NV12 缩放器，以及复用的源分辨率 frame
+struct Nv12Scaler {
+    context: scaling::Context,
+    input_frame: ffmpeg::frame::Video,
+    input_width: u32,
+    input_height: u32,
 }
 
 /// 编码后的帧数据
@@ -100,6 +113,26 @@ impl Default for EncoderConfig {
 impl AmfEncoder {
     /// 创建 AMF 编码器（直接接受 NV12 输入，无 swscale 色彩转换开销）
     pub fn new(config: &EncoderConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::new_scaled(config, config.width, config.height)
+    }
+
+    /// 以默认参数试建一次编码器，检查当前 GPU 与 FFmpeg 是否支持该编码格式
+    pub fn probe(codec: VideoCodec) -> Result<(), Box<dyn std::error::Error>> {
+        Self::new(&EncoderConfig {
+            codec,
+            ..EncoderConfig::default()
+        })
+        .map(|_| ())
+    }
+
+    /// 创建 AMF 编码器，输入为 `input_width`×`input_height` 的 NV12 数据
+    ///
+    /// 输入分辨率与 `config` 不一致时，先经 swscale 缩放到编码分辨率（仅缩放，不做色彩转换）
+    pub fn new_scaled(
+        config: &EncoderConfig,
+        input_width: u32,
+        input_height: u32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
         ffmpeg::init()?;
 
         let encoder_name = config.codec.ffmpeg_encoder_name();
@@ -164,12 +197,41 @@ impl AmfEncoder {
 
         let nv12_frame = ffmpeg::frame::Video::new(Pixel::NV12, config.width, config.height);
 
+        let scaler = if input_width != config.width || input_height != config.height {
+            let context = scaling::Context::get(
+                Pixel::NV12,
+                input_width,
+                input_height,
+                Pixel::NV12,
+                config.width,
+                config.height,
+                scaling::Flags::FAST_BILINEAR,
+            )?;
+            log::info!(
+                "{} 编码器启用缩放: {}x{} → {}x{}",
+                config.codec,
+                input_width,
+                input_height,
+                config.width,
+                config.height
+            );
+            Some(Nv12Scaler {
+                context,
+                input_frame: ffmpeg::frame::Video::new(Pixel::NV12, input_width, input_height),
+                input_width,
+                input_height,
+            })
+        } else {
+            None
+        };
+
         Ok(Self {
             encoder,
             frame_index: 0,
             width: config.width,
             height: config.height,
             nv12_frame,
+            scaler,
         })
     }
 
@@ -183,32 +245,24 @@ impl AmfEncoder {
     ) -> Result<Vec<EncodedFrame>, Box<dyn std::error::Error>> {
         let encode_start = Instant::now();
 
-        // 将 NV12 字节流写入 ffmpeg frame——Y 面
-        let y_stride = self.nv12_frame.stride(0);
-        let uv_stride = self.nv12_frame.stride(1);
-        let width = self.width as usize;
-        let height = self.height as usize;
-
-        {
-            let y_dst = self.nv12_frame.data_mut(0);
-            let y_src = &nv12_data[..width * height];
-            for row in 0..height {
-                let src = &y_src[row * width..(row + 1) * width];
-                let dst_off = row * y_stride;
-                y_dst[dst_off..dst_off + width].copy_from_slice(src);
-            }
-        }
-
-        // UV 面（交错，每行 width 字节，高 height/2）
-        {
-            let uv_dst = self.nv12_frame.data_mut(1);
-            let uv_src = &nv12_data[width * height..];
-            let uv_rows = height / 2;
-            for row in 0..uv_rows {
-                let src = &uv_src[row * width..(row + 1) * width];
-                let dst_off = row * uv_stride;
-                uv_dst[dst_off..dst_off + width].copy_from_slice(src);
+        match self.scaler.as_mut() {
+            Some(scaler) => {
+                copy_nv12_into_frame(
+                    &mut scaler.input_frame,
+                    nv12_data,
+                    scaler.input_width as usize,
+                    scaler.input_height as usize,
+                );
+                scaler
+                    .context
+                    .run(&scaler.input_frame, &mut self.nv12_frame)?;
             }
+            None => copy_nv12_into_frame(
+                &mut self.nv12_frame,
+                nv12_data,
+                self.width as usize,
+                self.height as usize,
+            ),
         }
 
         self.nv12_frame.set_pts(Some(self.frame_index));
@@ -257,3 +311,38 @@ impl AmfEncoder {
         Ok(encoded_frames)
     }
 }
+
+/// 将紧凑排列的 NV12 字节流按 frame 的行跨度写入
+///
+/// `nv12_data` 布局：Y 面 width×height 字节，之后 UV 面 width×height/2 字节（交错）
+fn copy_nv12_into_frame(
+    frame: &mut ffmpeg::frame::Video,
+    nv12_data: &[u8],
+    width: usize,
+    height: usize,
+) {
+    let y_stride = frame.stride(0);
+    let uv_stride = frame.stride(1);
+
+    {
+        let y_dst = frame.data_mut(0);
+        let y_src = &nv12_data[..width * height];
+        for row in 0..height {
+            let src = &y_src[row * width..(row + 1) * width];
+            let dst_off = row * y_stride;
+            y_dst[dst_off..dst_off + width].copy_from_slice(src);
+        }
+    }
+
+    // UV 面（交错，每行 width 字节，高 height/2）
+    {
+        let uv_dst = frame.data_mut(1);
+        let uv_src = &nv12_data[width * height..];
+        let uv_rows = height / 2;
+        for row in 0..uv_rows {
+            let src = &uv_src[row * width..(row + 1) * width];
+            let dst_off = row * uv_stride;
+            uv_dst[dst_off..dst_off + width].copy_from_slice(src);
+        }
+    }
+}
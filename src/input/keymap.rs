@@ -0,0 +1,142 @@
+/// W3C `KeyboardEvent.code` 到物理按键扫描码的映射
+///
+/// `code` 表示按键的物理位置，与客户端键盘布局无关；主机端再按自身布局解释扫描码，
+/// 因此非美式布局、小键盘与多媒体键都能得到正确结果。
+struct KeyMapping {
+    code: &'static str,
+    /// Windows 扫描码集 1；高字节为 0xE0 表示扩展键
+    scancode: u16,
+}
+
+/// 扩展键扫描码的前缀字节
+const EXTENDED_PREFIX: u16 = 0xE000;
+
+const fn key(code: &'static str, scancode: u16) -> KeyMapping {
+    KeyMapping { code, scancode }
+}
+
+#[rustfmt::skip]
+const KEY_MAPPINGS: &[KeyMapping] = &[
+    // 字母
+    key("KeyA", 0x001E), key("KeyB", 0x0030), key("KeyC", 0x002E),
+    key("KeyD", 0x0020), key("KeyE", 0x0012), key("KeyF", 0x0021),
+    key("KeyG", 0x0022), key("KeyH", 0x0023), key("KeyI", 0x0017),
+    key("KeyJ", 0x0024), key("KeyK", 0x0025), key("KeyL", 0x0026),
+    key("KeyM", 0x0032), key("KeyN", 0x0031), key("KeyO", 0x0018),
+    key("KeyP", 0x0019), key("KeyQ", 0x0010), key("KeyR", 0x0013),
+    key("KeyS", 0x001F), key("KeyT", 0x0014), key("KeyU", 0x0016),
+    key("KeyV", 0x002F), key("KeyW", 0x0011), key("KeyX", 0x002D),
+    key("KeyY", 0x0015), key("KeyZ", 0x002C),
+    // 数字行
+    key("Digit1", 0x0002), key("Digit2", 0x0003), key("Digit3", 0x0004),
+    key("Digit4", 0x0005), key("Digit5", 0x0006), key("Digit6", 0x0007),
+    key("Digit7", 0x0008), key("Digit8", 0x0009), key("Digit9", 0x000A),
+    key("Digit0", 0x000B),
+    // 符号与编辑键
+    key("Enter", 0x001C), key("Escape", 0x0001), key("Backspace", 0x000E),
+    key("Tab", 0x000F), key("Space", 0x0039), key("Minus", 0x000C),
+    key("Equal", 0x000D), key("BracketLeft", 0x001A), key("BracketRight", 0x001B),
+    key("Backslash", 0x002B), key("Semicolon", 0x0027), key("Quote", 0x0028),
+    key("Backquote", 0x0029), key("Comma", 0x0033), key("Period", 0x0034),
+    key("Slash", 0x0035), key("CapsLock", 0x003A),
+    // 国际布局附加键
+    key("IntlBackslash", 0x0056), key("IntlRo", 0x0073), key("IntlYen", 0x007D),
+    key("KanaMode", 0x0070), key("Convert", 0x0079), key("NonConvert", 0x007B),
+    key("Lang1", 0x0072), key("Lang2", 0x0071),
+    // 功能键
+    key("F1", 0x003B), key("F2", 0x003C), key("F3", 0x003D),
+    key("F4", 0x003E), key("F5", 0x003F), key("F6", 0x0040),
+    key("F7", 0x0041), key("F8", 0x0042), key("F9", 0x0043),
+    key("F10", 0x0044), key("F11", 0x0057), key("F12", 0x0058),
+    key("F13", 0x0064), key("F14", 0x0065), key("F15", 0x0066),
+    key("F16", 0x0067), key("F17", 0x0068), key("F18", 0x0069),
+    key("F19", 0x006A), key("F20", 0x006B), key("F21", 0x006C),
+    key("F22", 0x006D), key("F23", 0x006E), key("F24", 0x0076),
+    key("PrintScreen", 0xE037), key("ScrollLock", 0x0046), key("Pause", 0x0045),
+    // 导航键
+    key("Insert", 0xE052), key("Home", 0xE047), key("PageUp", 0xE049),
+    key("Delete", 0xE053), key("End", 0xE04F), key("PageDown", 0xE051),
+    key("ArrowRight", 0xE04D), key("ArrowLeft", 0xE04B),
+    key("ArrowDown", 0xE050), key("ArrowUp", 0xE048),
+    // 小键盘
+    key("NumLock", 0xE045), key("NumpadDivide", 0xE035),
+    key("NumpadMultiply", 0x0037), key("NumpadSubtract", 0x004A),
+    key("NumpadAdd", 0x004E), key("NumpadEnter", 0xE01C),
+    key("Numpad1", 0x004F), key("Numpad2", 0x0050), key("Numpad3", 0x0051),
+    key("Numpad4", 0x004B), key("Numpad5", 0x004C), key("Numpad6", 0x004D),
+    key("Numpad7", 0x0047), key("Numpad8", 0x0048), key("Numpad9", 0x0049),
+    key("Numpad0", 0x0052), key("NumpadDecimal", 0x0053),
+    key("NumpadEqual", 0x0059), key("NumpadComma", 0x007E),
+    // 修饰键
+    key("ControlLeft", 0x001D), key("ShiftLeft", 0x002A), key("AltLeft", 0x0038),
+    key("MetaLeft", 0xE05B), key("ControlRight", 0xE01D),
+    key("ShiftRight", 0x0036), key("AltRight", 0xE038),
+    key("MetaRight", 0xE05C), key("ContextMenu", 0xE05D),
+    // 多媒体与浏览器键
+    key("AudioVolumeMute", 0xE020), key("AudioVolumeDown", 0xE02E),
+    key("AudioVolumeUp", 0xE030), key("MediaTrackNext", 0xE019),
+    key("MediaTrackPrevious", 0xE010), key("MediaStop", 0xE024),
+    key("MediaPlayPause", 0xE022), key("MediaSelect", 0xE06D),
+    key("LaunchMail", 0xE06C), key("LaunchApp1", 0xE06B),
+    key("LaunchApp2", 0xE021), key("BrowserSearch", 0xE065),
+    key("BrowserHome", 0xE032), key("BrowserBack", 0xE06A),
+    key("BrowserForward", 0xE069), key("BrowserStop", 0xE068),
+    key("BrowserRefresh", 0xE067), key("BrowserFavorites", 0xE066),
+    // 电源键
+    key("Power", 0xE05E), key("Sleep", 0xE05F), key("WakeUp", 0xE063),
+];
+
+fn find(code: &str) -> Option<&'static KeyMapping> {
+    KEY_MAPPINGS.iter().find(|mapping| mapping.code == code)
+}
+
+/// 按键的 Windows 扫描码：(扫描码低字节, 是否扩展键)
+pub fn windows_scancode(code: &str) -> Option<(u16, bool)> {
+    find(code).map(|mapping| {
+        (
+            mapping.scancode & 0x00FF,
+            mapping.scancode & 0xFF00 == EXTENDED_PREFIX,
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn maps_physical_keys() {
+        assert_eq!(windows_scancode("KeyA"), Some((0x1E, false)));
+        assert_eq!(windows_scancode("Digit0"), Some((0x0B, false)));
+        assert_eq!(windows_scancode("IntlBackslash"), Some((0x56, false)));
+        assert_eq!(windows_scancode("NumpadEnter"), Some((0x1C, true)));
+        assert_eq!(windows_scancode("ControlRight"), Some((0x1D, true)));
+        assert_eq!(windows_scancode("NumLock"), Some((0x45, true)));
+        assert_eq!(windows_scancode("Pause"), Some((0x45, false)));
+    }
+
+    #[test]
+    fn unknown_codes_are_unmapped() {
+        assert_eq!(windows_scancode(""), None);
+        assert_eq!(windows_scancode("keya"), None);
+        assert_eq!(windows_scancode("Fn"), None);
+    }
+
+    #[test]
+    fn mappings_are_unique() {
+        let mut codes = HashSet::new();
+        let mut scancodes = HashSet::new();
+        for mapping in KEY_MAPPINGS {
+            assert!(codes.insert(mapping.code), "重复的 code: {}", mapping.code);
+            assert!(
+                scancodes.insert(mapping.scancode),
+                "重复的扫描码: {:#06x}",
+                mapping.scancode
+            );
+            let prefix = mapping.scancode & 0xFF00;
+            assert!(prefix == 0 || prefix == EXTENDED_PREFIX, "{}", mapping.code);
+            assert_ne!(mapping.scancode & 0x00FF, 0, "{}", mapping.code);
+        }
+    }
+}
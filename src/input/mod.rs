@@ -1 +1,3 @@
+pub mod keymap;
+pub mod pointer;
 pub mod win32;
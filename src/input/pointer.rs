@@ -0,0 +1,423 @@
+use serde::Deserialize;
+use windows::Win32::Foundation::{POINT, RECT};
+use windows::Win32::UI::Controls::{
+    CreateSyntheticPointerDevice, DestroySyntheticPointerDevice, HSYNTHETICPOINTERDEVICE,
+    InjectSyntheticPointerInput, POINTER_FEEDBACK_DEFAULT, POINTER_TYPE_INFO, POINTER_TYPE_INFO_0,
+};
+use windows::Win32::UI::Input::Pointer::{
+    POINTER_FLAG_CANCELED, POINTER_FLAG_DOWN, POINTER_FLAG_INCONTACT, POINTER_FLAG_INRANGE,
+    POINTER_FLAG_PRIMARY, POINTER_FLAG_UP, POINTER_FLAG_UPDATE, POINTER_FLAGS, POINTER_INFO,
+    POINTER_PEN_INFO, POINTER_TOUCH_INFO,
+};
+use windows::Win32::UI::WindowsAndMessaging::{POINTER_INPUT_TYPE, PT_PEN, PT_TOUCH};
+
+/// 同时注入的最大触控点数，客户端分配的触控点 id 需小于该值
+pub const MAX_TOUCH_CONTACTS: u32 = 10;
+
+/// 触控与笔的压力范围为 0..=1024
+const MAX_PRESSURE: f32 = 1024.0;
+
+const TOUCH_MASK_CONTACTAREA: u32 = 0x0001;
+const TOUCH_MASK_PRESSURE: u32 = 0x0004;
+const PEN_FLAG_BARREL: u32 = 0x0001;
+const PEN_FLAG_INVERTED: u32 = 0x0002;
+const PEN_FLAG_ERASER: u32 = 0x0004;
+const PEN_MASK_PRESSURE: u32 = 0x0001;
+const PEN_MASK_TILT_X: u32 = 0x0004;
+const PEN_MASK_TILT_Y: u32 = 0x0008;
+
+/// 触控点在本次输入中的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContactState {
+    Down,
+    Move,
+    Up,
+    Cancel,
+}
+
+/// 客户端上报的单个触控点，坐标与尺寸均为相对当前显示器的 0..1 比例
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct TouchContact {
+    pub id: u32,
+    pub x: f32,
+    pub y: f32,
+    pub state: ContactState,
+    #[serde(default)]
+    pub primary: bool,
+    /// 0..1，缺省时不上报压力
+    #[serde(default)]
+    pub pressure: Option<f32>,
+    #[serde(default)]
+    pub width: Option<f32>,
+    #[serde(default)]
+    pub height: Option<f32>,
+}
+
+/// 笔在本次输入中的状态：悬停、落笔、拖动、抬笔、离开感应范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PenState {
+    Hover,
+    Down,
+    Move,
+    Up,
+    Leave,
+}
+
+impl PenState {
+    pub fn in_contact(self) -> bool {
+        matches!(self, Self::Down | Self::Move)
+    }
+}
+
+/// 客户端上报的笔输入，坐标为相对当前显示器的 0..1 比例
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PenSample {
+    pub x: f32,
+    pub y: f32,
+    pub state: PenState,
+    #[serde(default)]
+    pub pressure: Option<f32>,
+    /// 倾角（度，-90..90）
+    #[serde(default)]
+    pub tilt_x: i32,
+    #[serde(default)]
+    pub tilt_y: i32,
+    #[serde(default)]
+    pub eraser: bool,
+    /// 笔杆按钮
+    #[serde(default)]
+    pub barrel: bool,
+}
+
+/// 已换算为桌面坐标的触控点
+#[derive(Debug, Clone, Copy)]
+pub struct TouchPoint {
+    pub location: POINT,
+    pub width: i32,
+    pub height: i32,
+    pub pressure: Option<f32>,
+    pub primary: bool,
+}
+
+/// Windows 合成指针设备（Windows 10 1809+），用于注入原生触控与笔输入
+pub struct SyntheticPointer {
+    device: HSYNTHETICPOINTERDEVICE,
+}
+
+impl SyntheticPointer {
+    pub fn touch() -> Result<Self, String> {
+        Self::create(PT_TOUCH, MAX_TOUCH_CONTACTS)
+    }
+
+    pub fn pen() -> Result<Self, String> {
+        Self::create(PT_PEN, 1)
+    }
+
+    fn create(pointer_type: POINTER_INPUT_TYPE, max_count: u32) -> Result<Self, String> {
+        let device = unsafe {
+            CreateSyntheticPointerDevice(pointer_type, max_count, POINTER_FEEDBACK_DEFAULT)
+        }
+        .map_err(|e| format!("创建合成指针设备失败: {}", e))?;
+        Ok(Self { device })
+    }
+
+    /// 注入一帧指针输入；触控帧需包含所有仍在接触中的触控点
+    pub fn inject(&self, frame: &[POINTER_TYPE_INFO]) -> Result<(), String> {
+        if frame.is_empty() {
+            return Ok(());
+        }
+        unsafe { InjectSyntheticPointerInput(self.device, frame) }
+            .map_err(|e| format!("注入合成指针输入失败: {}", e))
+    }
+}
+
+impl Drop for SyntheticPointer {
+    fn drop(&mut self) {
+        unsafe { DestroySyntheticPointerDevice(self.device) };
+    }
+}
+
+fn touch_flags(state: ContactState) -> POINTER_FLAGS {
+    match state {
+        ContactState::Down => POINTER_FLAG_DOWN | POINTER_FLAG_INRANGE | POINTER_FLAG_INCONTACT,
+        ContactState::Move => POINTER_FLAG_UPDATE | POINTER_FLAG_INRANGE | POINTER_FLAG_INCONTACT,
+        ContactState::Up => POINTER_FLAG_UP,
+        ContactState::Cancel => POINTER_FLAG_UP | POINTER_FLAG_CANCELED,
+    }
+}
+
+pub fn touch_info(id: u32, point: &TouchPoint, state: ContactState) -> POINTER_TYPE_INFO {
+    let mut flags = touch_flags(state);
+    if point.primary {
+        flags |= POINTER_FLAG_PRIMARY;
+    }
+
+    let mut touch_mask = 0;
+    let mut contact = RECT::default();
+    if point.width > 0 && point.height > 0 {
+        touch_mask |= TOUCH_MASK_CONTACTAREA;
+        contact = RECT {
+            left: point.location.x - point.width / 2,
+            top: point.location.y - point.height / 2,
+            right: point.location.x + (point.width + 1) / 2,
+            bottom: point.location.y + (point.height + 1) / 2,
+        };
+    }
+    let pressure = point.pressure.map_or(0, |pressure| {
+        touch_mask |= TOUCH_MASK_PRESSURE;
+        to_pressure(pressure)
+    });
+
+    POINTER_TYPE_INFO {
+        r#type: PT_TOUCH,
+        Anonymous: POINTER_TYPE_INFO_0 {
+            touchInfo: POINTER_TOUCH_INFO {
+                pointerInfo: pointer_info(PT_TOUCH, id, point.location, flags),
+                touchMask: touch_mask,
+                rcContact: contact,
+                pressure,
+                ..Default::default()
+            },
+        },
+    }
+}
+
+fn pen_flags(state: PenState) -> POINTER_FLAGS {
+    match state {
+        PenState::Hover => POINTER_FLAG_UPDATE | POINTER_FLAG_INRANGE,
+        PenState::Down => POINTER_FLAG_DOWN | POINTER_FLAG_INRANGE | POINTER_FLAG_INCONTACT,
+        PenState::Move => POINTER_FLAG_UPDATE | POINTER_FLAG_INRANGE | POINTER_FLAG_INCONTACT,
+        PenState::Up => POINTER_FLAG_UP | POINTER_FLAG_INRANGE,
+        PenState::Leave => POINTER_FLAG_UPDATE,
+    }
+}
+
+/// `state` 为按主机端笔状态修正后的状态，其余属性取自客户端采样
+pub fn pen_info(location: POINT, state: PenState, sample: &PenSample) -> POINTER_TYPE_INFO {
+    let mut pen_flags_value = 0;
+    if sample.barrel {
+        pen_flags_value |= PEN_FLAG_BARREL;
+    }
+    // 橡皮擦端悬停时为 INVERTED，接触时为 ERASER
+    if sample.eraser {
+        pen_flags_value |= if state.in_contact() {
+            PEN_FLAG_ERASER
+        } else {
+            PEN_FLAG_INVERTED
+        };
+    }
+
+    let mut pen_mask = PEN_MASK_TILT_X | PEN_MASK_TILT_Y;
+    let pressure = match sample.pressure {
+        Some(pressure) if state.in_contact() => {
+            pen_mask |= PEN_MASK_PRESSURE;
+            to_pressure(pressure)
+        }
+        _ => 0,
+    };
+
+    POINTER_TYPE_INFO {
+        r#type: PT_PEN,
+        Anonymous: POINTER_TYPE_INFO_0 {
+            penInfo: POINTER_PEN_INFO {
+                pointerInfo: pointer_info(
+                    PT_PEN,
+                    0,
+                    location,
+                    pen_flags(state) | POINTER_FLAG_PRIMARY,
+                ),
+                penFlags: pen_flags_value,
+                penMask: pen_mask,
+                pressure,
+                tiltX: sample.tilt_x.clamp(-90, 90),
+                tiltY: sample.tilt_y.clamp(-90, 90),
+                ..Default::default()
+            },
+        },
+    }
+}
+
+fn pointer_info(
+    pointer_type: POINTER_INPUT_TYPE,
+    id: u32,
+    location: POINT,
+    flags: POINTER_FLAGS,
+) -> POINTER_INFO {
+    POINTER_INFO {
+        pointerType: pointer_type,
+        pointerId: id,
+        pointerFlags: flags,
+        ptPixelLocation: location,
+        ..Default::default()
+    }
+}
+
+fn to_pressure(pressure: f32) -> u32 {
+    (pressure.clamp(0.0, 1.0) * MAX_PRESSURE).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(state: PenState) -> PenSample {
+        PenSample {
+            x: 0.5,
+            y: 0.5,
+            state,
+            pressure: Some(0.5),
+            tilt_x: 120,
+            tilt_y: -30,
+            eraser: false,
+            barrel: false,
+        }
+    }
+
+    #[test]
+    fn scales_pressure() {
+        assert_eq!(to_pressure(0.0), 0);
+        assert_eq!(to_pressure(0.5), 512);
+        assert_eq!(to_pressure(1.0), 1024);
+        assert_eq!(to_pressure(-0.2), 0);
+        assert_eq!(to_pressure(3.0), 1024);
+    }
+
+    #[test]
+    fn pen_contact_states() {
+        assert!(PenState::Down.in_contact());
+        assert!(PenState::Move.in_contact());
+        for state in [PenState::Hover, PenState::Up, PenState::Leave] {
+            assert!(!state.in_contact());
+            assert_eq!(pen_flags(state) & POINTER_FLAG_INCONTACT, POINTER_FLAGS(0));
+        }
+        assert_eq!(
+            pen_flags(PenState::Down),
+            POINTER_FLAG_DOWN | POINTER_FLAG_INRANGE | POINTER_FLAG_INCONTACT
+        );
+        assert_eq!(pen_flags(PenState::Leave), POINTER_FLAG_UPDATE);
+    }
+
+    #[test]
+    fn touch_state_flags() {
+        assert_eq!(
+            touch_flags(ContactState::Move),
+            POINTER_FLAG_UPDATE | POINTER_FLAG_INRANGE | POINTER_FLAG_INCONTACT
+        );
+        assert_eq!(touch_flags(ContactState::Up), POINTER_FLAG_UP);
+        assert_eq!(
+            touch_flags(ContactState::Cancel),
+            POINTER_FLAG_UP | POINTER_FLAG_CANCELED
+        );
+    }
+
+    #[test]
+    fn builds_touch_contact_area() {
+        let point = TouchPoint {
+            location: POINT { x: 100, y: 200 },
+            width: 11,
+            height: 10,
+            pressure: Some(0.25),
+            primary: true,
+        };
+        let info = touch_info(3, &point, ContactState::Down);
+        let touch = unsafe { info.Anonymous.touchInfo };
+        assert_eq!(info.r#type, PT_TOUCH);
+        assert_eq!(touch.pointerInfo.pointerId, 3);
+        assert_eq!(
+            touch.pointerInfo.pointerFlags,
+            touch_flags(ContactState::Down) | POINTER_FLAG_PRIMARY
+        );
+        assert_eq!(
+            touch.touchMask,
+            TOUCH_MASK_CONTACTAREA | TOUCH_MASK_PRESSURE
+        );
+        assert_eq!(
+            (
+                touch.rcContact.left,
+                touch.rcContact.top,
+                touch.rcContact.right,
+                touch.rcContact.bottom
+            ),
+            (95, 195, 106, 205)
+        );
+        assert_eq!(touch.pressure, 256);
+
+        let bare = TouchPoint {
+            width: 0,
+            pressure: None,
+            primary: false,
+            ..point
+        };
+        let touch = unsafe { touch_info(4, &bare, ContactState::Move).Anonymous.touchInfo };
+        assert_eq!(touch.touchMask, 0);
+        assert_eq!(touch.pressure, 0);
+        assert_eq!(
+            touch.pointerInfo.pointerFlags & POINTER_FLAG_PRIMARY,
+            POINTER_FLAGS(0)
+        );
+    }
+
+    #[test]
+    fn pen_pressure_only_while_in_contact() {
+        let location = POINT { x: 10, y: 20 };
+        let pen = unsafe {
+            pen_info(location, PenState::Move, &sample(PenState::Move))
+                .Anonymous
+                .penInfo
+        };
+        assert_eq!(pen.pressure, 512);
+        assert_eq!(
+            pen.penMask,
+            PEN_MASK_PRESSURE | PEN_MASK_TILT_X | PEN_MASK_TILT_Y
+        );
+        assert_eq!((pen.tiltX, pen.tiltY), (90, -30));
+
+        let pen = unsafe {
+            pen_info(location, PenState::Hover, &sample(PenState::Down))
+                .Anonymous
+                .penInfo
+        };
+        assert_eq!(pen.pressure, 0);
+        assert_eq!(pen.penMask, PEN_MASK_TILT_X | PEN_MASK_TILT_Y);
+    }
+
+    #[test]
+    fn eraser_end_is_inverted_until_contact() {
+        let location = POINT { x: 10, y: 20 };
+        let eraser = PenSample {
+            eraser: true,
+            barrel: true,
+            ..sample(PenState::Hover)
+        };
+        let hover = unsafe {
+            pen_info(location, PenState::Hover, &eraser)
+                .Anonymous
+                .penInfo
+        };
+        assert_eq!(hover.penFlags, PEN_FLAG_BARREL | PEN_FLAG_INVERTED);
+        let down = unsafe {
+            pen_info(location, PenState::Down, &eraser)
+                .Anonymous
+                .penInfo
+        };
+        assert_eq!(down.penFlags, PEN_FLAG_BARREL | PEN_FLAG_ERASER);
+    }
+
+    #[test]
+    fn parses_client_samples() {
+        let contact: TouchContact =
+            serde_json::from_str(r#"{"id":1,"x":0.25,"y":0.75,"state":"cancel"}"#).unwrap();
+        assert_eq!(contact.state, ContactState::Cancel);
+        assert!(!contact.primary);
+        assert!(contact.pressure.is_none() && contact.width.is_none());
+
+        let pen: PenSample =
+            serde_json::from_str(r#"{"x":0.5,"y":0.5,"state":"leave","eraser":true}"#).unwrap();
+        assert_eq!(pen.state, PenState::Leave);
+        assert!(pen.eraser && !pen.barrel);
+        assert_eq!((pen.tilt_x, pen.tilt_y), (0, 0));
+        assert!(serde_json::from_str::<PenSample>(r#"{"x":0,"y":0,"state":"tap"}"#).is_err());
+    }
+}
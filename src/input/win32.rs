@@ -1,15 +1,27 @@
 use crate::capture::dda::MonitorInfo;
-use windows::Win32::Foundation::GetLastError;
+use crate::input::keymap;
+use crate::input::pointer::{
+    self, ContactState, MAX_TOUCH_CONTACTS, PenSample, PenState, SyntheticPointer, TouchContact,
+    TouchPoint,
+};
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use windows::Win32::Foundation::{GetLastError, POINT};
+use windows::Win32::System::Threading::{AttachThreadInput, GetCurrentThreadId};
 use windows::Win32::UI::Input::KeyboardAndMouse::{
-    INPUT, INPUT_0, INPUT_KEYBOARD, INPUT_MOUSE, KEYBD_EVENT_FLAGS, KEYBDINPUT, KEYEVENTF_KEYUP,
+    GetKeyState, GetKeyboardState, INPUT, INPUT_0, INPUT_KEYBOARD, INPUT_MOUSE, KEYBD_EVENT_FLAGS,
+    KEYBDINPUT, KEYEVENTF_EXTENDEDKEY, KEYEVENTF_KEYUP, KEYEVENTF_SCANCODE, KEYEVENTF_UNICODE,
     MOUSE_EVENT_FLAGS, MOUSEEVENTF_ABSOLUTE, MOUSEEVENTF_HWHEEL, MOUSEEVENTF_LEFTDOWN,
     MOUSEEVENTF_LEFTUP, MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP, MOUSEEVENTF_MOVE,
     MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP, MOUSEEVENTF_VIRTUALDESK, MOUSEEVENTF_WHEEL,
-    MOUSEEVENTF_XDOWN, MOUSEEVENTF_XUP, MOUSEINPUT, SendInput, VIRTUAL_KEY, VK_LCONTROL, VK_LMENU,
-    VK_LSHIFT, VK_LWIN, VK_RCONTROL, VK_RMENU, VK_RSHIFT, VK_RWIN,
+    MOUSEEVENTF_XDOWN, MOUSEEVENTF_XUP, MOUSEINPUT, SendInput, VIRTUAL_KEY, VK_CAPITAL, VK_NUMLOCK,
+    VK_SCROLL,
 };
 use windows::Win32::UI::WindowsAndMessaging::{
-    GetSystemMetrics, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN,
+    GetForegroundWindow, GetSystemMetrics, GetWindowThreadProcessId, SM_CXVIRTUALSCREEN,
+    SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN,
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -36,8 +48,106 @@ pub struct InputInjector {
     virtual_top: i32,
     virtual_width: i32,
     virtual_height: i32,
+    /// 已按下尚未松开的按键与鼠标按钮，会话结束时统一松开；
+    /// 同时登记到会话表，退出时会话线程未及时结束也能由主线程松开
+    held: Arc<HeldInputs>,
+    /// 相对鼠标模式：光标由主机应用（如游戏）控制，只注入位移
+    relative_mouse: Cell<bool>,
+    /// 相对位移中不足一像素的部分，累加到下一次移动
+    relative_remainder: Cell<(f32, f32)>,
+    /// 首次收到触控/笔输入时创建；系统不支持时为 `None`，退回鼠标模拟
+    touch_device: OnceCell<Option<SyntheticPointer>>,
+    pen_device: OnceCell<Option<SyntheticPointer>>,
+    /// 仍在接触中的触控点（合成触控每帧需包含全部触控点）
+    touches: RefCell<BTreeMap<u32, TouchPoint>>,
+    /// 笔尖接触屏幕时的位置
+    pen_contact: Cell<Option<POINT>>,
+    /// 鼠标模拟时驱动光标的触控点
+    emulated_touch: Cell<Option<u32>>,
+    /// 鼠标模拟时笔按下的鼠标按钮
+    emulated_pen_button: Cell<Option<u8>>,
+}
+
+/// 会话已按下的按键与鼠标按钮；SendInput 可在任意线程调用，因此可跨线程松开
+#[derive(Default)]
+pub struct HeldInputs {
+    state: Mutex<HeldState>,
+}
+
+#[derive(Default)]
+struct HeldState {
+    keys: BTreeSet<KeyStroke>,
+    buttons: BTreeSet<u8>,
+}
+
+/// 注入的按键：优先按物理位置发送扫描码，`code` 无法映射时退回浏览器的虚拟键码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum KeyStroke {
+    Scancode { scancode: u16, extended: bool },
+    VirtualKey(u16),
+}
+
+impl KeyStroke {
+    fn resolve(key_code: u16, code: Option<&str>) -> Option<Self> {
+        code.and_then(keymap::windows_scancode)
+            .map(|(scancode, extended)| Self::Scancode { scancode, extended })
+            .or_else(|| (key_code != 0).then_some(Self::VirtualKey(key_code)))
+    }
+}
+
+/// 文本中的换行与制表符按实际按键发送，使其在编辑器与表单中生效
+const ENTER_KEY: KeyStroke = KeyStroke::Scancode {
+    scancode: 0x1C,
+    extended: false,
+};
+const TAB_KEY: KeyStroke = KeyStroke::Scancode {
+    scancode: 0x0F,
+    extended: false,
+};
+
+/// 主机锁定键是否开启
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockKeys {
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
 }
 
+impl LockKeys {
+    /// 是否与目标状态一致，`None` 表示不关心
+    fn matches(
+        &self,
+        caps_lock: Option<bool>,
+        num_lock: Option<bool>,
+        scroll_lock: Option<bool>,
+    ) -> bool {
+        [
+            (self.caps_lock, caps_lock),
+            (self.num_lock, num_lock),
+            (self.scroll_lock, scroll_lock),
+        ]
+        .iter()
+        .all(|&(current, target)| target.is_none_or(|target| target == current))
+    }
+}
+
+const CAPS_LOCK_KEY: KeyStroke = KeyStroke::Scancode {
+    scancode: 0x3A,
+    extended: false,
+};
+const NUM_LOCK_KEY: KeyStroke = KeyStroke::Scancode {
+    scancode: 0x45,
+    extended: true,
+};
+const SCROLL_LOCK_KEY: KeyStroke = KeyStroke::Scancode {
+    scancode: 0x46,
+    extended: false,
+};
+
+/// 注入锁定键后等待系统应用切换的轮询间隔与次数
+const LOCK_KEYS_POLL_INTERVAL: Duration = Duration::from_millis(5);
+const LOCK_KEYS_POLL_ATTEMPTS: u32 = 20;
+
 const XBUTTON1_DATA: u32 = 0x0001;
 const XBUTTON2_DATA: u32 = 0x0002;
 
@@ -58,15 +168,53 @@ impl InputInjector {
                 virtual_top,
                 virtual_width,
                 virtual_height,
+                held: Arc::new(HeldInputs::default()),
+                relative_mouse: Cell::new(false),
+                relative_remainder: Cell::new((0.0, 0.0)),
+                touch_device: OnceCell::new(),
+                pen_device: OnceCell::new(),
+                touches: RefCell::new(BTreeMap::new()),
+                pen_contact: Cell::new(None),
+                emulated_touch: Cell::new(None),
+                emulated_pen_button: Cell::new(None),
             })
         }
     }
 
+    pub fn relative_mouse(&self) -> bool {
+        self.relative_mouse.get()
+    }
+
+    pub fn set_relative_mouse(&self, enabled: bool) {
+        self.relative_mouse.set(enabled);
+        self.relative_remainder.set((0.0, 0.0));
+    }
+
+    /// 相对模式下忽略客户端的绝对坐标，避免与应用自身的光标控制冲突
     pub fn move_mouse(&self, monitor: ActiveMonitor, x: f32, y: f32) -> Result<(), String> {
+        if self.relative_mouse.get() {
+            return Ok(());
+        }
         let (desktop_x, desktop_y) = self.to_desktop_point(monitor, x, y);
         self.send_mouse_move(desktop_x, desktop_y)
     }
 
+    /// 按主机像素注入相对位移，小数部分累加到后续移动
+    pub fn move_mouse_relative(&self, dx: f32, dy: f32) -> Result<(), String> {
+        if !dx.is_finite() || !dy.is_finite() {
+            return Ok(());
+        }
+
+        let ((move_x, move_y), remainder) =
+            split_relative_motion(self.relative_remainder.get(), dx, dy);
+        self.relative_remainder.set(remainder);
+        if move_x == 0 && move_y == 0 {
+            return Ok(());
+        }
+
+        send_inputs(&[mouse_input(move_x, move_y, 0, MOUSEEVENTF_MOVE)])
+    }
+
     pub fn mouse_button(
         &self,
         monitor: ActiveMonitor,
@@ -80,11 +228,19 @@ impl InputInjector {
             return Ok(());
         };
 
-        let inputs = [
-            self.mouse_input_absolute(desktop_x, desktop_y, MOUSEEVENTF_MOVE),
-            mouse_input(0, 0, button_data, button_flags),
-        ];
-        self.send_inputs(&inputs)
+        let button_input = mouse_input(0, 0, button_data, button_flags);
+        if self.relative_mouse.get() {
+            send_inputs(&[button_input])?;
+        } else {
+            let inputs = [
+                self.mouse_input_absolute(desktop_x, desktop_y, MOUSEEVENTF_MOVE),
+                button_input,
+            ];
+            send_inputs(&inputs)?;
+        }
+
+        self.held.set_button(button, down);
+        Ok(())
     }
 
     pub fn mouse_wheel(
@@ -98,7 +254,9 @@ impl InputInjector {
         let (desktop_x, desktop_y) = self.to_desktop_point(monitor, x, y);
 
         let mut inputs = Vec::with_capacity(3);
-        inputs.push(self.mouse_input_absolute(desktop_x, desktop_y, MOUSEEVENTF_MOVE));
+        if !self.relative_mouse.get() {
+            inputs.push(self.mouse_input_absolute(desktop_x, desktop_y, MOUSEEVENTF_MOVE));
+        }
 
         if delta_y != 0 {
             inputs.push(mouse_input(0, 0, delta_y as u32, MOUSEEVENTF_WHEEL));
@@ -107,7 +265,7 @@ impl InputInjector {
             inputs.push(mouse_input(0, 0, delta_x as u32, MOUSEEVENTF_HWHEEL));
         }
 
-        self.send_inputs(&inputs)
+        send_inputs(&inputs)
     }
 
     pub fn keyboard_key(
@@ -116,36 +274,218 @@ impl InputInjector {
         code: Option<&str>,
         down: bool,
     ) -> Result<(), String> {
-        if key_code == 0 {
+        let Some(key) = KeyStroke::resolve(key_code, code) else {
             return Ok(());
-        }
+        };
+        send_inputs(&[keyboard_input(key, down)])?;
 
-        let vk = map_virtual_key(key_code, code);
-        let flags = if down {
-            KEYBD_EVENT_FLAGS(0)
-        } else {
-            KEYEVENTF_KEYUP
+        self.held.set_key(key, down);
+        Ok(())
+    }
+
+    /// 注入多点触控；客户端只需上报状态变化的触控点，其余接触中的触控点由此补全
+    pub fn touch(&self, monitor: ActiveMonitor, contacts: &[TouchContact]) -> Result<(), String> {
+        let Some(device) = self
+            .touch_device
+            .get_or_init(|| open_synthetic_pointer(SyntheticPointer::touch, "触控"))
+        else {
+            return self.emulate_touch(monitor, contacts);
         };
 
-        let input = INPUT {
-            r#type: INPUT_KEYBOARD,
-            Anonymous: INPUT_0 {
-                ki: KEYBDINPUT {
-                    wVk: vk,
-                    wScan: 0,
-                    dwFlags: flags,
-                    time: 0,
-                    dwExtraInfo: 0,
+        let mut touches = self.touches.borrow_mut();
+        let mut changed = BTreeMap::new();
+        for contact in contacts.iter().filter(|c| c.id < MAX_TOUCH_CONTACTS) {
+            let known = touches.contains_key(&contact.id);
+            // 修正丢失的落下/抬起，保证主机端看到的触控序列完整
+            let state = match (contact.state, known) {
+                (ContactState::Up | ContactState::Cancel, false) => continue,
+                (ContactState::Move, false) => ContactState::Down,
+                (ContactState::Down, true) => ContactState::Move,
+                (state, _) => state,
+            };
+            let (x, y) = self.to_desktop_point(monitor, contact.x, contact.y);
+            touches.insert(
+                contact.id,
+                TouchPoint {
+                    location: POINT { x, y },
+                    width: to_pixels(contact.width, monitor.width),
+                    height: to_pixels(contact.height, monitor.height),
+                    pressure: contact.pressure,
+                    primary: contact.primary,
                 },
-            },
+            );
+            changed.insert(contact.id, state);
+        }
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        let frame: Vec<_> = touches
+            .iter()
+            .map(|(&id, point)| {
+                let state = changed.get(&id).copied().unwrap_or(ContactState::Move);
+                pointer::touch_info(id, point, state)
+            })
+            .collect();
+        touches.retain(|id, _| {
+            !matches!(
+                changed.get(id),
+                Some(ContactState::Up | ContactState::Cancel)
+            )
+        });
+        device.inject(&frame)
+    }
+
+    /// 注入笔输入（压力、倾角、橡皮擦与笔杆按钮）
+    pub fn pen(&self, monitor: ActiveMonitor, sample: &PenSample) -> Result<(), String> {
+        let Some(device) = self
+            .pen_device
+            .get_or_init(|| open_synthetic_pointer(SyntheticPointer::pen, "笔"))
+        else {
+            return self.emulate_pen(monitor, sample);
+        };
+
+        let (x, y) = self.to_desktop_point(monitor, sample.x, sample.y);
+        let location = POINT { x, y };
+        let state = match (sample.state, self.pen_contact.get().is_some()) {
+            (PenState::Move, false) => PenState::Down,
+            (PenState::Down, true) => PenState::Move,
+            (PenState::Up, false) => PenState::Hover,
+            (PenState::Leave, true) => PenState::Up,
+            (state, _) => state,
         };
+        device.inject(&[pointer::pen_info(location, state, sample)])?;
+        self.pen_contact.set(state.in_contact().then_some(location));
+        Ok(())
+    }
+
+    /// 不支持合成触控时，用第一个触控点模拟鼠标左键
+    fn emulate_touch(
+        &self,
+        monitor: ActiveMonitor,
+        contacts: &[TouchContact],
+    ) -> Result<(), String> {
+        for contact in contacts {
+            let driving = self.emulated_touch.get();
+            match contact.state {
+                ContactState::Down if driving.is_none() => {
+                    self.emulated_touch.set(Some(contact.id));
+                    self.mouse_button(monitor, contact.x, contact.y, 0, true)?;
+                }
+                ContactState::Move if driving == Some(contact.id) => {
+                    self.move_mouse(monitor, contact.x, contact.y)?;
+                }
+                ContactState::Up | ContactState::Cancel if driving == Some(contact.id) => {
+                    self.emulated_touch.set(None);
+                    self.mouse_button(monitor, contact.x, contact.y, 0, false)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// 不支持合成笔时模拟鼠标：笔尖为左键，按住笔杆按钮落笔为右键
+    fn emulate_pen(&self, monitor: ActiveMonitor, sample: &PenSample) -> Result<(), String> {
+        match (sample.state, self.emulated_pen_button.get()) {
+            (PenState::Down, None) => {
+                let button = if sample.barrel { 2 } else { 0 };
+                self.emulated_pen_button.set(Some(button));
+                self.mouse_button(monitor, sample.x, sample.y, button, true)
+            }
+            (PenState::Up | PenState::Leave, Some(button)) => {
+                self.emulated_pen_button.set(None);
+                self.mouse_button(monitor, sample.x, sample.y, button, false)
+            }
+            (PenState::Leave, None) => Ok(()),
+            _ => self.move_mouse(monitor, sample.x, sample.y),
+        }
+    }
+
+    /// 以 Unicode 字符方式键入文本，不依赖主机键盘布局；返回键入的字符数
+    pub fn type_text(&self, text: &str) -> Result<usize, String> {
+        let (inputs, typed) = text_inputs(text);
+        send_inputs(&inputs)?;
+        Ok(typed)
+    }
+
+    pub fn lock_keys(&self) -> LockKeys {
+        read_lock_keys()
+    }
+
+    /// 按下与目标状态不一致的锁定键（`None` 表示保持原样），不等待切换生效
+    pub fn toggle_lock_keys(
+        &self,
+        caps_lock: Option<bool>,
+        num_lock: Option<bool>,
+        scroll_lock: Option<bool>,
+    ) -> Result<(), String> {
+        let state = self.lock_keys();
+        let mut inputs = Vec::new();
+        for (current, target, key) in [
+            (state.caps_lock, caps_lock, CAPS_LOCK_KEY),
+            (state.num_lock, num_lock, NUM_LOCK_KEY),
+            (state.scroll_lock, scroll_lock, SCROLL_LOCK_KEY),
+        ] {
+            if target.is_some_and(|target| target != current) {
+                inputs.extend([keyboard_input(key, true), keyboard_input(key, false)]);
+            }
+        }
+        if inputs.is_empty() {
+            return Ok(());
+        }
+        send_inputs(&inputs)
+    }
+
+    /// 松开所有仍处于按下状态的按键与鼠标按钮，返回松开的数量
+    pub fn release_all(&self) -> Result<usize, String> {
+        self.emulated_touch.set(None);
+        self.emulated_pen_button.set(None);
+
+        let mut released = self.held.release()?;
+        released += self.lift_pointers()?;
+        Ok(released)
+    }
+
+    /// 供会话表登记的已按下输入集合
+    pub fn held_inputs(&self) -> Arc<HeldInputs> {
+        self.held.clone()
+    }
+
+    /// 取消仍在接触中的触控点并抬起笔，返回抬起的数量
+    fn lift_pointers(&self) -> Result<usize, String> {
+        let touches = std::mem::take(&mut *self.touches.borrow_mut());
+        let pen_contact = self.pen_contact.take();
+        let mut lifted = 0;
 
-        self.send_inputs(&[input])
+        if let Some(Some(device)) = self.touch_device.get() {
+            let frame: Vec<_> = touches
+                .iter()
+                .map(|(&id, point)| pointer::touch_info(id, point, ContactState::Cancel))
+                .collect();
+            device.inject(&frame)?;
+            lifted += frame.len();
+        }
+        if let (Some(Some(device)), Some(location)) = (self.pen_device.get(), pen_contact) {
+            let sample = PenSample {
+                x: 0.0,
+                y: 0.0,
+                state: PenState::Up,
+                pressure: None,
+                tilt_x: 0,
+                tilt_y: 0,
+                eraser: false,
+                barrel: false,
+            };
+            device.inject(&[pointer::pen_info(location, PenState::Up, &sample)])?;
+            lifted += 1;
+        }
+        Ok(lifted)
     }
 
     fn send_mouse_move(&self, desktop_x: i32, desktop_y: i32) -> Result<(), String> {
         let input = self.mouse_input_absolute(desktop_x, desktop_y, MOUSEEVENTF_MOVE);
-        self.send_inputs(&[input])
+        send_inputs(&[input])
     }
 
     fn mouse_input_absolute(
@@ -165,27 +505,6 @@ impl InputInjector {
         )
     }
 
-    fn send_inputs(&self, inputs: &[INPUT]) -> Result<(), String> {
-        if inputs.is_empty() {
-            return Ok(());
-        }
-
-        unsafe {
-            let sent = SendInput(inputs, std::mem::size_of::<INPUT>() as i32) as usize;
-            if sent != inputs.len() {
-                let err = GetLastError();
-                return Err(format!(
-                    "SendInput 发送不完整: sent={}, expected={}, err={}",
-                    sent,
-                    inputs.len(),
-                    err.0
-                ));
-            }
-        }
-
-        Ok(())
-    }
-
     fn to_desktop_point(&self, monitor: ActiveMonitor, x: f32, y: f32) -> (i32, i32) {
         let clamped_x = x.clamp(0.0, 1.0);
         let clamped_y = y.clamp(0.0, 1.0);
@@ -199,6 +518,228 @@ impl InputInjector {
     }
 }
 
+impl HeldInputs {
+    fn set_key(&self, key: KeyStroke, down: bool) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if down {
+            state.keys.insert(key);
+        } else {
+            state.keys.remove(&key);
+        }
+    }
+
+    fn set_button(&self, button: u8, down: bool) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if down {
+            state.buttons.insert(button);
+        } else {
+            state.buttons.remove(&button);
+        }
+    }
+
+    /// 松开所有仍处于按下状态的按键与鼠标按钮，返回松开的数量
+    pub fn release(&self) -> Result<usize, String> {
+        let held = match self.state.lock() {
+            Ok(mut state) => std::mem::take(&mut *state),
+            Err(e) => return Err(e.to_string()),
+        };
+        let mut inputs: Vec<_> = held
+            .keys
+            .iter()
+            .map(|&key| keyboard_input(key, false))
+            .collect();
+        inputs.extend(held.buttons.iter().filter_map(|&button| {
+            mouse_button_flags(button, false).map(|(flags, data)| mouse_input(0, 0, data, flags))
+        }));
+
+        send_inputs(&inputs)?;
+        Ok(inputs.len())
+    }
+}
+
+impl Drop for InputInjector {
+    /// 会话以任何方式结束时都不留下卡住的按键
+    fn drop(&mut self) {
+        match self.release_all() {
+            Ok(0) => {}
+            Ok(released) => log::info!("会话结束，已松开 {} 个按下的按键/按钮", released),
+            Err(e) => log::warn!("会话结束时松开按键失败: {}", e),
+        }
+    }
+}
+
+fn open_synthetic_pointer(
+    open: fn() -> Result<SyntheticPointer, String>,
+    kind: &str,
+) -> Option<SyntheticPointer> {
+    match open() {
+        Ok(device) => Some(device),
+        Err(e) => {
+            log::warn!("{}输入将以鼠标模拟: {}", kind, e);
+            None
+        }
+    }
+}
+
+/// 累加上次剩余的小数位移，拆分为本次注入的整数像素与新的剩余（向零取整，剩余与位移同号）
+fn split_relative_motion(remainder: (f32, f32), dx: f32, dy: f32) -> ((i32, i32), (f32, f32)) {
+    let (total_x, total_y) = (remainder.0 + dx, remainder.1 + dy);
+    let (move_x, move_y) = (total_x.trunc(), total_y.trunc());
+    (
+        (move_x as i32, move_y as i32),
+        (total_x - move_x, total_y - move_y),
+    )
+}
+
+/// 相对显示器尺寸的比例换算为像素，缺省为 0
+fn to_pixels(ratio: Option<f32>, size: u32) -> i32 {
+    ratio.map_or(0, |ratio| {
+        (ratio.clamp(0.0, 1.0) * size as f32).round() as i32
+    })
+}
+
+/// 等待锁定键切换生效，返回与目标状态一致或超时时读取到的主机状态
+///
+/// SendInput 只是排队，切换在系统输入线程处理后才生效；轮询期间会阻塞，不要在会话线程调用
+pub fn wait_for_lock_keys(
+    caps_lock: Option<bool>,
+    num_lock: Option<bool>,
+    scroll_lock: Option<bool>,
+) -> LockKeys {
+    let mut state = read_lock_keys();
+    for _ in 0..LOCK_KEYS_POLL_ATTEMPTS {
+        if state.matches(caps_lock, num_lock, scroll_lock) {
+            break;
+        }
+        std::thread::sleep(LOCK_KEYS_POLL_INTERVAL);
+        state = read_lock_keys();
+    }
+    state
+}
+
+/// 读取系统当前的锁定键状态
+///
+/// `GetKeyState` 只反映调用线程消息队列看到的状态，会话线程没有窗口，读到的值不会随
+/// 系统变化；临时挂接到前台窗口线程的输入状态后用 `GetKeyboardState` 读取
+fn read_lock_keys() -> LockKeys {
+    unsafe {
+        let current_thread = GetCurrentThreadId();
+        let foreground_thread = GetWindowThreadProcessId(GetForegroundWindow(), None);
+        let attached = foreground_thread != 0
+            && foreground_thread != current_thread
+            && AttachThreadInput(current_thread, foreground_thread, true).as_bool();
+
+        let mut keys = [0u8; 256];
+        let read = GetKeyboardState(&mut keys);
+        if attached {
+            let _ = AttachThreadInput(current_thread, foreground_thread, false);
+        }
+
+        if read.is_err() {
+            return LockKeys {
+                caps_lock: is_toggled(VK_CAPITAL),
+                num_lock: is_toggled(VK_NUMLOCK),
+                scroll_lock: is_toggled(VK_SCROLL),
+            };
+        }
+        let toggled = |key: VIRTUAL_KEY| keys[key.0 as usize] & 1 != 0;
+        LockKeys {
+            caps_lock: toggled(VK_CAPITAL),
+            num_lock: toggled(VK_NUMLOCK),
+            scroll_lock: toggled(VK_SCROLL),
+        }
+    }
+}
+
+fn is_toggled(key: VIRTUAL_KEY) -> bool {
+    unsafe { GetKeyState(key.0 as i32) & 1 != 0 }
+}
+
+fn keyboard_input(key: KeyStroke, down: bool) -> INPUT {
+    let (vk, scan, mut flags) = match key {
+        KeyStroke::Scancode { scancode, extended } => (
+            VIRTUAL_KEY(0),
+            scancode,
+            if extended {
+                KEYEVENTF_SCANCODE | KEYEVENTF_EXTENDEDKEY
+            } else {
+                KEYEVENTF_SCANCODE
+            },
+        ),
+        KeyStroke::VirtualKey(vk) => (VIRTUAL_KEY(vk), 0, KEYBD_EVENT_FLAGS(0)),
+    };
+    if !down {
+        flags |= KEYEVENTF_KEYUP;
+    }
+
+    INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: vk,
+                wScan: scan,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    }
+}
+
+/// 文本对应的按键序列与实际键入的字符数
+fn text_inputs(text: &str) -> (Vec<INPUT>, usize) {
+    let mut inputs = Vec::with_capacity(text.len() * 2);
+    let mut typed = 0;
+    for ch in text.chars() {
+        match ch {
+            '\n' => inputs.extend([
+                keyboard_input(ENTER_KEY, true),
+                keyboard_input(ENTER_KEY, false),
+            ]),
+            '\t' => inputs.extend([
+                keyboard_input(TAB_KEY, true),
+                keyboard_input(TAB_KEY, false),
+            ]),
+            // \r\n 只按一次回车；其余控制字符没有可见效果
+            ch if ch.is_control() => continue,
+            ch => {
+                let mut units = [0u16; 2];
+                for &unit in ch.encode_utf16(&mut units).iter() {
+                    inputs.extend([unicode_input(unit, true), unicode_input(unit, false)]);
+                }
+            }
+        }
+        typed += 1;
+    }
+    (inputs, typed)
+}
+
+/// 单个 UTF-16 码元；代理对需按顺序发送两个码元
+fn unicode_input(unit: u16, down: bool) -> INPUT {
+    let flags = if down {
+        KEYEVENTF_UNICODE
+    } else {
+        KEYEVENTF_UNICODE | KEYEVENTF_KEYUP
+    };
+
+    INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: VIRTUAL_KEY(0),
+                wScan: unit,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    }
+}
+
 fn mouse_input(dx: i32, dy: i32, mouse_data: u32, flags: MOUSE_EVENT_FLAGS) -> INPUT {
     INPUT {
         r#type: INPUT_MOUSE,
@@ -271,16 +812,250 @@ fn to_sendinput_absolute(value: i32, virtual_origin: i32, virtual_size: i32) ->
     (numerator / denominator).clamp(0, 65535) as i32
 }
 
-fn map_virtual_key(key_code: u16, code: Option<&str>) -> VIRTUAL_KEY {
-    match code {
-        Some("ShiftLeft") => VK_LSHIFT,
-        Some("ShiftRight") => VK_RSHIFT,
-        Some("ControlLeft") => VK_LCONTROL,
-        Some("ControlRight") => VK_RCONTROL,
-        Some("AltLeft") => VK_LMENU,
-        Some("AltRight") => VK_RMENU,
-        Some("MetaLeft") => VK_LWIN,
-        Some("MetaRight") => VK_RWIN,
-        _ => VIRTUAL_KEY(key_code),
+fn send_inputs(inputs: &[INPUT]) -> Result<(), String> {
+    if inputs.is_empty() {
+        return Ok(());
+    }
+
+    unsafe {
+        let sent = SendInput(inputs, std::mem::size_of::<INPUT>() as i32) as usize;
+        if sent != inputs.len() {
+            let err = GetLastError();
+            return Err(format!(
+                "SendInput 发送不完整: sent={}, expected={}, err={}",
+                sent,
+                inputs.len(),
+                err.0
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held_counts(held: &HeldInputs) -> (usize, usize) {
+        let state = held.state.lock().unwrap();
+        (state.keys.len(), state.buttons.len())
+    }
+
+    #[test]
+    fn tracks_held_keys_and_buttons() {
+        let held = HeldInputs::default();
+        let key_a = KeyStroke::Scancode {
+            scancode: 0x1E,
+            extended: false,
+        };
+
+        held.set_key(key_a, true);
+        held.set_key(key_a, true);
+        held.set_key(KeyStroke::VirtualKey(0x41), true);
+        held.set_button(0, true);
+        held.set_button(2, true);
+        assert_eq!(held_counts(&held), (2, 2));
+
+        held.set_key(key_a, false);
+        held.set_button(2, false);
+        held.set_button(4, false);
+        assert_eq!(held_counts(&held), (1, 1));
+    }
+
+    #[test]
+    fn releasing_nothing_sends_nothing() {
+        let held = HeldInputs::default();
+        assert_eq!(held.release(), Ok(0));
+
+        let key_a = KeyStroke::VirtualKey(0x41);
+        held.set_key(key_a, true);
+        held.set_key(key_a, false);
+        assert_eq!(held.release(), Ok(0));
+    }
+
+    #[test]
+    fn builds_key_release_input() {
+        let input = keyboard_input(
+            KeyStroke::Scancode {
+                scancode: 0x1D,
+                extended: true,
+            },
+            false,
+        );
+        let ki = unsafe { input.Anonymous.ki };
+        assert_eq!(input.r#type, INPUT_KEYBOARD);
+        assert_eq!(ki.wScan, 0x1D);
+        assert_eq!(ki.wVk, VIRTUAL_KEY(0));
+        assert_eq!(
+            ki.dwFlags,
+            KEYEVENTF_SCANCODE | KEYEVENTF_EXTENDEDKEY | KEYEVENTF_KEYUP
+        );
+
+        let ki = unsafe {
+            keyboard_input(KeyStroke::VirtualKey(0x41), true)
+                .Anonymous
+                .ki
+        };
+        assert_eq!(ki.wVk, VIRTUAL_KEY(0x41));
+        assert_eq!(ki.dwFlags, KEYBD_EVENT_FLAGS(0));
+    }
+
+    #[test]
+    fn maps_mouse_buttons() {
+        assert_eq!(mouse_button_flags(0, true), Some((MOUSEEVENTF_LEFTDOWN, 0)));
+        assert_eq!(mouse_button_flags(0, false), Some((MOUSEEVENTF_LEFTUP, 0)));
+        assert_eq!(
+            mouse_button_flags(1, false),
+            Some((MOUSEEVENTF_MIDDLEUP, 0))
+        );
+        assert_eq!(
+            mouse_button_flags(2, true),
+            Some((MOUSEEVENTF_RIGHTDOWN, 0))
+        );
+        assert_eq!(
+            mouse_button_flags(3, false),
+            Some((MOUSEEVENTF_XUP, XBUTTON1_DATA))
+        );
+        assert_eq!(
+            mouse_button_flags(4, true),
+            Some((MOUSEEVENTF_XDOWN, XBUTTON2_DATA))
+        );
+        assert_eq!(mouse_button_flags(5, true), None);
+    }
+
+    #[test]
+    fn resolves_physical_keys_first() {
+        assert_eq!(
+            KeyStroke::resolve(0x41, Some("KeyA")),
+            Some(KeyStroke::Scancode {
+                scancode: 0x1E,
+                extended: false,
+            })
+        );
+        assert_eq!(
+            KeyStroke::resolve(0, Some("ArrowUp")),
+            Some(KeyStroke::Scancode {
+                scancode: 0x48,
+                extended: true,
+            })
+        );
+        // 无法映射的 code 退回虚拟键码
+        assert_eq!(
+            KeyStroke::resolve(0xAD, Some("Unidentified")),
+            Some(KeyStroke::VirtualKey(0xAD))
+        );
+        assert_eq!(
+            KeyStroke::resolve(0x41, None),
+            Some(KeyStroke::VirtualKey(0x41))
+        );
+        assert_eq!(KeyStroke::resolve(0, Some("Unidentified")), None);
+    }
+
+    /// 按键序列中每个输入的 (扫描码或 UTF-16 码元, 是否为 Unicode 输入, 是否松开)
+    fn describe_inputs(inputs: &[INPUT]) -> Vec<(u16, bool, bool)> {
+        inputs
+            .iter()
+            .map(|input| {
+                let ki = unsafe { input.Anonymous.ki };
+                (
+                    ki.wScan,
+                    ki.dwFlags.contains(KEYEVENTF_UNICODE),
+                    ki.dwFlags.contains(KEYEVENTF_KEYUP),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn types_text_as_unicode_units() {
+        let (inputs, typed) = text_inputs("a中");
+        assert_eq!(typed, 2);
+        assert_eq!(
+            describe_inputs(&inputs),
+            [
+                (0x61, true, false),
+                (0x61, true, true),
+                (0x4E2D, true, false),
+                (0x4E2D, true, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn sends_surrogate_pairs_in_order() {
+        let (inputs, typed) = text_inputs("😀");
+        assert_eq!(typed, 1);
+        assert_eq!(
+            describe_inputs(&inputs),
+            [
+                (0xD83D, true, false),
+                (0xD83D, true, true),
+                (0xDE00, true, false),
+                (0xDE00, true, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn presses_enter_and_tab_for_line_breaks() {
+        let (inputs, typed) = text_inputs("a\r\n\tb\u{7}");
+        assert_eq!(typed, 4);
+        assert_eq!(
+            describe_inputs(&inputs),
+            [
+                (0x61, true, false),
+                (0x61, true, true),
+                (0x1C, false, false),
+                (0x1C, false, true),
+                (0x0F, false, false),
+                (0x0F, false, true),
+                (0x62, true, false),
+                (0x62, true, true),
+            ]
+        );
+
+        let (inputs, typed) = text_inputs("");
+        assert!(inputs.is_empty());
+        assert_eq!(typed, 0);
+    }
+
+    #[test]
+    fn matches_requested_lock_keys() {
+        let state = LockKeys {
+            caps_lock: true,
+            num_lock: false,
+            scroll_lock: false,
+        };
+        assert!(state.matches(None, None, None));
+        assert!(state.matches(Some(true), Some(false), None));
+        assert!(!state.matches(Some(false), None, None));
+        assert!(!state.matches(Some(true), None, Some(true)));
+    }
+
+    #[test]
+    fn accumulates_sub_pixel_motion() {
+        let (moved, remainder) = split_relative_motion((0.0, 0.0), 0.4, -0.4);
+        assert_eq!(moved, (0, 0));
+        assert_eq!(remainder, (0.4, -0.4));
+
+        let (moved, remainder) = split_relative_motion(remainder, 0.75, -0.75);
+        assert_eq!(moved, (1, -1));
+        assert!((remainder.0 - 0.15).abs() < 1e-6);
+        assert!((remainder.1 + 0.15).abs() < 1e-6);
+
+        let (moved, remainder) = split_relative_motion((0.0, 0.0), 12.0, -3.0);
+        assert_eq!(moved, (12, -3));
+        assert_eq!(remainder, (0.0, 0.0));
+    }
+
+    #[test]
+    fn opposite_motion_cancels_remainder() {
+        let (moved, remainder) = split_relative_motion((0.6, 0.0), -1.0, 0.0);
+        assert_eq!(moved, (0, 0));
+        assert!((remainder.0 + 0.4).abs() < 1e-6);
+
+        let (moved, _) = split_relative_motion(remainder, -0.7, 0.0);
+        assert_eq!(moved, (-1, 0));
     }
 }
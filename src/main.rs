@@ -1,4 +1,5 @@
 mod capture;
+mod control;
 mod encode;
 mod input;
 mod protocol;
@@ -6,12 +7,27 @@ mod server;
 mod transport;
 
 use capture::dda::DdaCapture;
-use server::http::run_server;
+use control::audit::AuditLog;
+use control::consent::ConsentGate;
+use control::floor::InputFloor;
+use control::registry::SessionRegistry;
+use server::access::AccessPolicy;
+use server::admin::AdminApi;
+use server::auth::Authenticator;
+use server::config::{Config, ListenEndpoint};
+use server::health::Health;
+use server::http::{HttpServices, run_server};
+use server::metrics::{Metrics, MetricsEndpoint, SIMULCAST_METRICS_LABEL};
+use server::pairing::PairingStore;
+use server::proxy::ForwardedResolver;
+use server::shutdown::{SHUTDOWN_GRACE_PERIOD, Shutdown, drain_sessions, wait_for_signal};
+use server::tls::{ServerCertResolver, WebTransportCerts};
+use transport::session::SessionContext;
+use transport::simulcast::SimulcastHubs;
 use transport::webrtc::WebRtcServer;
 use transport::websocket::WebSocketServer;
 use transport::webtransport::WebTransportServer;
 
-use std::net::SocketAddr;
 use std::sync::Arc;
 
 #[tokio::main]
@@ -19,6 +35,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Setup logger with default info level
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
+    // `hash-secret`：从标准输入读取密码或令牌，输出写入凭据文件的哈希
+    if std::env::args().nth(1).as_deref() == Some("hash-secret") {
+        let mut secret = String::new();
+        std::io::stdin().read_line(&mut secret)?;
+        let secret = secret.trim_end_matches(['\r', '\n']);
+        println!("{}", server::auth::hash_secret(secret)?);
+        return Ok(());
+    }
+
+    // 其余参数均为配置覆盖项，如 `--config webdisplay.toml --listen 127.0.0.1:9443,[::1]:9443`
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = Config::load(&args).map_err(|e| format!("配置无效: {}", e))?;
+
     unsafe {
         windows::Win32::Media::timeBeginPeriod(1);
     }
@@ -39,42 +68,175 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     let monitor_list_json = Arc::new(serde_json::to_vec(monitors.as_ref()).unwrap_or_default());
 
-    // 初始化 WebSocket 服务器
-    let ws_server = Arc::new(WebSocketServer::new(
-        monitor_list_json.clone(),
-        monitors.clone(),
+    let auth = Arc::new(Authenticator::load(&config.auth.credentials_file)?);
+    let consent = Arc::new(ConsentGate::new(
+        config.consent.mode,
+        config.consent.timeout(),
+    ));
+    consent.spawn_stdin_prompt();
+
+    // 配对 CA 既签发客户端证书，也用于 mTLS 校验
+    let pairing_ca = server::tls::load_or_generate_pairing_ca(&config.pairing)?;
+    let server_cert = Arc::new(ServerCertResolver::load(config.tls.clone())?);
+    server_cert.clone().spawn_reload_watcher();
+    let health = Arc::new(Health::probe(monitors.len(), server_cert.clone()));
+    let tls_config = server::tls::get_tls_config(server_cert, &pairing_ca)?;
+    let pairing = Arc::new(PairingStore::load(pairing_ca, &config.pairing.devices)?);
+
+    // 三种传输共享同一组 simulcast 捕获源与会话登记表
+    let sessions = Arc::new(SessionRegistry::new());
+    let metrics = Arc::new(Metrics::new());
+    let admin_api = Arc::new(AdminApi::new(
+        sessions.clone(),
+        monitors.len(),
+        auth.clone(),
+        pairing.clone(),
+        config.proxy.base_path.clone(),
+        config.admin.token.clone(),
     ));
+    let access = Arc::new(AccessPolicy::new(config.access));
+    let metrics_endpoint = Arc::new(MetricsEndpoint::new(
+        metrics.clone(),
+        sessions.clone(),
+        auth.clone(),
+        config.metrics,
+    ));
+    let session_context = Arc::new(SessionContext {
+        monitor_list_json,
+        monitors,
+        simulcast_hubs: Arc::new(SimulcastHubs::new(
+            metrics.transport(SIMULCAST_METRICS_LABEL),
+        )),
+        sessions,
+        input_floor: Arc::new(InputFloor::new(
+            config.control.floor_mode,
+            config.control.floor_idle_timeout(),
+        )),
+        auth: auth.clone(),
+        consent: consent.clone(),
+        access: access.clone(),
+        encoding_limits: config.encoding,
+        audit: Arc::new(AuditLog::open(&config.audit)?),
+        metrics,
+    });
+
+    let shutdown = Arc::new(Shutdown::new());
+    // 退出时通知会话与落盘审计日志
+    let sessions = session_context.sessions.clone();
+    let audit = session_context.audit.clone();
+
+    // 初始化 WebSocket 服务器
+    let ws_server = Arc::new(WebSocketServer::new(session_context.clone()));
     let wt_server = Arc::new(WebTransportServer::new(
-        monitor_list_json.clone(),
-        monitors.clone(),
+        session_context.clone(),
+        config.proxy.base_path.clone(),
+        access.clone(),
+        shutdown.clone(),
     ));
-    let webrtc_server = Arc::new(WebRtcServer::new(monitor_list_json, monitors));
+    let webrtc_server = Arc::new(WebRtcServer::new(session_context));
 
     // 初始化 TLS
-    let tls_config = server::tls::get_tls_config()?;
-    let webtransport_cert_hash = Arc::new(server::tls::get_webtransport_certificate_hash_sha256()?);
+    let webtransport_certs = Arc::new(WebTransportCerts::generate()?);
+    webtransport_certs.clone().spawn_rotation();
     let tls_acceptor = tokio_rustls::TlsAcceptor::from(tls_config);
 
-    // 启动统一 HTTP + WebSocket 服务器
-    let server_addr: SocketAddr = "0.0.0.0:8080".parse()?;
-
-    // 启动 WebTransport（UDP/QUIC）服务，与 HTTPS 共享端口号（不同协议）
-    wt_server.clone().spawn(server_addr.port());
+    // 每个 TCP 监听端点同地址启动 WebTransport（UDP/QUIC）服务，与 HTTPS 共享端口号（不同协议）
+    for endpoint in &config.server.listen {
+        if let ListenEndpoint::Tcp {
+            addr,
+            dual_stack,
+            tls: true,
+        } = *endpoint
+        {
+            wt_server
+                .clone()
+                .spawn(addr, dual_stack, webtransport_certs.clone(), health.clone());
+        }
+    }
 
     log::info!("服务已启动！");
-    log::info!("  Web 界面: https://localhost:8080");
-    log::info!("  WebSocket: wss://localhost:8080/ws");
-    log::info!("  WebTransport: https://localhost:8080/webtransport");
-    log::info!("  WebRTC: https://localhost:8080/webrtc/offer");
+    let https_port = config.server.https_port();
+    if let Some(port) = https_port {
+        let origin = format!("localhost:{}{}", port, config.proxy.base_path);
+        log::info!("  Web 界面: https://{}/", origin);
+        log::info!("  WebSocket: wss://{}/ws", origin);
+        log::info!("  WebTransport: https://{}/webtransport", origin);
+        log::info!("  WebRTC: https://{}/webrtc/offer", origin);
+        log::info!("  会话管理: https://{}/api/sessions", origin);
+        log::info!("  运行指标: https://{}/metrics", origin);
+        log::info!("  健康检查: https://{}/healthz, /readyz", origin);
+        log::info!("  设备配对: https://{}/api/pair/start", origin);
+    }
 
-    run_server(
-        server_addr,
-        tls_acceptor,
+    let services = HttpServices {
         ws_server,
         webrtc_server,
-        webtransport_cert_hash,
+        webtransport_certs,
+        admin_api,
+        metrics: metrics_endpoint,
+        health,
+        auth,
+        consent,
+        pairing,
+        web_dir: config.server.web_dir,
+        base_path: config.proxy.base_path.clone(),
+        forwarded: Arc::new(ForwardedResolver::new(config.proxy.trusted_proxies)),
+        access: access.clone(),
+    };
+    // 配置校验已保证启用重定向监听时存在 TCP 端点
+    if let (Some(redirect_addr), Some(port)) = (config.server.http_redirect_bind, https_port) {
+        tokio::spawn(async move {
+            if let Err(e) = server::http::run_redirect_listener(redirect_addr, port, access).await {
+                log::warn!("HTTP 重定向监听不可用: {}", e);
+            }
+        });
+    }
+
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            wait_for_signal().await;
+            shutdown.trigger();
+        }
+    });
+
+    run_server(
+        &config.server.listen,
+        tls_acceptor,
+        services,
+        config.server.redirect_http,
+        &shutdown,
     )
     .await
     .map_err(|e| -> Box<dyn std::error::Error> { e })?;
-    Ok(())
+
+    // 已停止接受新连接，通知在线会话结束；再次收到信号时不再等待
+    tokio::select! {
+        remaining = drain_sessions(&sessions, SHUTDOWN_GRACE_PERIOD) => {
+            if remaining > 0 {
+                log::warn!(
+                    "{} 个会话未在 {}s 内结束，强制退出",
+                    remaining,
+                    SHUTDOWN_GRACE_PERIOD.as_secs()
+                );
+            }
+        }
+        _ = wait_for_signal() => log::warn!("再次收到退出信号，立即退出"),
+    }
+    // 未结束的会话线程不会再执行 Drop，由主线程松开其按下的按键
+    let abandoned = sessions.count();
+    if abandoned > 0 {
+        let released = sessions.release_held_inputs();
+        log::warn!(
+            "放弃 {} 个未结束的会话，已松开 {} 个按下的按键/按钮",
+            abandoned,
+            released
+        );
+    }
+
+    audit.flush();
+    log::info!("=== 串流服务器已退出 ===");
+    log::logger().flush();
+    // 不等待仍阻塞在捕获或发送中的会话线程
+    std::process::exit(0)
 }
@@ -30,9 +30,27 @@ pub enum FrameType {
     KeyboardInput = 0x07,
     /// 编码参数设置（双向：客户端请求 / 服务端回执）
     EncodingSettings = 0x08,
+    /// 会话角色（双向：服务端推送角色与会话列表 / 客户端请求移交控制权）
+    SessionRole = 0x09,
+    /// 服务端提示（服务端 → 客户端，如输入被拒绝）
+    Notice = 0x0A,
+    /// 输入控制权（双向：服务端推送持有者 / 客户端请求或释放）
+    FloorControl = 0x0B,
+    /// 松开全部按下的按键与鼠标按钮（客户端 → 服务端，如窗口失去焦点时）
+    InputRelease = 0x0C,
+    /// 文本输入：输入法提交的文字或按文字键入的剪贴板内容（客户端 → 服务端）
+    TextInput = 0x0D,
+    /// 锁定键状态同步（客户端 → 服务端，主机状态以提示回报）
+    LockKeys = 0x0E,
+    /// 鼠标模式（双向：客户端切换相对模式 / 服务端回执）
+    MouseMode = 0x0F,
     /// 心跳包
     Ping = 0x10,
     Pong = 0x11,
+    /// 多点触控输入（客户端 → 服务端）
+    TouchInput = 0x12,
+    /// 笔输入（客户端 → 服务端）
+    PenInput = 0x13,
 }
 
 bitflags::bitflags! {
@@ -81,8 +99,17 @@ impl FrameHeader {
             0x06 => FrameType::MouseInput,
             0x07 => FrameType::KeyboardInput,
             0x08 => FrameType::EncodingSettings,
+            0x09 => FrameType::SessionRole,
+            0x0A => FrameType::Notice,
+            0x0B => FrameType::FloorControl,
+            0x0C => FrameType::InputRelease,
+            0x0D => FrameType::TextInput,
+            0x0E => FrameType::LockKeys,
+            0x0F => FrameType::MouseMode,
             0x10 => FrameType::Ping,
             0x11 => FrameType::Pong,
+            0x12 => FrameType::TouchInput,
+            0x13 => FrameType::PenInput,
             _ => return None,
         };
 
@@ -0,0 +1,248 @@
+use crate::server::config::{AccessSection, IpCidr};
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// 地址表超过此条目数时清理空闲记录
+const PEER_TABLE_PRUNE_THRESHOLD: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessDenied {
+    /// 地址不在 allow 中或命中 deny
+    Blocked,
+    /// 新会话过于频繁
+    RateLimited,
+    /// 并发会话已达上限
+    TooManySessions,
+}
+
+impl fmt::Display for AccessDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Self::Blocked => "地址不在允许范围内",
+            Self::RateLimited => "新会话过于频繁",
+            Self::TooManySessions => "并发会话数已达上限",
+        };
+        f.write_str(message)
+    }
+}
+
+/// 单个来源地址的会话状态：令牌桶限速与当前会话数
+struct PeerState {
+    active: usize,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// 网络层访问策略：地址段黑白名单（按连接检查）、单 IP 并发会话上限与新会话速率限制（按会话检查）
+pub struct AccessPolicy {
+    allow: Vec<IpCidr>,
+    deny: Vec<IpCidr>,
+    max_sessions_per_ip: usize,
+    connections_per_minute: u32,
+    connection_burst: u32,
+    peers: Mutex<HashMap<IpAddr, PeerState>>,
+}
+
+/// 占用的会话名额，释放时归还
+pub struct SessionSlot {
+    policy: Arc<AccessPolicy>,
+    ip: IpAddr,
+}
+
+impl AccessPolicy {
+    pub fn new(section: AccessSection) -> Self {
+        Self {
+            allow: section.allow,
+            deny: section.deny,
+            max_sessions_per_ip: section.max_sessions_per_ip,
+            connections_per_minute: section.connections_per_minute,
+            connection_burst: section.connection_burst,
+            peers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// deny 优先；allow 为空时允许其余所有地址
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        if self.deny.iter().any(|cidr| cidr.contains(ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    /// 接纳新会话：依次检查地址规则、会话速率与并发上限，通过时占用一个会话名额
+    pub fn admit(self: &Arc<Self>, ip: IpAddr) -> Result<SessionSlot, AccessDenied> {
+        let ip = ip.to_canonical();
+        if !self.is_allowed(ip) {
+            return Err(AccessDenied::Blocked);
+        }
+
+        if let Ok(mut peers) = self.peers.lock() {
+            self.charge(&mut peers, ip)?;
+        }
+        Ok(SessionSlot {
+            policy: Arc::clone(self),
+            ip,
+        })
+    }
+
+    /// 扣除一次会话配额并计入会话数
+    fn charge(
+        &self,
+        peers: &mut HashMap<IpAddr, PeerState>,
+        ip: IpAddr,
+    ) -> Result<(), AccessDenied> {
+        let now = Instant::now();
+        let burst = f64::from(self.connection_burst);
+        let refill_per_sec = f64::from(self.connections_per_minute) / 60.0;
+        if peers.len() >= PEER_TABLE_PRUNE_THRESHOLD {
+            peers.retain(|_, peer| {
+                let tokens = peer.tokens
+                    + now.duration_since(peer.refilled_at).as_secs_f64() * refill_per_sec;
+                peer.active > 0 || tokens < burst
+            });
+        }
+
+        let peer = peers.entry(ip).or_insert(PeerState {
+            active: 0,
+            tokens: burst,
+            refilled_at: now,
+        });
+        if self.connections_per_minute > 0 {
+            let elapsed = now.duration_since(peer.refilled_at).as_secs_f64();
+            peer.tokens = (peer.tokens + elapsed * refill_per_sec).min(burst);
+            peer.refilled_at = now;
+            if peer.tokens < 1.0 {
+                return Err(AccessDenied::RateLimited);
+            }
+            peer.tokens -= 1.0;
+        }
+        if self.max_sessions_per_ip > 0 && peer.active >= self.max_sessions_per_ip {
+            return Err(AccessDenied::TooManySessions);
+        }
+        peer.active += 1;
+        Ok(())
+    }
+
+    fn release(&self, ip: IpAddr) {
+        let Ok(mut peers) = self.peers.lock() else {
+            return;
+        };
+        if let Some(peer) = peers.get_mut(&ip) {
+            peer.active = peer.active.saturating_sub(1);
+        }
+    }
+}
+
+impl Drop for SessionSlot {
+    fn drop(&mut self) {
+        self.policy.release(self.ip);
+    }
+}
+
+/// 按请求的客户端地址检查 allow/deny，覆盖经反向代理转发的连接
+pub async fn enforce_address_rules(
+    State(policy): State<Arc<AccessPolicy>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !policy.is_allowed(peer_addr.ip()) {
+        log::warn!("拒绝来自 {} 的请求: {}", peer_addr, AccessDenied::Blocked);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(section: AccessSection) -> Arc<AccessPolicy> {
+        Arc::new(AccessPolicy::new(section))
+    }
+
+    fn unlimited() -> AccessSection {
+        AccessSection {
+            max_sessions_per_ip: 0,
+            connections_per_minute: 0,
+            ..AccessSection::default()
+        }
+    }
+
+    fn ip(raw: &str) -> IpAddr {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    fn deny_takes_precedence_over_allow() {
+        let policy = policy(AccessSection {
+            allow: vec!["192.168.1.0/24".parse().unwrap()],
+            deny: vec!["192.168.1.13".parse().unwrap()],
+            ..unlimited()
+        });
+        assert!(policy.is_allowed(ip("192.168.1.20")));
+        assert!(!policy.is_allowed(ip("192.168.1.13")));
+        assert!(!policy.is_allowed(ip("10.0.0.1")));
+
+        assert!(policy.admit(ip("192.168.1.20")).is_ok());
+        // IPv4 映射的 IPv6 地址按 IPv4 规则检查
+        assert!(!policy.is_allowed(ip("::ffff:192.168.1.13")));
+        assert!(policy.admit(ip("::ffff:192.168.1.20")).is_ok());
+        assert_eq!(
+            policy.admit(ip("::ffff:192.168.1.13")).err(),
+            Some(AccessDenied::Blocked)
+        );
+    }
+
+    #[test]
+    fn empty_allow_list_admits_everyone() {
+        let policy = policy(unlimited());
+        assert!(policy.is_allowed(ip("203.0.113.5")));
+        assert!(policy.is_allowed(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn limits_connection_bursts() {
+        let policy = policy(AccessSection {
+            max_sessions_per_ip: 0,
+            connections_per_minute: 1,
+            connection_burst: 2,
+            ..AccessSection::default()
+        });
+        let first = policy.admit(ip("203.0.113.5")).unwrap();
+        drop(first);
+        let _second = policy.admit(ip("203.0.113.5")).unwrap();
+        assert_eq!(
+            policy.admit(ip("203.0.113.5")).err(),
+            Some(AccessDenied::RateLimited)
+        );
+        // 每个地址单独计算
+        assert!(policy.admit(ip("203.0.113.6")).is_ok());
+    }
+
+    #[test]
+    fn caps_concurrent_sessions_per_ip() {
+        let policy = policy(AccessSection {
+            max_sessions_per_ip: 2,
+            ..unlimited()
+        });
+        let first = policy.admit(ip("203.0.113.5")).unwrap();
+        let _second = policy.admit(ip("203.0.113.5")).unwrap();
+        assert_eq!(
+            policy.admit(ip("203.0.113.5")).err(),
+            Some(AccessDenied::TooManySessions)
+        );
+
+        // 会话结束后归还名额
+        drop(first);
+        assert!(policy.admit(ip("203.0.113.5")).is_ok());
+    }
+}
@@ -0,0 +1,333 @@
+use crate::control::registry::{EncodingUpdate, SessionCommand, SessionDetails, SessionRegistry};
+use crate::control::role::SessionRole;
+use crate::server::auth::{Authenticator, constant_time_eq};
+use crate::server::pairing::{PairedDevice, PairingStore};
+use crate::server::share::{
+    CreatedShareLink, DEFAULT_SHARE_MAX_USES, DEFAULT_SHARE_TTL_SECS, ShareLinkInfo,
+};
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::{HeaderMap, StatusCode, Uri, header};
+use axum::routing::{delete, get};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+type ApiError = (StatusCode, String);
+
+/// 会话管理 HTTP 接口（/api/sessions）
+pub struct AdminApi {
+    sessions: Arc<SessionRegistry>,
+    monitor_count: usize,
+    /// 也接受管理员角色的会话令牌
+    auth: Arc<Authenticator>,
+    pairing: Arc<PairingStore>,
+    /// 生成分享链接时使用的路径前缀
+    base_path: String,
+    /// 静态 Bearer 令牌；未配置且未启用认证时接口整体禁用
+    token: Option<String>,
+}
+
+/// PATCH /api/sessions/{id} 请求体，未指定的字段保持不变
+#[derive(Debug, Deserialize)]
+struct SessionPatchRequest {
+    #[serde(default)]
+    role: Option<String>,
+    #[serde(default)]
+    monitor: Option<u32>,
+    #[serde(default)]
+    encoding: Option<EncodingUpdate>,
+}
+
+/// POST /api/shares 请求体
+#[derive(Debug, Deserialize)]
+struct ShareCreateRequest {
+    monitor: u32,
+    /// viewer 或 controller，默认只读
+    #[serde(default)]
+    role: Option<String>,
+    #[serde(default)]
+    ttl_secs: Option<u64>,
+    #[serde(default)]
+    max_uses: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+struct ShareCreateResponse {
+    #[serde(flatten)]
+    link: CreatedShareLink,
+    /// 可直接发给对方的访问地址
+    url: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SessionDisconnectRequest {
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+impl AdminApi {
+    pub fn new(
+        sessions: Arc<SessionRegistry>,
+        monitor_count: usize,
+        auth: Arc<Authenticator>,
+        pairing: Arc<PairingStore>,
+        base_path: String,
+        token: Option<String>,
+    ) -> Self {
+        if token.is_none() && !auth.is_enabled() {
+            log::warn!("未设置 admin.token 且未启用认证，会话管理接口已禁用");
+        }
+
+        Self {
+            sessions,
+            monitor_count,
+            auth,
+            pairing,
+            base_path,
+            token,
+        }
+    }
+
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/api/sessions", get(list_sessions))
+            .route(
+                "/api/sessions/{id}",
+                get(get_session)
+                    .patch(patch_session)
+                    .delete(disconnect_session),
+            )
+            .route("/api/shares", get(list_shares).post(create_share))
+            .route("/api/shares/{id}", delete(revoke_share))
+            .route("/api/devices", get(list_devices))
+            .route("/api/devices/{id}", delete(revoke_device))
+            .with_state(self)
+    }
+
+    fn authorize(&self, peer: IpAddr, headers: &HeaderMap) -> Result<(), ApiError> {
+        if self.token.is_none() && !self.auth.is_enabled() {
+            return Err((StatusCode::FORBIDDEN, "会话管理接口未启用".to_string()));
+        }
+        self.auth
+            .check_lockout(peer)
+            .map_err(|e| (e.status_code(), e.to_string()))?;
+
+        let provided = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(str::trim)
+            .unwrap_or_default();
+
+        let static_token_ok = self
+            .token
+            .as_deref()
+            .is_some_and(|expected| constant_time_eq(provided.as_bytes(), expected.as_bytes()));
+        let session_token_ok = self
+            .auth
+            .verify_session_token(provided)
+            .is_some_and(|claims| claims.role == SessionRole::Admin);
+        if static_token_ok || session_token_ok {
+            return Ok(());
+        }
+
+        self.auth.record_failure(peer, "管理接口令牌无效");
+        Err((StatusCode::UNAUTHORIZED, "管理令牌无效".to_string()))
+    }
+
+    fn find(&self, id: u64) -> Result<SessionDetails, ApiError> {
+        self.sessions
+            .session_details(id)
+            .ok_or_else(|| (StatusCode::NOT_FOUND, format!("会话 #{} 不存在", id)))
+    }
+}
+
+async fn list_sessions(
+    State(api): State<Arc<AdminApi>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<SessionDetails>>, ApiError> {
+    api.authorize(peer_addr.ip(), &headers)?;
+    Ok(Json(api.sessions.details()))
+}
+
+async fn get_session(
+    State(api): State<Arc<AdminApi>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<u64>,
+    headers: HeaderMap,
+) -> Result<Json<SessionDetails>, ApiError> {
+    api.authorize(peer_addr.ip(), &headers)?;
+    api.find(id).map(Json)
+}
+
+async fn patch_session(
+    State(api): State<Arc<AdminApi>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<u64>,
+    headers: HeaderMap,
+    Json(request): Json<SessionPatchRequest>,
+) -> Result<Json<SessionDetails>, ApiError> {
+    api.authorize(peer_addr.ip(), &headers)?;
+    api.find(id)?;
+
+    // 先完整校验请求，避免只应用了一部分修改
+    let role = match request.role.as_deref() {
+        Some(raw) => Some(
+            SessionRole::from_client_name(raw)
+                .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("未知角色: {}", raw)))?,
+        ),
+        None => None,
+    };
+    if let Some(monitor) = request.monitor {
+        if monitor as usize >= api.monitor_count {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("显示器索引超出范围: {}", monitor),
+            ));
+        }
+    }
+
+    if let Some(role) = role {
+        api.sessions.set_role(id, role);
+    }
+    if let Some(monitor) = request.monitor {
+        api.sessions
+            .send_command(id, SessionCommand::SelectMonitor(monitor));
+    }
+    if let Some(encoding) = request.encoding {
+        api.sessions
+            .send_command(id, SessionCommand::UpdateEncoding(encoding));
+    }
+
+    // 显示器与编码修改由会话线程异步执行，这里返回提交时的状态
+    api.find(id).map(Json)
+}
+
+async fn disconnect_session(
+    State(api): State<Arc<AdminApi>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<u64>,
+    headers: HeaderMap,
+    request: Option<Json<SessionDisconnectRequest>>,
+) -> Result<StatusCode, ApiError> {
+    api.authorize(peer_addr.ip(), &headers)?;
+
+    let reason = request
+        .and_then(|Json(r)| r.reason)
+        .unwrap_or_else(|| "会话已被管理员断开".to_string());
+    if !api
+        .sessions
+        .send_command(id, SessionCommand::Disconnect { reason })
+    {
+        return Err((StatusCode::NOT_FOUND, format!("会话 #{} 不存在", id)));
+    }
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn list_shares(
+    State(api): State<Arc<AdminApi>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<ShareLinkInfo>>, ApiError> {
+    api.authorize(peer_addr.ip(), &headers)?;
+    Ok(Json(api.auth.shares().list()))
+}
+
+async fn create_share(
+    State(api): State<Arc<AdminApi>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    uri: Uri,
+    headers: HeaderMap,
+    Json(request): Json<ShareCreateRequest>,
+) -> Result<(StatusCode, Json<ShareCreateResponse>), ApiError> {
+    api.authorize(peer_addr.ip(), &headers)?;
+
+    if request.monitor as usize >= api.monitor_count {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("显示器索引超出范围: {}", request.monitor),
+        ));
+    }
+    let role = match request.role.as_deref() {
+        Some(raw) => SessionRole::from_client_name(raw)
+            .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("未知角色: {}", raw)))?,
+        None => SessionRole::Viewer,
+    };
+
+    let link = api
+        .auth
+        .shares()
+        .create(
+            request.monitor,
+            role,
+            request.ttl_secs.unwrap_or(DEFAULT_SHARE_TTL_SECS),
+            request.max_uses.unwrap_or(DEFAULT_SHARE_MAX_USES),
+        )
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let path = format!("{}/?share={}", api.base_path, link.token);
+    // HTTP/2 请求没有 Host 头，改用 URI 中的 authority
+    let host = headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| uri.authority().map(|authority| authority.as_str()));
+    let url = match host {
+        Some(host) => format!("https://{}{}", host, path),
+        None => path,
+    };
+
+    Ok((StatusCode::CREATED, Json(ShareCreateResponse { link, url })))
+}
+
+async fn revoke_share(
+    State(api): State<Arc<AdminApi>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<u64>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    api.authorize(peer_addr.ip(), &headers)?;
+
+    if !api.auth.shares().revoke(id) {
+        return Err((StatusCode::NOT_FOUND, format!("分享链接 #{} 不存在", id)));
+    }
+
+    // 撤销同时断开通过该链接接入的会话
+    for session_id in api.sessions.share_sessions(id) {
+        api.sessions.send_command(
+            session_id,
+            SessionCommand::Disconnect {
+                reason: "分享链接已被撤销".to_string(),
+            },
+        );
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_devices(
+    State(api): State<Arc<AdminApi>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<PairedDevice>>, ApiError> {
+    api.authorize(peer_addr.ip(), &headers)?;
+    Ok(Json(api.pairing.list()))
+}
+
+async fn revoke_device(
+    State(api): State<Arc<AdminApi>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<u64>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    api.authorize(peer_addr.ip(), &headers)?;
+
+    // 撤销后该设备的证书在下次建立连接时不再被识别
+    match api.pairing.revoke(id) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((StatusCode::NOT_FOUND, format!("配对设备 #{} 不存在", id))),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
@@ -0,0 +1,234 @@
+use crate::server::auth::encode_hex;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode, Uri, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use rust_embed::{EmbeddedFile, RustEmbed};
+
+/// Vite 输出的带内容哈希的资源目录，文件名随内容变化，可长期缓存
+const HASHED_ASSETS_PREFIX: &str = "/assets/";
+const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+/// 其余文件（index.html 等）每次都需经 ETag / Last-Modified 重新验证
+const REVALIDATE_CACHE_CONTROL: &str = "no-cache";
+
+/// 编译时嵌入的前端构建产物；debug 构建下直接读取磁盘上的 web/dist
+#[derive(RustEmbed)]
+#[folder = "web/dist"]
+#[allow_missing = true]
+struct WebAssets;
+
+/// 预压缩版本，按优先级排列：(Content-Encoding, 文件后缀)
+const PRECOMPRESSED: [(&str, &str); 2] = [("br", ".br"), ("gzip", ".gz")];
+
+/// 构建时是否嵌入了前端文件
+pub fn has_embedded_assets() -> bool {
+    WebAssets::get("index.html").is_some()
+}
+
+/// 提供嵌入的前端文件，支持 ETag 条件请求与预压缩版本
+pub async fn serve_embedded(method: Method, headers: HeaderMap, uri: Uri) -> Response {
+    if method != Method::GET && method != Method::HEAD {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+
+    let mut path = uri.path().trim_start_matches('/').to_string();
+    if path.is_empty() || path.ends_with('/') {
+        path.push_str("index.html");
+    }
+    let Some(file) = WebAssets::get(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let content_type = file.metadata.mimetype().to_string();
+
+    // 客户端接受时改用预压缩版本，ETag 随实际发送的表示变化
+    let accepted = accepted_encodings(&headers);
+    let (encoding, file) = PRECOMPRESSED
+        .iter()
+        .filter(|(encoding, _)| accepted.contains(encoding))
+        .find_map(|(encoding, suffix)| {
+            WebAssets::get(&format!("{}{}", path, suffix)).map(|variant| (Some(*encoding), variant))
+        })
+        .unwrap_or((None, file));
+
+    let etag = etag_for(&file);
+    let mut response = if if_none_match(&headers, &etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let mut response = Response::new(Body::from(file.data.into_owned()));
+        if let Ok(value) = HeaderValue::from_str(&content_type) {
+            response.headers_mut().insert(header::CONTENT_TYPE, value);
+        }
+        if let Some(encoding) = encoding {
+            response
+                .headers_mut()
+                .insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding));
+        }
+        response
+    };
+
+    let response_headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response_headers.insert(header::ETAG, value);
+    }
+    response_headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    response
+}
+
+/// 为静态文件响应补充 Cache-Control
+pub async fn cache_control(request: Request, next: Next) -> Response {
+    let policy = if request.uri().path().starts_with(HASHED_ASSETS_PREFIX) {
+        IMMUTABLE_CACHE_CONTROL
+    } else {
+        REVALIDATE_CACHE_CONTROL
+    };
+    let mut response = next.run(request).await;
+    if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+        response
+            .headers_mut()
+            .entry(header::CACHE_CONTROL)
+            .or_insert(HeaderValue::from_static(policy));
+    }
+    response
+}
+
+fn etag_for(file: &EmbeddedFile) -> String {
+    // 取 SHA-256 前 16 字节即足以区分版本
+    format!("\"{}\"", encode_hex(&file.metadata.sha256_hash()[..16]))
+}
+
+/// 解析 Accept-Encoding，忽略 q=0 的编码
+fn accepted_encodings(headers: &HeaderMap) -> Vec<&str> {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let encoding = parts.next()?;
+            let rejected = parts.any(|param| {
+                param
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q <= 0.0)
+            });
+            (!encoding.is_empty() && !rejected).then_some(encoding)
+        })
+        .collect()
+}
+
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|candidate| candidate.trim().trim_start_matches("W/"))
+        .any(|candidate| candidate == "*" || candidate == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::middleware;
+    use tower::ServiceExt;
+
+    fn headers(name: header::HeaderName, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn parses_accept_encoding() {
+        let accepted = headers(header::ACCEPT_ENCODING, "gzip, deflate;q=0.5, br;q=0");
+        assert_eq!(accepted_encodings(&accepted), vec!["gzip", "deflate"]);
+        assert!(accepted_encodings(&HeaderMap::new()).is_empty());
+    }
+
+    #[test]
+    fn matches_if_none_match() {
+        let etag = "\"abc\"";
+        assert!(if_none_match(
+            &headers(header::IF_NONE_MATCH, "\"abc\""),
+            etag
+        ));
+        assert!(if_none_match(
+            &headers(header::IF_NONE_MATCH, "\"x\", W/\"abc\""),
+            etag
+        ));
+        assert!(if_none_match(&headers(header::IF_NONE_MATCH, "*"), etag));
+        assert!(!if_none_match(
+            &headers(header::IF_NONE_MATCH, "\"x\""),
+            etag
+        ));
+        assert!(!if_none_match(&HeaderMap::new(), etag));
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_files_and_methods() {
+        let uri = Uri::from_static("/no-such-file.txt");
+        let response = serve_embedded(Method::GET, HeaderMap::new(), uri.clone()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = serve_embedded(Method::POST, HeaderMap::new(), uri).await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn revalidates_embedded_index() {
+        // 未构建前端时没有可测试的文件
+        if !has_embedded_assets() {
+            return;
+        }
+
+        let response = serve_embedded(Method::GET, HeaderMap::new(), Uri::from_static("/")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG].clone();
+
+        let mut conditional = HeaderMap::new();
+        conditional.insert(header::IF_NONE_MATCH, etag);
+        let response = serve_embedded(Method::GET, conditional, Uri::from_static("/")).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn sets_cache_control_by_path() {
+        let app = Router::new()
+            .route(
+                "/missing",
+                axum::routing::get(|| async { StatusCode::NOT_FOUND }),
+            )
+            .fallback(|| async { "ok" })
+            .layer(middleware::from_fn(cache_control));
+        let cache_control_of = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(
+                        axum::http::Request::builder()
+                            .uri(uri)
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                response
+                    .headers()
+                    .get(header::CACHE_CONTROL)
+                    .map(|value| value.to_str().unwrap().to_string())
+            }
+        };
+
+        assert_eq!(
+            cache_control_of("/assets/index-1a2b3c.js").await.as_deref(),
+            Some(IMMUTABLE_CACHE_CONTROL)
+        );
+        assert_eq!(
+            cache_control_of("/index.html").await.as_deref(),
+            Some(REVALIDATE_CACHE_CONTROL)
+        );
+        assert_eq!(cache_control_of("/missing").await, None);
+    }
+}
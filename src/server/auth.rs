@@ -0,0 +1,722 @@
+use crate::control::role::{DEFAULT_GRANTED_ROLE, SessionRole};
+use crate::server::pairing::PairedClient;
+use crate::server::share::{SHARE_TOKEN_PREFIX, ShareLinks, ShareScope};
+use axum::extract::{ConnectInfo, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{hmac, pbkdf2};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// 凭据哈希格式：pbkdf2-sha256$<迭代次数>$<盐 hex>$<哈希 hex>
+const HASH_SCHEME: &str = "pbkdf2-sha256";
+const HASH_ITERATIONS: u32 = 100_000;
+const HASH_SALT_LEN: usize = 16;
+const HASH_OUTPUT_LEN: usize = 32;
+
+/// 会话令牌有效期
+const SESSION_TOKEN_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+const SESSION_TOKEN_VERSION: &str = "v2";
+
+/// 同一来源地址在统计窗口内允许的失败次数，超过后锁定
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+const FAILURE_WINDOW: Duration = Duration::from_secs(5 * 60);
+const LOCKOUT_DURATION: Duration = Duration::from_secs(5 * 60);
+/// 失败记录表超过该规模时清理过期条目
+const FAILURE_TABLE_PRUNE_THRESHOLD: usize = 1024;
+
+static PBKDF2_ALGORITHM: pbkdf2::Algorithm = pbkdf2::PBKDF2_HMAC_SHA256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    /// 未提供会话令牌
+    Missing,
+    /// 凭据或令牌无效
+    Invalid,
+    /// 失败次数过多，来源地址被暂时锁定
+    RateLimited,
+}
+
+impl AuthError {
+    pub fn status_code(self) -> StatusCode {
+        match self {
+            Self::Missing | Self::Invalid => StatusCode::UNAUTHORIZED,
+            Self::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Self::Missing => "需要登录",
+            Self::Invalid => "凭据无效",
+            Self::RateLimited => "失败次数过多，请稍后再试",
+        };
+        f.write_str(message)
+    }
+}
+
+/// 凭据文件中的一条记录（密码或静态令牌，均只保存哈希）
+struct Credential {
+    name: String,
+    role: SessionRole,
+    iterations: NonZeroU32,
+    salt: Vec<u8>,
+    hash: Vec<u8>,
+}
+
+struct FailureRecord {
+    count: u32,
+    window_start: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// 会话令牌中携带的授权信息
+#[derive(Debug, Clone)]
+pub struct SessionClaims {
+    pub role: SessionRole,
+    pub expires_at: u64,
+    /// 签发令牌的凭据名称
+    pub subject: String,
+}
+
+/// 会话的认证来源，写入审计日志
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SessionIdentity {
+    /// 未启用认证
+    Anonymous,
+    /// 凭据登录签发的会话令牌
+    Credential { name: String },
+    /// 已配对设备的客户端证书
+    Device { device_id: u64 },
+    /// 分享链接
+    Share { share_id: u64 },
+}
+
+/// 传输连接通过认证后获得的访问权限
+#[derive(Debug, Clone)]
+pub struct AccessGrant {
+    /// 授予的最高角色
+    pub role: SessionRole,
+    /// 通过分享链接接入时的访问范围
+    pub share: Option<ShareScope>,
+    pub identity: SessionIdentity,
+}
+
+/// Web 界面与各传输共享的认证器
+pub struct Authenticator {
+    credentials: Vec<Credential>,
+    signing_key: hmac::Key,
+    shares: ShareLinks,
+    rng: SystemRandom,
+    failures: Mutex<HashMap<IpAddr, FailureRecord>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginRequest {
+    secret: String,
+}
+
+#[derive(Debug, Serialize)]
+struct LoginResponse {
+    token: String,
+    role: SessionRole,
+    expires_at: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct AuthStatusResponse {
+    required: bool,
+    /// 请求携带的会话令牌是否仍然有效
+    authenticated: bool,
+}
+
+impl Authenticator {
+    /// 从凭据文件加载；文件不存在时认证关闭
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let credentials = if path.exists() {
+            let content = std::fs::read_to_string(path)?;
+            let credentials = parse_credentials(&content)
+                .map_err(|e| format!("解析凭据文件 {} 失败: {}", path.display(), e))?;
+            log::info!("已加载 {} 条凭据 ({})", credentials.len(), path.display());
+            credentials
+        } else {
+            Vec::new()
+        };
+
+        if credentials.is_empty() {
+            log::warn!(
+                "未配置任何凭据（{}），认证已关闭，任何可访问端口的人都能控制桌面",
+                path.display()
+            );
+        }
+
+        let rng = SystemRandom::new();
+        let signing_key =
+            hmac::Key::generate(hmac::HMAC_SHA256, &rng).map_err(|_| "生成令牌签名密钥失败")?;
+        let shares = ShareLinks::new(&rng)?;
+
+        Ok(Self {
+            credentials,
+            signing_key,
+            shares,
+            rng,
+            failures: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.credentials.is_empty()
+    }
+
+    pub fn shares(&self) -> &ShareLinks {
+        &self.shares
+    }
+
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/api/auth/status", get(auth_status))
+            .route("/api/auth/login", post(login))
+            .with_state(self)
+    }
+
+    /// 校验传输连接携带的会话令牌、分享令牌或已配对设备身份
+    pub fn authorize(
+        &self,
+        peer: IpAddr,
+        token: Option<&str>,
+        device: Option<&PairedClient>,
+    ) -> Result<AccessGrant, AuthError> {
+        let token = token.filter(|t| !t.is_empty());
+
+        // 分享链接在是否启用认证时都可用
+        if let Some(token) = token.filter(|t| t.starts_with(SHARE_TOKEN_PREFIX)) {
+            self.check_lockout(peer)?;
+            return match self.shares.redeem(token) {
+                Some((role, scope)) => Ok(AccessGrant {
+                    role,
+                    share: Some(scope),
+                    identity: SessionIdentity::Share { share_id: scope.id },
+                }),
+                None => {
+                    self.record_failure(peer, "分享链接无效、已过期或已用完");
+                    Err(AuthError::Invalid)
+                }
+            };
+        }
+
+        // 已配对设备的客户端证书在 TLS 握手时完成校验，无需再登录
+        if let Some(device) = device {
+            return Ok(AccessGrant {
+                role: device.role,
+                share: None,
+                identity: SessionIdentity::Device {
+                    device_id: device.device_id,
+                },
+            });
+        }
+
+        if !self.is_enabled() {
+            return Ok(AccessGrant {
+                role: DEFAULT_GRANTED_ROLE,
+                share: None,
+                identity: SessionIdentity::Anonymous,
+            });
+        }
+
+        self.check_lockout(peer)?;
+        let Some(token) = token else {
+            return Err(AuthError::Missing);
+        };
+
+        match self.verify_session_token(token) {
+            Some(claims) => Ok(AccessGrant {
+                role: claims.role,
+                share: None,
+                identity: SessionIdentity::Credential {
+                    name: claims.subject,
+                },
+            }),
+            None => {
+                self.record_failure(peer, "会话令牌无效或已过期");
+                Err(AuthError::Invalid)
+            }
+        }
+    }
+
+    /// 仅校验令牌签名与有效期，不计入失败次数
+    pub fn verify_session_token(&self, token: &str) -> Option<SessionClaims> {
+        let (body, signature) = token.rsplit_once('.')?;
+        let signature = decode_hex(signature)?;
+        hmac::verify(&self.signing_key, body.as_bytes(), &signature).ok()?;
+
+        let mut parts = body.split('.');
+        if parts.next()? != SESSION_TOKEN_VERSION {
+            return None;
+        }
+        let role = SessionRole::from_client_name(parts.next()?)?;
+        let expires_at: u64 = parts.next()?.parse().ok()?;
+        if expires_at <= unix_now() {
+            return None;
+        }
+        // 跳过随机数
+        parts.next()?;
+        let subject = String::from_utf8(decode_hex(parts.next()?)?).ok()?;
+
+        Some(SessionClaims {
+            role,
+            expires_at,
+            subject,
+        })
+    }
+
+    /// 校验密码或静态令牌，成功时签发会话令牌
+    fn login(&self, peer: IpAddr, secret: &str) -> Result<LoginResponse, AuthError> {
+        self.check_lockout(peer)?;
+
+        let Some(credential) = self.credentials.iter().find(|c| c.verify(secret)) else {
+            self.record_failure(peer, "登录凭据错误");
+            return Err(AuthError::Invalid);
+        };
+
+        self.clear_failures(peer);
+        let expires_at = unix_now() + SESSION_TOKEN_TTL.as_secs();
+        let token = self
+            .issue_session_token(credential, expires_at)
+            .ok_or(AuthError::Invalid)?;
+        log::info!(
+            "{} 使用凭据 '{}' 登录成功，角色 {}",
+            peer,
+            credential.name,
+            credential.role
+        );
+
+        Ok(LoginResponse {
+            token,
+            role: credential.role,
+            expires_at,
+        })
+    }
+
+    fn issue_session_token(&self, credential: &Credential, expires_at: u64) -> Option<String> {
+        let mut nonce = [0u8; 12];
+        self.rng.fill(&mut nonce).ok()?;
+
+        // 凭据名称以 hex 编码，避免其中的 '.' 干扰解析
+        let body = format!(
+            "{}.{}.{}.{}.{}",
+            SESSION_TOKEN_VERSION,
+            credential.role.as_client_name(),
+            expires_at,
+            encode_hex(&nonce),
+            encode_hex(credential.name.as_bytes())
+        );
+        let signature = hmac::sign(&self.signing_key, body.as_bytes());
+        Some(format!("{}.{}", body, encode_hex(signature.as_ref())))
+    }
+
+    pub fn check_lockout(&self, peer: IpAddr) -> Result<(), AuthError> {
+        let Ok(failures) = self.failures.lock() else {
+            return Ok(());
+        };
+
+        match failures.get(&peer).and_then(|r| r.locked_until) {
+            Some(until) if until > Instant::now() => Err(AuthError::RateLimited),
+            _ => Ok(()),
+        }
+    }
+
+    /// 记录一次失败尝试，超过阈值后锁定来源地址
+    pub fn record_failure(&self, peer: IpAddr, reason: &str) {
+        let Ok(mut failures) = self.failures.lock() else {
+            return;
+        };
+
+        let now = Instant::now();
+        if failures.len() >= FAILURE_TABLE_PRUNE_THRESHOLD {
+            failures.retain(|_, r| {
+                r.locked_until.is_some_and(|t| t > now)
+                    || now.duration_since(r.window_start) < FAILURE_WINDOW
+            });
+        }
+
+        let record = failures.entry(peer).or_insert(FailureRecord {
+            count: 0,
+            window_start: now,
+            locked_until: None,
+        });
+        if now.duration_since(record.window_start) >= FAILURE_WINDOW {
+            record.count = 0;
+            record.window_start = now;
+            record.locked_until = None;
+        }
+        record.count += 1;
+
+        log::warn!(
+            "认证失败: {} ({}), 窗口内第 {} 次",
+            peer,
+            reason,
+            record.count
+        );
+        if record.count >= MAX_FAILED_ATTEMPTS && record.locked_until.is_none() {
+            record.locked_until = Some(now + LOCKOUT_DURATION);
+            log::warn!(
+                "{} 认证失败次数过多，锁定 {} 秒",
+                peer,
+                LOCKOUT_DURATION.as_secs()
+            );
+        }
+    }
+
+    fn clear_failures(&self, peer: IpAddr) {
+        if let Ok(mut failures) = self.failures.lock() {
+            failures.remove(&peer);
+        }
+    }
+}
+
+impl Credential {
+    fn verify(&self, secret: &str) -> bool {
+        pbkdf2::verify(
+            PBKDF2_ALGORITHM,
+            self.iterations,
+            &self.salt,
+            secret.as_bytes(),
+            &self.hash,
+        )
+        .is_ok()
+    }
+}
+
+async fn auth_status(
+    State(auth): State<Arc<Authenticator>>,
+    headers: HeaderMap,
+) -> Json<AuthStatusResponse> {
+    let authenticated = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|token| auth.verify_session_token(token.trim()).is_some());
+
+    Json(AuthStatusResponse {
+        required: auth.is_enabled(),
+        authenticated: !auth.is_enabled() || authenticated,
+    })
+}
+
+async fn login(
+    State(auth): State<Arc<Authenticator>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    Json(request): Json<LoginRequest>,
+) -> Result<Json<LoginResponse>, (StatusCode, String)> {
+    if !auth.is_enabled() {
+        return Err((StatusCode::NOT_FOUND, "认证未启用".to_string()));
+    }
+
+    // PBKDF2 校验较耗时，放到阻塞线程池执行
+    let result = tokio::task::spawn_blocking(move || auth.login(peer_addr.ip(), &request.secret))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    result
+        .map(Json)
+        .map_err(|e| (e.status_code(), e.to_string()))
+}
+
+/// 生成凭据文件中的哈希字段（供 `hash-secret` 命令使用）
+pub fn hash_secret(secret: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut salt = [0u8; HASH_SALT_LEN];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| "生成随机盐失败")?;
+
+    let iterations = NonZeroU32::new(HASH_ITERATIONS).ok_or("迭代次数无效")?;
+    let mut hash = [0u8; HASH_OUTPUT_LEN];
+    pbkdf2::derive(
+        PBKDF2_ALGORITHM,
+        iterations,
+        &salt,
+        secret.as_bytes(),
+        &mut hash,
+    );
+
+    Ok(format!(
+        "{}${}${}${}",
+        HASH_SCHEME,
+        HASH_ITERATIONS,
+        encode_hex(&salt),
+        encode_hex(&hash)
+    ))
+}
+
+/// 凭据文件每行格式：`<名称> <角色> <哈希>`，`#` 开头为注释
+fn parse_credentials(content: &str) -> Result<Vec<Credential>, String> {
+    let mut credentials = Vec::new();
+
+    for (line_no, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [name, role, hash] = fields[..] else {
+            return Err(format!(
+                "第 {} 行格式应为 `<名称> <角色> <哈希>`",
+                line_no + 1
+            ));
+        };
+
+        let role = SessionRole::from_client_name(role)
+            .ok_or_else(|| format!("第 {} 行角色无效: {}", line_no + 1, role))?;
+        let (iterations, salt, hash) = parse_hash(hash)
+            .ok_or_else(|| format!("第 {} 行哈希格式无效（不支持明文）", line_no + 1))?;
+
+        credentials.push(Credential {
+            name: name.to_string(),
+            role,
+            iterations,
+            salt,
+            hash,
+        });
+    }
+
+    Ok(credentials)
+}
+
+fn parse_hash(raw: &str) -> Option<(NonZeroU32, Vec<u8>, Vec<u8>)> {
+    let mut parts = raw.split('$');
+    if parts.next()? != HASH_SCHEME {
+        return None;
+    }
+    let iterations = NonZeroU32::new(parts.next()?.parse().ok()?)?;
+    let salt = decode_hex(parts.next()?)?;
+    let hash = decode_hex(parts.next()?)?;
+    if parts.next().is_some() || hash.is_empty() {
+        return None;
+    }
+    Some((iterations, salt, hash))
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn decode_hex(raw: &str) -> Option<Vec<u8>> {
+    if raw.len() % 2 == 1 {
+        return None;
+    }
+    (0..raw.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(raw.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const PEER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 9));
+
+    /// 测试用凭据行：迭代次数取 1，避免 PBKDF2 拖慢测试
+    fn credential_line(name: &str, role: &str, secret: &str) -> String {
+        let salt = [7u8; HASH_SALT_LEN];
+        let mut hash = [0u8; HASH_OUTPUT_LEN];
+        pbkdf2::derive(
+            PBKDF2_ALGORITHM,
+            NonZeroU32::MIN,
+            &salt,
+            secret.as_bytes(),
+            &mut hash,
+        );
+        format!(
+            "{} {} {}$1${}${}",
+            name,
+            role,
+            HASH_SCHEME,
+            encode_hex(&salt),
+            encode_hex(&hash)
+        )
+    }
+
+    fn authenticator(content: &str) -> Authenticator {
+        let rng = SystemRandom::new();
+        Authenticator {
+            credentials: parse_credentials(content).unwrap(),
+            signing_key: hmac::Key::generate(hmac::HMAC_SHA256, &rng).unwrap(),
+            shares: ShareLinks::new(&rng).unwrap(),
+            rng,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[test]
+    fn hash_secret_verifies_only_the_original_secret() {
+        let line = format!("alice admin {}", hash_secret("correct horse").unwrap());
+        let credentials = parse_credentials(&line).unwrap();
+        assert_eq!(credentials.len(), 1);
+        assert_eq!(credentials[0].role, SessionRole::Admin);
+        assert_eq!(credentials[0].iterations.get(), HASH_ITERATIONS);
+        assert!(credentials[0].verify("correct horse"));
+        assert!(!credentials[0].verify("correct horse "));
+
+        // 每次使用新的随机盐
+        assert_ne!(
+            hash_secret("correct horse").unwrap(),
+            hash_secret("correct horse").unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_credentials_file() {
+        let content = format!(
+            "# 注释\n\n{}\n  {}  \n",
+            credential_line("alice", "admin", "a"),
+            credential_line("bob", "viewer", "b")
+        );
+        let credentials = parse_credentials(&content).unwrap();
+        let names: Vec<_> = credentials
+            .iter()
+            .map(|c| (c.name.as_str(), c.role))
+            .collect();
+        assert_eq!(
+            names,
+            vec![("alice", SessionRole::Admin), ("bob", SessionRole::Viewer)]
+        );
+        assert!(credentials[1].verify("b"));
+        assert!(!credentials[1].verify("a"));
+    }
+
+    #[test]
+    fn rejects_malformed_credentials() {
+        assert!(parse_credentials("alice admin").is_err());
+        assert!(parse_credentials("alice owner pbkdf2-sha256$1$00$00").is_err());
+        assert!(parse_credentials("alice admin hunter2").is_err());
+        assert!(parse_credentials("alice admin pbkdf2-sha256$0$00$00").is_err());
+        assert!(parse_credentials("alice admin pbkdf2-sha256$1$00$").is_err());
+        assert!(parse_credentials("alice admin pbkdf2-sha256$1$00$00$00").is_err());
+    }
+
+    #[test]
+    fn session_token_round_trip() {
+        let auth = authenticator(&credential_line("alice", "controller", "secret"));
+        let response = auth.login(PEER, "secret").unwrap();
+        assert_eq!(response.role, SessionRole::Controller);
+
+        let claims = auth.verify_session_token(&response.token).unwrap();
+        assert_eq!(claims.role, SessionRole::Controller);
+        assert_eq!(claims.subject, "alice");
+        assert_eq!(claims.expires_at, response.expires_at);
+
+        let grant = auth.authorize(PEER, Some(&response.token), None).unwrap();
+        assert_eq!(grant.role, SessionRole::Controller);
+        assert_eq!(
+            grant.identity,
+            SessionIdentity::Credential {
+                name: "alice".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn tampered_or_foreign_tokens_are_rejected() {
+        let auth = authenticator(&credential_line("alice", "viewer", "secret"));
+        let token = auth.login(PEER, "secret").unwrap().token;
+
+        let elevated = token.replacen(".viewer.", ".admin.", 1);
+        assert_ne!(elevated, token);
+        assert!(auth.verify_session_token(&elevated).is_none());
+        assert!(auth.verify_session_token("v2.admin.1").is_none());
+        assert!(auth.verify_session_token("").is_none());
+
+        // 其他实例（重启后）签发的令牌
+        let other = authenticator(&credential_line("alice", "viewer", "secret"));
+        assert!(other.verify_session_token(&token).is_none());
+    }
+
+    #[test]
+    fn expired_tokens_are_rejected() {
+        let auth = authenticator(&credential_line("alice", "admin", "secret"));
+        let expired = auth
+            .issue_session_token(&auth.credentials[0], unix_now() - 1)
+            .unwrap();
+        assert!(auth.verify_session_token(&expired).is_none());
+
+        let valid = auth
+            .issue_session_token(&auth.credentials[0], unix_now() + 60)
+            .unwrap();
+        assert!(auth.verify_session_token(&valid).is_some());
+    }
+
+    #[test]
+    fn repeated_failures_lock_out_the_peer() {
+        let auth = authenticator(&credential_line("alice", "admin", "secret"));
+        for _ in 0..MAX_FAILED_ATTEMPTS {
+            assert_eq!(
+                auth.login(PEER, "wrong").map(|_| ()),
+                Err(AuthError::Invalid)
+            );
+        }
+        assert_eq!(auth.check_lockout(PEER), Err(AuthError::RateLimited));
+        assert_eq!(
+            auth.login(PEER, "secret").map(|_| ()),
+            Err(AuthError::RateLimited)
+        );
+        assert!(auth.check_lockout(IpAddr::V4(Ipv4Addr::LOCALHOST)).is_ok());
+    }
+
+    #[test]
+    fn authorize_without_credentials_grants_default_role() {
+        let disabled = authenticator("");
+        let grant = disabled.authorize(PEER, None, None).unwrap();
+        assert_eq!(grant.role, DEFAULT_GRANTED_ROLE);
+        assert_eq!(grant.identity, SessionIdentity::Anonymous);
+
+        let enabled = authenticator(&credential_line("alice", "admin", "secret"));
+        assert_eq!(
+            enabled.authorize(PEER, None, None).map(|_| ()),
+            Err(AuthError::Missing)
+        );
+        assert_eq!(
+            enabled.authorize(PEER, Some("garbage"), None).map(|_| ()),
+            Err(AuthError::Invalid)
+        );
+    }
+
+    #[test]
+    fn hex_helpers() {
+        assert_eq!(encode_hex(&[0x00, 0xab, 0x7f]), "00ab7f");
+        assert_eq!(decode_hex("00ab7f"), Some(vec![0x00, 0xab, 0x7f]));
+        assert_eq!(decode_hex("00AB"), Some(vec![0x00, 0xab]));
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(decode_hex("zz"), None);
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+    }
+}
@@ -0,0 +1,1280 @@
+use crate::control::consent::ConsentMode;
+use crate::control::floor::FloorMode;
+use serde::Deserialize;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// 配置文件路径的环境变量名，以及未指定时尝试加载的默认文件
+pub const CONFIG_PATH_ENV: &str = "WEBDISPLAY_CONFIG";
+pub const DEFAULT_CONFIG_FILE: &str = "webdisplay.toml";
+
+/// 单项配置的环境变量前缀，如 `--max-fps` 对应 `WEBDISPLAY_MAX_FPS`
+const ENV_PREFIX: &str = "WEBDISPLAY_";
+
+/// 可通过命令行与环境变量覆盖的配置项（命令行写作 `--<name> <value>` 或 `--<name>=<value>`）
+const OVERRIDE_NAMES: &[&str] = &[
+    "listen",
+    "tls-cert",
+    "tls-key",
+    "web-dir",
+    "redirect-http",
+    "http-redirect-bind",
+    "base-path",
+    "trusted-proxies",
+    "plain-http-direct",
+    "allow",
+    "deny",
+    "max-sessions-per-ip",
+    "connections-per-minute",
+    "connection-burst",
+    "metrics-token",
+    "metrics-allow",
+    "audit-log",
+    "audit-keystrokes",
+    "audit-input-interval",
+    "credentials-file",
+    "admin-token",
+    "consent",
+    "consent-timeout",
+    "paired-devices",
+    "pairing-ca-cert",
+    "pairing-ca-key",
+    "floor-mode",
+    "floor-idle-timeout",
+    "fps",
+    "min-fps",
+    "max-fps",
+    "bitrate",
+    "min-bitrate",
+    "max-bitrate",
+    "keyframe-interval",
+    "min-keyframe-interval",
+    "max-keyframe-interval",
+];
+
+/// 帧率的绝对上限，避免配置成超出捕获能力的值
+const FPS_CEILING: u32 = 240;
+/// 码率的绝对上限 (bps)
+const BITRATE_CEILING: usize = 500_000_000;
+/// 关键帧间隔的绝对上限（秒）
+const KEYFRAME_INTERVAL_CEILING: u32 = 60;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerSection,
+    pub tls: TlsFiles,
+    pub auth: AuthSection,
+    pub admin: AdminSection,
+    pub consent: ConsentSection,
+    pub pairing: PairingFiles,
+    pub proxy: ProxySection,
+    pub access: AccessSection,
+    pub audit: AuditSection,
+    pub metrics: MetricsSection,
+    pub control: ControlSection,
+    pub encoding: EncodingLimits,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSection {
+    /// 监听端点列表；TCP 端点同时提供 HTTPS 与 WebTransport（UDP 同端口）
+    pub listen: Vec<ListenEndpoint>,
+    /// 从磁盘提供前端文件的目录（开发时使用），未设置时使用编译时嵌入的文件
+    pub web_dir: Option<PathBuf>,
+    /// 主端口收到明文 HTTP 请求时重定向到 HTTPS
+    pub redirect_http: bool,
+    /// 额外的明文 HTTP 重定向监听地址（如 `0.0.0.0:80`），未设置时不启用
+    pub http_redirect_bind: Option<SocketAddr>,
+}
+
+/// 监听端点，配置中写作字符串：
+/// `0.0.0.0:8080`、`192.168.1.10:8080`（IPv4）、`[::]:8080`（仅 IPv6）、
+/// `dual:[::]:8080`（IPv6 双栈，同时接受 IPv4）、`unix:/run/webdisplay.sock`（明文 HTTP，供本机反向代理）；
+/// TCP 端点加 `http:` 前缀（如 `http:127.0.0.1:8081`）表示由反向代理终止 TLS，本端点只提供明文 HTTP
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ListenEndpoint {
+    Tcp {
+        addr: SocketAddr,
+        dual_stack: bool,
+        tls: bool,
+    },
+    Unix(PathBuf),
+}
+
+/// 反向代理部署
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxySection {
+    /// 所有路由的路径前缀（如 `/desk`），为空时挂载在根路径
+    pub base_path: String,
+    /// 信任其 `Forwarded` / `X-Forwarded-For` 头的代理地址段；Unix 套接字连接始终信任
+    pub trusted_proxies: Vec<IpCidr>,
+    /// 明文 HTTP 端点由客户端直接访问（不经反向代理），连接地址即客户端地址；
+    /// 未配置 trusted_proxies 又启用了按地址的访问策略时必须显式开启
+    pub plain_http_direct: bool,
+}
+
+/// 网络层访问策略，在 TLS 握手与 WebTransport 会话建立前生效；
+/// 经受信任代理或 Unix 套接字接入的连接只按转发的客户端地址检查 allow/deny，速率与并发由代理自行限制
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccessSection {
+    /// 允许接入的地址段，为空时不限制
+    pub allow: Vec<IpCidr>,
+    /// 拒绝接入的地址段，优先于 allow
+    pub deny: Vec<IpCidr>,
+    /// 单个 IP 同时在线的串流会话（各传输合计）上限，0 表示不限
+    pub max_sessions_per_ip: usize,
+    /// 单个 IP 每分钟可发起的串流会话数，0 表示不限；页面、静态资源等普通请求不计入
+    pub connections_per_minute: u32,
+    /// 短时间内允许突发的串流会话数
+    pub connection_burst: u32,
+}
+
+/// 审计日志
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditSection {
+    /// JSON lines 格式的审计日志文件（只追加写入），未设置时不记录
+    pub path: Option<PathBuf>,
+    /// 逐条记录按键；默认只定期记录输入次数汇总
+    pub log_keystrokes: bool,
+    /// 输入次数汇总的记录间隔（秒）
+    pub input_summary_interval: u64,
+}
+
+/// IP 地址段，写作 `10.0.0.0/8`、`::1/128`，或不带前缀长度的单个地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct IpCidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+/// 服务端证书与私钥路径，文件不存在时生成自签名证书
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// 登录认证
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSection {
+    /// 凭据文件（每行 `<名称> <角色> <哈希>`，以空白分隔，`#` 开头为注释），不存在时认证关闭
+    pub credentials_file: PathBuf,
+}
+
+/// 会话管理接口
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminSection {
+    /// 静态 Bearer 令牌；未设置时只接受管理员角色的会话令牌
+    pub token: Option<String>,
+}
+
+/// Prometheus 抓取接口 /metrics，令牌与白名单都未设置时禁用
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsSection {
+    /// 抓取时携带的 Bearer 令牌
+    pub token: Option<String>,
+    /// 允许抓取的客户端地址段
+    pub allow: Vec<IpCidr>,
+}
+
+/// 新会话接入前的主机端确认
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConsentSection {
+    /// `off`、`http`、`stdin` 或 `both`
+    pub mode: ConsentMode,
+    /// 等待确认的时长（秒），超时视为拒绝
+    pub timeout: u64,
+}
+
+/// 配对设备与签发客户端证书的本地 CA
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PairingFiles {
+    /// 已配对设备的持久化文件
+    pub devices: PathBuf,
+    /// CA 证书与私钥，不存在时生成
+    pub ca_cert: PathBuf,
+    pub ca_key: PathBuf,
+}
+
+/// 多控制者之间的输入仲裁
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlSection {
+    /// `exclusive`：同一时刻只注入一个控制者的输入；`free`：不仲裁
+    pub floor_mode: FloorMode,
+    /// 控制权持有者无输入超过该时长（秒）后，其他控制者可直接接管
+    pub floor_idle_timeout: u64,
+}
+
+/// 客户端可调编码参数的默认值与上下限
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EncodingLimits {
+    pub default_fps: u32,
+    pub min_fps: u32,
+    pub max_fps: u32,
+    /// 码率 (bps)
+    pub default_bitrate: usize,
+    pub min_bitrate: usize,
+    pub max_bitrate: usize,
+    /// 关键帧间隔（秒）
+    pub default_keyframe_interval: u32,
+    pub min_keyframe_interval: u32,
+    pub max_keyframe_interval: u32,
+}
+
+impl Default for ServerSection {
+    fn default() -> Self {
+        Self {
+            listen: vec![ListenEndpoint::Tcp {
+                addr: SocketAddr::from(([0, 0, 0, 0], 8080)),
+                dual_stack: false,
+                tls: true,
+            }],
+            web_dir: None,
+            redirect_http: true,
+            http_redirect_bind: None,
+        }
+    }
+}
+
+impl Default for AccessSection {
+    fn default() -> Self {
+        Self {
+            allow: Vec::new(),
+            deny: Vec::new(),
+            max_sessions_per_ip: 32,
+            connections_per_minute: 120,
+            connection_burst: 30,
+        }
+    }
+}
+
+impl Default for AuditSection {
+    fn default() -> Self {
+        Self {
+            path: None,
+            log_keystrokes: false,
+            input_summary_interval: 60,
+        }
+    }
+}
+
+impl Default for TlsFiles {
+    fn default() -> Self {
+        Self {
+            cert: PathBuf::from("cert.pem"),
+            key: PathBuf::from("key.pem"),
+        }
+    }
+}
+
+impl Default for AuthSection {
+    fn default() -> Self {
+        Self {
+            credentials_file: PathBuf::from("credentials.txt"),
+        }
+    }
+}
+
+impl Default for ConsentSection {
+    fn default() -> Self {
+        Self {
+            mode: ConsentMode::Off,
+            timeout: 30,
+        }
+    }
+}
+
+impl ConsentSection {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+}
+
+impl Default for PairingFiles {
+    fn default() -> Self {
+        Self {
+            devices: PathBuf::from("paired_devices.json"),
+            ca_cert: PathBuf::from("pairing-ca.pem"),
+            ca_key: PathBuf::from("pairing-ca-key.pem"),
+        }
+    }
+}
+
+impl Default for ControlSection {
+    fn default() -> Self {
+        Self {
+            floor_mode: FloorMode::Exclusive,
+            floor_idle_timeout: 5,
+        }
+    }
+}
+
+impl ControlSection {
+    pub fn floor_idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.floor_idle_timeout)
+    }
+}
+
+impl Default for EncodingLimits {
+    fn default() -> Self {
+        Self {
+            default_fps: 60,
+            min_fps: 24,
+            max_fps: 120,
+            default_bitrate: 20_000_000,
+            min_bitrate: 2_000_000,
+            max_bitrate: 80_000_000,
+            default_keyframe_interval: 2,
+            min_keyframe_interval: 1,
+            max_keyframe_interval: 10,
+        }
+    }
+}
+
+impl EncodingLimits {
+    pub fn clamp_fps(&self, fps: u32) -> u32 {
+        fps.clamp(self.min_fps, self.max_fps)
+    }
+
+    pub fn clamp_bitrate(&self, bitrate: usize) -> usize {
+        bitrate.clamp(self.min_bitrate, self.max_bitrate)
+    }
+
+    pub fn clamp_keyframe_interval(&self, secs: u32) -> u32 {
+        secs.clamp(self.min_keyframe_interval, self.max_keyframe_interval)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_range(
+            "帧率",
+            self.min_fps,
+            self.default_fps,
+            self.max_fps,
+            FPS_CEILING,
+        )?;
+        validate_range(
+            "码率",
+            self.min_bitrate,
+            self.default_bitrate,
+            self.max_bitrate,
+            BITRATE_CEILING,
+        )?;
+        validate_range(
+            "关键帧间隔",
+            self.min_keyframe_interval,
+            self.default_keyframe_interval,
+            self.max_keyframe_interval,
+            KEYFRAME_INTERVAL_CEILING,
+        )
+    }
+}
+
+impl FromStr for ListenEndpoint {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let raw = raw.trim();
+        if let Some(path) = raw.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err("Unix 套接字路径不能为空".to_string());
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+
+        let (tls, addr) = match raw.strip_prefix("http:") {
+            Some(addr) => (false, addr),
+            None => (true, raw),
+        };
+        let (dual_stack, addr) = match addr.strip_prefix("dual:") {
+            Some(addr) => (true, addr),
+            None => (false, addr),
+        };
+        let addr: SocketAddr = addr
+            .parse()
+            .map_err(|e| format!("监听地址无效 '{}': {}", raw, e))?;
+        if dual_stack && !addr.is_ipv6() {
+            return Err(format!("双栈监听需要 IPv6 地址: {}", raw));
+        }
+        Ok(Self::Tcp {
+            addr,
+            dual_stack,
+            tls,
+        })
+    }
+}
+
+impl TryFrom<String> for ListenEndpoint {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        raw.parse()
+    }
+}
+
+impl fmt::Display for ListenEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp {
+                addr,
+                dual_stack,
+                tls,
+            } => {
+                if !tls {
+                    write!(f, "http:")?;
+                }
+                if *dual_stack {
+                    write!(f, "dual:")?;
+                }
+                write!(f, "{}", addr)
+            }
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl AccessSection {
+    /// 是否启用了依赖客户端地址的访问策略
+    pub fn restricts_by_address(&self) -> bool {
+        !self.allow.is_empty()
+            || !self.deny.is_empty()
+            || self.max_sessions_per_ip > 0
+            || self.connections_per_minute > 0
+    }
+}
+
+impl IpCidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                prefix_matches(&network.octets(), &ip.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                prefix_matches(&network.octets(), &ip.octets(), self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_matches(network: &[u8], ip: &[u8], prefix_len: u8) -> bool {
+    let full_bytes = prefix_len as usize / 8;
+    let rest_bits = prefix_len % 8;
+    if network[..full_bytes] != ip[..full_bytes] {
+        return false;
+    }
+    if rest_bits == 0 {
+        return true;
+    }
+    let mask = 0xffu8 << (8 - rest_bits);
+    network[full_bytes] & mask == ip[full_bytes] & mask
+}
+
+impl FromStr for IpCidr {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let raw = raw.trim();
+        let (addr, prefix_len) = match raw.split_once('/') {
+            Some((addr, len)) => (addr, Some(len)),
+            None => (raw, None),
+        };
+        let network = addr
+            .parse::<IpAddr>()
+            .map_err(|e| format!("地址段无效 '{}': {}", raw, e))?
+            .to_canonical();
+        let max_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => len
+                .parse::<u8>()
+                .ok()
+                .filter(|&len| len <= max_len)
+                .ok_or_else(|| format!("前缀长度无效 '{}'（最大 {}）", raw, max_len))?,
+            None => max_len,
+        };
+
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+impl TryFrom<String> for IpCidr {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        raw.parse()
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+/// 规范化路径前缀：以 `/` 开头、不以 `/` 结尾，根路径为空字符串
+fn normalize_base_path(raw: &str) -> Result<String, String> {
+    let trimmed = raw.trim().trim_end_matches('/');
+    if trimmed.is_empty() {
+        return Ok(String::new());
+    }
+    if !trimmed.starts_with('/') {
+        return Err(format!("路径前缀需以 / 开头: {}", raw));
+    }
+    let invalid = trimmed
+        .chars()
+        .any(|c| matches!(c, '?' | '#' | '{' | '}' | '%') || c.is_whitespace());
+    if invalid || trimmed.contains("//") {
+        return Err(format!("路径前缀包含无效字符: {}", raw));
+    }
+    Ok(trimmed.to_string())
+}
+
+impl ServerSection {
+    /// 第一个 TLS 端点的端口，用于重定向与日志中的访问地址
+    pub fn https_port(&self) -> Option<u16> {
+        self.listen.iter().find_map(|endpoint| match endpoint {
+            ListenEndpoint::Tcp {
+                addr, tls: true, ..
+            } => Some(addr.port()),
+            _ => None,
+        })
+    }
+
+    fn validate_listen(&self) -> Result<(), String> {
+        if self.listen.is_empty() {
+            return Err("至少需要配置一个监听端点".to_string());
+        }
+        for (index, endpoint) in self.listen.iter().enumerate() {
+            if self.listen[..index].contains(endpoint) {
+                return Err(format!("监听端点重复: {}", endpoint));
+            }
+            if matches!(endpoint, ListenEndpoint::Unix(_)) && !cfg!(unix) {
+                return Err(format!("当前平台不支持 Unix 套接字监听: {}", endpoint));
+            }
+        }
+
+        if let Some(redirect_addr) = self.http_redirect_bind {
+            if self.https_port().is_none() {
+                return Err("启用 HTTP 重定向监听时至少需要一个 TLS 监听端点".to_string());
+            }
+            let conflicts = self.listen.iter().any(|endpoint| {
+                matches!(endpoint, ListenEndpoint::Tcp { addr, .. } if *addr == redirect_addr)
+            });
+            if conflicts {
+                return Err(format!(
+                    "HTTP 重定向监听地址不能与主监听地址相同: {}（主端口的明文请求由 redirect_http 处理）",
+                    redirect_addr
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn validate_range<T: PartialOrd + std::fmt::Display + Default>(
+    label: &str,
+    min: T,
+    default: T,
+    max: T,
+    ceiling: T,
+) -> Result<(), String> {
+    if min <= T::default() {
+        return Err(format!("{}下限必须大于 0（当前 {}）", label, min));
+    }
+    if max > ceiling {
+        return Err(format!("{}上限不能超过 {}（当前 {}）", label, ceiling, max));
+    }
+    if !(min <= default && default <= max) {
+        return Err(format!(
+            "{}需满足 下限 <= 默认值 <= 上限（当前 {} / {} / {}）",
+            label, min, default, max
+        ));
+    }
+    Ok(())
+}
+
+impl Config {
+    /// 按 默认值 < 配置文件 < 环境变量 < 命令行参数 的优先级加载配置并校验
+    pub fn load(args: &[String]) -> Result<Self, String> {
+        let cli = parse_cli_args(args)?;
+
+        let config_path = cli
+            .config_path
+            .clone()
+            .or_else(|| std::env::var(CONFIG_PATH_ENV).ok().map(PathBuf::from));
+        let mut config = match &config_path {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => Self::default(),
+        };
+
+        for name in OVERRIDE_NAMES {
+            let env_name = override_env_name(name);
+            if let Ok(value) = std::env::var(&env_name) {
+                config
+                    .apply_override(name, &value)
+                    .map_err(|e| format!("环境变量 {}: {}", env_name, e))?;
+            }
+        }
+        for (name, value) in &cli.overrides {
+            config
+                .apply_override(name, value)
+                .map_err(|e| format!("参数 --{}: {}", name, e))?;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self, String> {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| format!("读取配置文件 {} 失败: {}", path.display(), e))?;
+        let config = toml::from_str(&raw)
+            .map_err(|e| format!("解析配置文件 {} 失败: {}", path.display(), e))?;
+        log::info!("已加载配置文件 {}", path.display());
+        Ok(config)
+    }
+
+    fn apply_override(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let limits = &mut self.encoding;
+        match name {
+            "listen" => self.server.listen = parse_list(value)?,
+            "tls-cert" => self.tls.cert = PathBuf::from(value),
+            "tls-key" => self.tls.key = PathBuf::from(value),
+            "web-dir" => self.server.web_dir = Some(PathBuf::from(value)),
+            "redirect-http" => self.server.redirect_http = parse_value(value)?,
+            "base-path" => self.proxy.base_path = value.to_string(),
+            "trusted-proxies" => self.proxy.trusted_proxies = parse_list(value)?,
+            "plain-http-direct" => self.proxy.plain_http_direct = parse_value(value)?,
+            "allow" => self.access.allow = parse_list(value)?,
+            "deny" => self.access.deny = parse_list(value)?,
+            "max-sessions-per-ip" => self.access.max_sessions_per_ip = parse_value(value)?,
+            "connections-per-minute" => self.access.connections_per_minute = parse_value(value)?,
+            "connection-burst" => self.access.connection_burst = parse_value(value)?,
+            "audit-log" => {
+                self.audit.path = match value {
+                    "" | "off" => None,
+                    path => Some(PathBuf::from(path)),
+                }
+            }
+            "audit-keystrokes" => self.audit.log_keystrokes = parse_value(value)?,
+            "audit-input-interval" => self.audit.input_summary_interval = parse_value(value)?,
+            "metrics-token" => {
+                self.metrics.token = match value {
+                    "" => None,
+                    token => Some(token.to_string()),
+                }
+            }
+            "metrics-allow" => self.metrics.allow = parse_list(value)?,
+            "credentials-file" => self.auth.credentials_file = PathBuf::from(value),
+            "admin-token" => {
+                self.admin.token = match value {
+                    "" => None,
+                    token => Some(token.to_string()),
+                }
+            }
+            "consent" => self.consent.mode = parse_value(value)?,
+            "consent-timeout" => self.consent.timeout = parse_value(value)?,
+            "paired-devices" => self.pairing.devices = PathBuf::from(value),
+            "pairing-ca-cert" => self.pairing.ca_cert = PathBuf::from(value),
+            "pairing-ca-key" => self.pairing.ca_key = PathBuf::from(value),
+            "floor-mode" => self.control.floor_mode = parse_value(value)?,
+            "floor-idle-timeout" => self.control.floor_idle_timeout = parse_value(value)?,
+            "http-redirect-bind" => {
+                self.server.http_redirect_bind = match value {
+                    "" | "off" => None,
+                    addr => Some(parse_value(addr)?),
+                }
+            }
+            "fps" => limits.default_fps = parse_value(value)?,
+            "min-fps" => limits.min_fps = parse_value(value)?,
+            "max-fps" => limits.max_fps = parse_value(value)?,
+            "bitrate" => limits.default_bitrate = parse_value(value)?,
+            "min-bitrate" => limits.min_bitrate = parse_value(value)?,
+            "max-bitrate" => limits.max_bitrate = parse_value(value)?,
+            "keyframe-interval" => limits.default_keyframe_interval = parse_value(value)?,
+            "min-keyframe-interval" => limits.min_keyframe_interval = parse_value(value)?,
+            "max-keyframe-interval" => limits.max_keyframe_interval = parse_value(value)?,
+            _ => return Err("未知配置项".to_string()),
+        }
+        Ok(())
+    }
+
+    fn validate(&mut self) -> Result<(), String> {
+        if self.tls.cert.as_os_str().is_empty() || self.tls.key.as_os_str().is_empty() {
+            return Err("证书与私钥路径不能为空".to_string());
+        }
+        if self.tls.cert == self.tls.key {
+            return Err(format!(
+                "证书与私钥不能使用同一个文件: {}",
+                self.tls.cert.display()
+            ));
+        }
+        if self.tls.cert.exists() != self.tls.key.exists() {
+            return Err(format!(
+                "证书 {} 与私钥 {} 必须同时存在（都不存在时自动生成自签名证书）",
+                self.tls.cert.display(),
+                self.tls.key.display()
+            ));
+        }
+        self.validate_files()?;
+        self.server.validate_listen()?;
+        self.proxy.base_path = normalize_base_path(&self.proxy.base_path)?;
+        let has_plain_tcp = self
+            .server
+            .listen
+            .iter()
+            .any(|endpoint| matches!(endpoint, ListenEndpoint::Tcp { tls: false, .. }));
+        if has_plain_tcp && self.proxy.trusted_proxies.is_empty() && !self.proxy.plain_http_direct {
+            // 经代理接入时所有客户端地址都是代理地址，按地址的策略与主机端确认无法区分客户端
+            if self.access.restricts_by_address() || self.consent.mode != ConsentMode::Off {
+                return Err(
+                    "存在明文 HTTP 监听端点但未配置 trusted_proxies，allow/deny、按 IP 限制与主机端确认无法区分客户端；\
+                     请配置 trusted_proxies，或在客户端直接访问该端点时设置 plain_http_direct = true"
+                        .to_string(),
+                );
+            }
+            log::warn!(
+                "存在明文 HTTP 监听端点但未配置 trusted_proxies，客户端地址将记录为代理地址"
+            );
+        }
+        if self.access.connections_per_minute > 0 && self.access.connection_burst == 0 {
+            return Err("启用连接速率限制时 connection_burst 不能为 0".to_string());
+        }
+        if self.audit.input_summary_interval == 0 {
+            return Err("审计输入汇总间隔必须大于 0".to_string());
+        }
+        if self.audit.log_keystrokes && self.audit.path.is_none() {
+            log::warn!("已启用逐条按键审计但未配置审计日志路径，不会记录");
+        }
+        if self.consent.mode != ConsentMode::Off && self.consent.timeout == 0 {
+            return Err("主机端确认超时必须大于 0".to_string());
+        }
+        self.admin.token = self
+            .admin
+            .token
+            .take()
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty());
+        self.metrics.token = self
+            .metrics
+            .token
+            .take()
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty());
+        if self.control.floor_mode == FloorMode::Exclusive && self.control.floor_idle_timeout == 0 {
+            return Err("独占输入仲裁的空闲接管时间必须大于 0".to_string());
+        }
+        self.encoding.validate()?;
+
+        match &self.server.web_dir {
+            Some(dir) if !dir.is_dir() => {
+                return Err(format!("前端目录 {} 不存在", dir.display()));
+            }
+            Some(_) => {}
+            // 编译前前端可能尚未构建，缺失只提示不中止
+            None if !crate::server::assets::has_embedded_assets() => {
+                log::warn!("构建时未嵌入前端文件（web/dist 不存在），Web 界面将不可用");
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// 凭据、配对与证书文件路径的一致性
+    fn validate_files(&self) -> Result<(), String> {
+        let pairing = &self.pairing;
+        let paths = [
+            ("凭据文件", &self.auth.credentials_file),
+            ("配对设备文件", &pairing.devices),
+            ("配对 CA 证书", &pairing.ca_cert),
+            ("配对 CA 私钥", &pairing.ca_key),
+        ];
+        for (label, path) in paths {
+            if path.as_os_str().is_empty() {
+                return Err(format!("{}路径不能为空", label));
+            }
+        }
+        if self.auth.credentials_file.exists() && !self.auth.credentials_file.is_file() {
+            return Err(format!(
+                "凭据文件 {} 不是普通文件",
+                self.auth.credentials_file.display()
+            ));
+        }
+        if pairing.ca_cert == pairing.ca_key {
+            return Err(format!(
+                "配对 CA 证书与私钥不能使用同一个文件: {}",
+                pairing.ca_cert.display()
+            ));
+        }
+        let tls_paths = [&self.tls.cert, &self.tls.key];
+        if tls_paths.contains(&&pairing.ca_cert) || tls_paths.contains(&&pairing.ca_key) {
+            return Err("配对 CA 不能与 HTTPS 证书使用同一组文件".to_string());
+        }
+        if pairing.ca_cert.exists() != pairing.ca_key.exists() {
+            return Err(format!(
+                "配对 CA 证书 {} 与私钥 {} 必须同时存在（都不存在时自动生成）",
+                pairing.ca_cert.display(),
+                pairing.ca_key.display()
+            ));
+        }
+        if let Some(dir) = pairing.devices.parent() {
+            if !dir.as_os_str().is_empty() && !dir.is_dir() {
+                return Err(format!("配对设备文件所在目录 {} 不存在", dir.display()));
+            }
+        }
+        Ok(())
+    }
+}
+
+struct CliArgs {
+    config_path: Option<PathBuf>,
+    overrides: Vec<(String, String)>,
+}
+
+fn parse_cli_args(args: &[String]) -> Result<CliArgs, String> {
+    let mut cli = CliArgs {
+        config_path: None,
+        overrides: Vec::new(),
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let Some(flag) = arg.strip_prefix("--") else {
+            return Err(format!("无法识别的参数: {}", arg));
+        };
+        let (name, inline_value) = match flag.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (flag, None),
+        };
+        if name != "config" && !OVERRIDE_NAMES.contains(&name) {
+            return Err(format!("未知参数 --{}（可用: {}）", name, usage_flags()));
+        }
+
+        let value = match inline_value {
+            Some(value) => value,
+            None => iter
+                .next()
+                .cloned()
+                .ok_or_else(|| format!("参数 --{} 缺少取值", name))?,
+        };
+        if name == "config" {
+            cli.config_path = Some(PathBuf::from(value));
+        } else {
+            cli.overrides.push((name.to_string(), value));
+        }
+    }
+
+    Ok(cli)
+}
+
+fn parse_value<T: std::str::FromStr>(value: &str) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e| format!("取值无效 '{}': {}", value, e))
+}
+
+/// 逗号分隔的列表，忽略空项
+fn parse_list<T: std::str::FromStr>(value: &str) -> Result<Vec<T>, String>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(parse_value)
+        .collect()
+}
+
+fn override_env_name(name: &str) -> String {
+    format!(
+        "{}{}",
+        ENV_PREFIX,
+        name.to_ascii_uppercase().replace('-', "_")
+    )
+}
+
+fn usage_flags() -> String {
+    std::iter::once("config")
+        .chain(OVERRIDE_NAMES.iter().copied())
+        .map(|name| format!("--{}", name))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::auth::{Authenticator, hash_secret};
+
+    fn args(raw: &[&str]) -> Vec<String> {
+        raw.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn validated(overrides: &[(&str, &str)]) -> Result<Config, String> {
+        let mut config = Config::default();
+        for (name, value) in overrides {
+            config.apply_override(name, value)?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn parses_cli_flags() {
+        let cli = parse_cli_args(&args(&[
+            "--config",
+            "desk.toml",
+            "--max-fps=90",
+            "--consent",
+            "http",
+        ]))
+        .unwrap();
+        assert_eq!(cli.config_path, Some(PathBuf::from("desk.toml")));
+        assert_eq!(
+            cli.overrides,
+            vec![
+                ("max-fps".to_string(), "90".to_string()),
+                ("consent".to_string(), "http".to_string()),
+            ]
+        );
+
+        assert!(parse_cli_args(&args(&["--no-such-flag", "1"])).is_err());
+        assert!(parse_cli_args(&args(&["max-fps", "90"])).is_err());
+        assert!(parse_cli_args(&args(&["--max-fps"])).is_err());
+    }
+
+    #[test]
+    fn env_names_follow_flags() {
+        assert_eq!(override_env_name("max-fps"), "WEBDISPLAY_MAX_FPS");
+        assert_eq!(
+            override_env_name("floor-idle-timeout"),
+            "WEBDISPLAY_FLOOR_IDLE_TIMEOUT"
+        );
+    }
+
+    #[test]
+    fn every_override_name_is_applied() {
+        let mut config = Config::default();
+        for name in OVERRIDE_NAMES {
+            // 取值可能无效，但不能是未知配置项
+            if let Err(e) = config.apply_override(name, "") {
+                assert_ne!(e, "未知配置项", "--{}", name);
+            }
+        }
+        assert_eq!(
+            config.apply_override("no-such-option", "1"),
+            Err("未知配置项".to_string())
+        );
+    }
+
+    #[test]
+    fn applies_overrides() {
+        let config = validated(&[
+            ("fps", "30"),
+            ("consent", "both"),
+            ("consent-timeout", "45"),
+            ("floor-mode", "free"),
+            ("floor-idle-timeout", "0"),
+            ("audit-log", "off"),
+            ("credentials-file", "users.txt"),
+        ])
+        .unwrap();
+        assert_eq!(config.encoding.default_fps, 30);
+        assert_eq!(config.consent.mode, ConsentMode::Both);
+        assert_eq!(config.consent.timeout(), Duration::from_secs(45));
+        assert_eq!(config.control.floor_mode, FloorMode::Free);
+        assert!(config.audit.path.is_none());
+        assert_eq!(config.auth.credentials_file, PathBuf::from("users.txt"));
+
+        let mut config = Config::default();
+        assert!(config.apply_override("max-fps", "fast").is_err());
+        assert!(config.apply_override("consent", "maybe").is_err());
+    }
+
+    #[test]
+    fn loads_toml_sections() {
+        let config: Config = toml::from_str(
+            r#"
+            [encoding]
+            max_fps = 144
+
+            [consent]
+            mode = "stdin"
+
+            [control]
+            floor_mode = "free"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.encoding.max_fps, 144);
+        assert_eq!(config.encoding.min_fps, EncodingLimits::default().min_fps);
+        assert_eq!(config.consent.mode, ConsentMode::Stdin);
+        assert_eq!(config.control.floor_mode, FloorMode::Free);
+
+        assert!(toml::from_str::<Config>("[encoding]\nmax_fsp = 1\n").is_err());
+        assert!(toml::from_str::<Config>("[consent]\nmode = \"maybe\"\n").is_err());
+    }
+
+    #[test]
+    fn trims_tokens() {
+        let config = validated(&[("admin-token", "  secret  ")]).unwrap();
+        assert_eq!(config.admin.token.as_deref(), Some("secret"));
+
+        let mut config = Config::default();
+        config.admin.token = Some("   ".to_string());
+        config.validate().unwrap();
+        assert!(config.admin.token.is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_limits() {
+        assert!(validated(&[]).is_ok());
+        assert!(validated(&[("min-fps", "0")]).is_err());
+        assert!(validated(&[("max-fps", "1000")]).is_err());
+        assert!(validated(&[("fps", "200")]).is_err());
+        assert!(validated(&[("min-bitrate", "90000000")]).is_err());
+        assert!(validated(&[("keyframe-interval", "0")]).is_err());
+        assert!(validated(&[("audit-input-interval", "0")]).is_err());
+        assert!(validated(&[("consent", "http"), ("consent-timeout", "0")]).is_err());
+        assert!(validated(&[("floor-idle-timeout", "0")]).is_err());
+    }
+
+    #[test]
+    fn rejects_conflicting_files() {
+        assert!(validated(&[("credentials-file", "")]).is_err());
+        assert!(validated(&[("pairing-ca-key", "pairing-ca.pem")]).is_err());
+        assert!(validated(&[("pairing-ca-cert", "cert.pem")]).is_err());
+        assert!(validated(&[("pairing-ca-key", "key.pem")]).is_err());
+        assert!(validated(&[("tls-key", "cert.pem")]).is_err());
+        assert!(validated(&[("paired-devices", "no-such-dir/devices.json")]).is_err());
+        // 凭据路径存在但不是文件
+        assert!(validated(&[("credentials-file", "src")]).is_err());
+    }
+
+    #[test]
+    fn loads_documented_credentials_file() {
+        let path = std::env::temp_dir().join(format!(
+            "webdisplay-config-credentials-{}.txt",
+            std::process::id()
+        ));
+        let hash = hash_secret("secret").unwrap();
+        std::fs::write(&path, format!("# 名称 角色 哈希\nalice  admin\t{}\n", hash)).unwrap();
+
+        let config = validated(&[("credentials-file", path.to_str().unwrap())]).unwrap();
+        let auth = Authenticator::load(&config.auth.credentials_file).unwrap();
+        assert!(auth.is_enabled());
+
+        // 冒号分隔的写法不被接受
+        std::fs::write(&path, format!("admin:alice:{}\n", hash)).unwrap();
+        assert!(Authenticator::load(&config.auth.credentials_file).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parses_listen_endpoints() {
+        let tcp = |addr: &str, dual_stack, tls| ListenEndpoint::Tcp {
+            addr: addr.parse().unwrap(),
+            dual_stack,
+            tls,
+        };
+        assert_eq!(
+            "0.0.0.0:8080".parse::<ListenEndpoint>(),
+            Ok(tcp("0.0.0.0:8080", false, true))
+        );
+        assert_eq!(
+            "[::]:8080".parse::<ListenEndpoint>(),
+            Ok(tcp("[::]:8080", false, true))
+        );
+        assert_eq!(
+            "dual:[::]:8080".parse::<ListenEndpoint>(),
+            Ok(tcp("[::]:8080", true, true))
+        );
+        assert_eq!(
+            "http:dual:[::1]:8081".parse::<ListenEndpoint>(),
+            Ok(tcp("[::1]:8081", true, false))
+        );
+        assert_eq!(
+            " unix:/run/webdisplay.sock ".parse::<ListenEndpoint>(),
+            Ok(ListenEndpoint::Unix(PathBuf::from("/run/webdisplay.sock")))
+        );
+
+        assert!("unix:".parse::<ListenEndpoint>().is_err());
+        assert!("dual:0.0.0.0:8080".parse::<ListenEndpoint>().is_err());
+        assert!("localhost:8080".parse::<ListenEndpoint>().is_err());
+        assert!("0.0.0.0".parse::<ListenEndpoint>().is_err());
+    }
+
+    #[test]
+    fn listen_endpoints_round_trip() {
+        for raw in [
+            "0.0.0.0:8080",
+            "http:127.0.0.1:8081",
+            "dual:[::]:8080",
+            "http:dual:[::]:8081",
+            "unix:/run/webdisplay.sock",
+        ] {
+            let endpoint: ListenEndpoint = raw.parse().unwrap();
+            assert_eq!(endpoint.to_string(), raw);
+        }
+    }
+
+    #[test]
+    fn validates_listen_endpoints() {
+        let config = validated(&[
+            ("listen", "http:127.0.0.1:8081, [::]:8443"),
+            ("plain-http-direct", "true"),
+        ])
+        .unwrap();
+        assert_eq!(config.server.listen.len(), 2);
+        assert_eq!(config.server.https_port(), Some(8443));
+
+        assert!(validated(&[("listen", "")]).is_err());
+        assert!(validated(&[("listen", "0.0.0.0:8080,0.0.0.0:8080")]).is_err());
+        assert!(
+            validated(&[
+                ("listen", "http:127.0.0.1:8081"),
+                ("plain-http-direct", "true"),
+                ("http-redirect-bind", "0.0.0.0:80"),
+            ])
+            .is_err()
+        );
+        assert!(
+            validated(&[
+                ("listen", "0.0.0.0:8080"),
+                ("http-redirect-bind", "0.0.0.0:8080"),
+            ])
+            .is_err()
+        );
+        assert!(validated(&[("http-redirect-bind", "0.0.0.0:80")]).is_ok());
+    }
+
+    #[test]
+    fn normalizes_base_paths() {
+        assert_eq!(normalize_base_path(""), Ok(String::new()));
+        assert_eq!(normalize_base_path(" / "), Ok(String::new()));
+        assert_eq!(normalize_base_path("/desk/"), Ok("/desk".to_string()));
+        assert_eq!(normalize_base_path("/a/b"), Ok("/a/b".to_string()));
+        assert!(normalize_base_path("desk").is_err());
+        assert!(normalize_base_path("/desk?x=1").is_err());
+        assert!(normalize_base_path("/my desk").is_err());
+        assert!(normalize_base_path("/a//b").is_err());
+        assert!(normalize_base_path("/{id}").is_err());
+
+        let config = validated(&[("base-path", "/desk/")]).unwrap();
+        assert_eq!(config.proxy.base_path, "/desk");
+    }
+
+    #[test]
+    fn plain_http_requires_trusted_proxies_for_address_policies() {
+        let plain = ("listen", "http:127.0.0.1:8081");
+        // 默认启用了按 IP 的并发与速率限制
+        assert!(validated(&[plain]).is_err());
+        assert!(validated(&[plain, ("trusted-proxies", "127.0.0.1")]).is_ok());
+        assert!(validated(&[plain, ("plain-http-direct", "true")]).is_ok());
+        assert!(
+            validated(&[
+                plain,
+                ("max-sessions-per-ip", "0"),
+                ("connections-per-minute", "0"),
+            ])
+            .is_ok()
+        );
+        assert!(
+            validated(&[
+                plain,
+                ("max-sessions-per-ip", "0"),
+                ("connections-per-minute", "0"),
+                ("consent", "http"),
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn parses_cidrs() {
+        let cidr: IpCidr = "10.0.0.0/8".parse().unwrap();
+        assert_eq!(cidr.to_string(), "10.0.0.0/8");
+        assert_eq!(
+            "192.0.2.1".parse::<IpCidr>().unwrap().to_string(),
+            "192.0.2.1/32"
+        );
+        assert_eq!("::1".parse::<IpCidr>().unwrap().to_string(), "::1/128");
+        assert_eq!(
+            "::ffff:10.0.0.0/8".parse::<IpCidr>().unwrap().to_string(),
+            "10.0.0.0/8"
+        );
+
+        assert!("10.0.0.0/33".parse::<IpCidr>().is_err());
+        assert!("2001:db8::/129".parse::<IpCidr>().is_err());
+        assert!("10.0.0.0/x".parse::<IpCidr>().is_err());
+        assert!("example.com".parse::<IpCidr>().is_err());
+    }
+
+    #[test]
+    fn matches_cidr_prefixes() {
+        let contains = |cidr: &str, ip: &str| {
+            cidr.parse::<IpCidr>()
+                .unwrap()
+                .contains(ip.parse().unwrap())
+        };
+        assert!(contains("10.0.0.0/8", "10.255.1.2"));
+        assert!(!contains("10.0.0.0/8", "11.0.0.1"));
+        assert!(contains("192.168.1.128/25", "192.168.1.200"));
+        assert!(!contains("192.168.1.128/25", "192.168.1.127"));
+        assert!(contains("0.0.0.0/0", "203.0.113.5"));
+        assert!(contains("2001:db8::/32", "2001:db8:1::5"));
+        assert!(!contains("2001:db8::/32", "2001:db9::5"));
+        assert!(contains("10.0.0.0/8", "::ffff:10.1.2.3"));
+        assert!(!contains("10.0.0.0/8", "2001:db8::1"));
+        assert!(!contains("::/0", "10.0.0.1"));
+    }
+
+    #[test]
+    fn address_restrictions() {
+        let unrestricted = AccessSection {
+            max_sessions_per_ip: 0,
+            connections_per_minute: 0,
+            ..AccessSection::default()
+        };
+        assert!(!unrestricted.restricts_by_address());
+        assert!(AccessSection::default().restricts_by_address());
+        assert!(
+            AccessSection {
+                deny: vec!["10.0.0.1".parse().unwrap()],
+                ..unrestricted.clone()
+            }
+            .restricts_by_address()
+        );
+        assert!(validated(&[("connections-per-minute", "60"), ("connection-burst", "0")]).is_err());
+        assert!(validated(&[("allow", "10.0.0.0/8, ::1"), ("deny", "10.0.0.13")]).is_ok());
+        assert!(validated(&[("allow", "10.0.0.0/40")]).is_err());
+    }
+}
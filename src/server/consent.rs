@@ -0,0 +1,84 @@
+use crate::control::consent::{ConsentGate, ConsentRequest, RemoteDecision};
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+type ApiError = (StatusCode, String);
+
+/// 允许/拒绝请求体：服务端控制台为每个连接请求打印的确认码
+#[derive(Debug, Deserialize)]
+struct DecisionRequest {
+    code: String,
+}
+
+/// 主机端确认接口，仅允许本机访问；反向代理转发的请求同样来自本机，
+/// 因此允许/拒绝还需提供控制台打印的确认码
+pub fn router(gate: Arc<ConsentGate>) -> Router {
+    Router::new()
+        .route("/api/consent", get(list_pending))
+        .route("/api/consent/{id}/accept", post(accept))
+        .route("/api/consent/{id}/reject", post(reject))
+        .with_state(gate)
+}
+
+fn authorize(gate: &ConsentGate, peer_addr: SocketAddr) -> Result<(), ApiError> {
+    if !gate.mode().allows_http() {
+        return Err((StatusCode::NOT_FOUND, "HTTP 确认未启用".to_string()));
+    }
+    if !peer_addr.ip().is_loopback() {
+        log::warn!("拒绝来自 {} 的确认接口访问", peer_addr);
+        return Err((StatusCode::FORBIDDEN, "确认接口仅允许本机访问".to_string()));
+    }
+    Ok(())
+}
+
+async fn list_pending(
+    State(gate): State<Arc<ConsentGate>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+) -> Result<Json<Vec<ConsentRequest>>, ApiError> {
+    authorize(&gate, peer_addr)?;
+    Ok(Json(gate.pending()))
+}
+
+async fn accept(
+    State(gate): State<Arc<ConsentGate>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<u64>,
+    Json(request): Json<DecisionRequest>,
+) -> Result<StatusCode, ApiError> {
+    decide(&gate, peer_addr, id, &request.code, true)
+}
+
+async fn reject(
+    State(gate): State<Arc<ConsentGate>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    Path(id): Path<u64>,
+    Json(request): Json<DecisionRequest>,
+) -> Result<StatusCode, ApiError> {
+    decide(&gate, peer_addr, id, &request.code, false)
+}
+
+fn decide(
+    gate: &ConsentGate,
+    peer_addr: SocketAddr,
+    id: u64,
+    code: &str,
+    approved: bool,
+) -> Result<StatusCode, ApiError> {
+    authorize(gate, peer_addr)?;
+    match gate.decide_with_code(id, code, approved) {
+        RemoteDecision::Applied => Ok(StatusCode::NO_CONTENT),
+        RemoteDecision::NotFound => Err((
+            StatusCode::NOT_FOUND,
+            format!("连接请求 #{} 不存在或已超时", id),
+        )),
+        RemoteDecision::WrongCode => {
+            log::warn!("来自 {} 的连接请求 #{} 确认码错误", peer_addr, id);
+            Err((StatusCode::FORBIDDEN, "确认码错误".to_string()))
+        }
+    }
+}
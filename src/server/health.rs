@@ -0,0 +1,262 @@
+use crate::encode::amf::{AmfEncoder, VideoCodec};
+use crate::server::auth::unix_now;
+use crate::server::tls::ServerCertResolver;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+/// WebTransport 端点的启动状态
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum EndpointStatus {
+    Starting,
+    Listening,
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct EncoderProbe {
+    codec: &'static str,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct HealthReport {
+    ready: bool,
+    monitors: MonitorCheck,
+    encoders: Vec<EncoderProbe>,
+    webtransport: Vec<WebTransportCheck>,
+    tls: TlsCheck,
+}
+
+#[derive(Debug, Serialize)]
+struct MonitorCheck {
+    ok: bool,
+    count: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct WebTransportCheck {
+    addr: SocketAddr,
+    #[serde(flatten)]
+    status: EndpointStatus,
+}
+
+#[derive(Debug, Serialize)]
+struct TlsCheck {
+    ok: bool,
+    /// HTTPS 证书过期时间（Unix 秒），无法解析时为空
+    expires_at: Option<u64>,
+    remaining_secs: Option<i64>,
+}
+
+/// 存活与就绪检查（/healthz、/readyz），供进程管理器判断服务是否可用
+pub struct Health {
+    monitor_count: usize,
+    encoders: Vec<EncoderProbe>,
+    webtransport: Mutex<BTreeMap<SocketAddr, EndpointStatus>>,
+    server_cert: Arc<ServerCertResolver>,
+}
+
+impl Health {
+    /// 启动时逐一试建各编码格式的编码器
+    pub fn probe(monitor_count: usize, server_cert: Arc<ServerCertResolver>) -> Self {
+        let encoders = VideoCodec::ALL
+            .iter()
+            .map(|&codec| {
+                let result = AmfEncoder::probe(codec);
+                if let Err(e) = &result {
+                    log::warn!("{} 编码器不可用: {}", codec, e);
+                }
+                EncoderProbe {
+                    codec: codec.as_client_name(),
+                    ok: result.is_ok(),
+                    error: result.err().map(|e| e.to_string()),
+                }
+            })
+            .collect();
+
+        Self {
+            monitor_count,
+            encoders,
+            webtransport: Mutex::new(BTreeMap::new()),
+            server_cert,
+        }
+    }
+
+    pub fn set_webtransport_status(&self, addr: SocketAddr, status: EndpointStatus) {
+        if let Ok(mut endpoints) = self.webtransport.lock() {
+            endpoints.insert(addr, status);
+        }
+    }
+
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/healthz", get(healthz))
+            .route("/readyz", get(readyz))
+            .with_state(self)
+    }
+
+    /// 至少一个显示器与一种编码格式可用、WebTransport 端点均已监听且证书未过期时才算就绪
+    fn report(&self) -> HealthReport {
+        let monitors = MonitorCheck {
+            ok: self.monitor_count > 0,
+            count: self.monitor_count,
+        };
+        let encoders_ok = self.encoders.iter().any(|probe| probe.ok);
+
+        let webtransport: Vec<_> = self
+            .webtransport
+            .lock()
+            .map(|endpoints| {
+                endpoints
+                    .iter()
+                    .map(|(addr, status)| WebTransportCheck {
+                        addr: *addr,
+                        status: status.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let webtransport_ok = webtransport
+            .iter()
+            .all(|check| matches!(check.status, EndpointStatus::Listening));
+
+        let expires_at = self.server_cert.expires_at();
+        let remaining_secs = expires_at.map(|expires_at| expires_at as i64 - unix_now() as i64);
+        let tls = TlsCheck {
+            ok: remaining_secs.is_some_and(|remaining| remaining > 0),
+            expires_at,
+            remaining_secs,
+        };
+
+        HealthReport {
+            ready: monitors.ok && encoders_ok && webtransport_ok && tls.ok,
+            monitors,
+            encoders: self.encoders.clone(),
+            webtransport,
+            tls,
+        }
+    }
+}
+
+/// 进程存活即返回 200，附带各项检查结果
+async fn healthz(State(health): State<Arc<Health>>) -> Json<HealthReport> {
+    Json(health.report())
+}
+
+async fn readyz(State(health): State<Arc<Health>>) -> (StatusCode, Json<HealthReport>) {
+    let report = health.report();
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::config::TlsFiles;
+    use axum::body::Body;
+    use axum::http::Request;
+    use std::sync::OnceLock;
+    use tower::ServiceExt;
+
+    /// 各测试共用一份证书，避免并行生成时互相覆盖
+    fn server_cert() -> Arc<ServerCertResolver> {
+        static SERVER_CERT: OnceLock<Arc<ServerCertResolver>> = OnceLock::new();
+        SERVER_CERT
+            .get_or_init(|| {
+                let dir =
+                    std::env::temp_dir().join(format!("webdisplay-health-{}", std::process::id()));
+                std::fs::create_dir_all(&dir).unwrap();
+                let files = TlsFiles {
+                    cert: dir.join("cert.pem"),
+                    key: dir.join("key.pem"),
+                };
+                Arc::new(ServerCertResolver::load(files).unwrap())
+            })
+            .clone()
+    }
+
+    fn health(monitor_count: usize, encoder_ok: bool) -> Health {
+        Health {
+            monitor_count,
+            encoders: vec![EncoderProbe {
+                codec: "h264",
+                ok: encoder_ok,
+                error: (!encoder_ok).then(|| "AMF 不可用".to_string()),
+            }],
+            webtransport: Mutex::new(BTreeMap::new()),
+            server_cert: server_cert(),
+        }
+    }
+
+    fn webtransport_addr() -> SocketAddr {
+        SocketAddr::from(([0, 0, 0, 0], 8080))
+    }
+
+    #[test]
+    fn ready_when_all_checks_pass() {
+        let report = health(2, true).report();
+        assert!(report.ready);
+        assert!(report.tls.ok);
+        assert!(report.tls.remaining_secs.unwrap() > 0);
+
+        assert!(!health(0, true).report().ready);
+        assert!(!health(1, false).report().ready);
+    }
+
+    #[test]
+    fn waits_for_webtransport_endpoints() {
+        let health = health(1, true);
+        health.set_webtransport_status(webtransport_addr(), EndpointStatus::Starting);
+        assert!(!health.report().ready);
+
+        health.set_webtransport_status(webtransport_addr(), EndpointStatus::Listening);
+        assert!(health.report().ready);
+
+        health.set_webtransport_status(
+            webtransport_addr(),
+            EndpointStatus::Failed {
+                error: "端口被占用".to_string(),
+            },
+        );
+        let report = health.report();
+        assert!(!report.ready);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["webtransport"][0]["addr"], "0.0.0.0:8080");
+        assert_eq!(json["webtransport"][0]["status"], "failed");
+        assert_eq!(json["webtransport"][0]["error"], "端口被占用");
+        assert!(json["encoders"][0].get("error").is_none());
+    }
+
+    #[tokio::test]
+    async fn readyz_reports_unavailable() {
+        let status_of = |health: Health, uri: &'static str| async move {
+            Arc::new(health)
+                .router()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap()
+                .status()
+        };
+
+        assert_eq!(status_of(health(1, true), "/readyz").await, StatusCode::OK);
+        assert_eq!(
+            status_of(health(0, true), "/readyz").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(status_of(health(0, true), "/healthz").await, StatusCode::OK);
+    }
+}
@@ -1,30 +1,78 @@
+use crate::control::audit::AuditEvent;
+use crate::control::consent::ConsentGate;
+use crate::control::role::SessionRole;
+use crate::server::access::{AccessDenied, AccessPolicy};
+use crate::server::admin::AdminApi;
+use crate::server::auth::Authenticator;
+use crate::server::config::ListenEndpoint;
+use crate::server::health::Health;
+use crate::server::metrics::MetricsEndpoint;
+use crate::server::pairing::{PairedClient, PairingStore};
+use crate::server::proxy::ForwardedResolver;
+use crate::server::shutdown::Shutdown;
+use crate::server::tls::WebTransportCerts;
+use crate::transport::session::SessionGrant;
 use crate::transport::websocket::WebSocketServer;
+use axum::Extension;
 use axum::Json;
 use axum::Router;
-use axum::http::{HeaderValue, header};
-use axum::routing::{get, get_service, post};
-use hyper::server::conn::http1;
-use hyper_util::rt::TokioIo;
+use axum::extract::ConnectInfo;
+use axum::http::{HeaderMap, HeaderValue, Uri, header};
+use axum::middleware;
+use axum::response::Redirect;
+use axum::routing::{get, post};
+use hyper::server::conn::{http1, http2};
+use hyper_util::rt::{TokioExecutor, TokioIo};
 use hyper_util::service::TowerToHyperService;
 use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tower::ServiceExt;
 use tower_http::services::ServeDir;
 use tower_http::set_header::SetResponseHeaderLayer;
 
+/// TLS 记录层的握手类型，用于区分 TLS 与明文 HTTP 连接
+const TLS_HANDSHAKE_RECORD: u8 = 0x16;
+/// TCP 监听队列长度
+const LISTEN_BACKLOG: i32 = 1024;
+/// Unix 套接字连接注入的对端地址
+#[cfg(unix)]
+const UNIX_SOCKET_PEER_ADDR: SocketAddr =
+    SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED), 0);
+/// HTTP/2 的 ALPN 标识
+pub const ALPN_HTTP2: &[u8] = b"h2";
+pub const ALPN_HTTP1: &[u8] = b"http/1.1";
+
 const CONTENT_SECURITY_POLICY: &str = "script-src 'self' 'unsafe-inline' 'unsafe-eval' blob:; connect-src 'self' ws: wss: https:; style-src 'self' 'unsafe-inline';";
-const ALT_SVC: &str = "h3=\":8080\"; ma=86400";
 
 #[derive(Serialize)]
-struct WebTransportHashResponse {
+struct WebTransportHash {
     algorithm: &'static str,
     value: Vec<u8>,
 }
 
+#[derive(Serialize)]
+struct WebTransportHashResponse {
+    /// 当前证书的哈希（兼容只读取单个哈希的客户端）
+    #[serde(flatten)]
+    current: WebTransportHash,
+    /// 当前及即将轮换到的证书哈希，均可用于 serverCertificateHashes
+    hashes: Vec<WebTransportHash>,
+}
+
 #[derive(Deserialize)]
 struct WebRtcOfferRequest {
     sdp: String,
+    /// 客户端请求的角色（不得高于授予角色）
+    #[serde(default)]
+    role: Option<String>,
+    /// 登录后获得的会话令牌
+    #[serde(default)]
+    token: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -32,14 +80,52 @@ struct WebRtcAnswerResponse {
     sdp: String,
 }
 
-fn build_router(
-    ws_server: Arc<WebSocketServer>,
-    webrtc_server: Arc<crate::transport::webrtc::WebRtcServer>,
-    webtransport_cert_hash: Arc<Vec<u8>>,
-) -> Router {
-    let static_files =
-        get_service(ServeDir::new("web/dist").append_index_html_on_directories(true));
-    let hash_for_route = webtransport_cert_hash.clone();
+/// HTTPS 服务挂载的各项服务
+pub struct HttpServices {
+    pub ws_server: Arc<WebSocketServer>,
+    pub webrtc_server: Arc<crate::transport::webrtc::WebRtcServer>,
+    pub webtransport_certs: Arc<WebTransportCerts>,
+    pub admin_api: Arc<AdminApi>,
+    pub metrics: Arc<MetricsEndpoint>,
+    pub health: Arc<Health>,
+    pub auth: Arc<Authenticator>,
+    pub consent: Arc<ConsentGate>,
+    pub pairing: Arc<PairingStore>,
+    /// 从磁盘提供前端文件的目录，未设置时使用编译时嵌入的文件
+    pub web_dir: Option<PathBuf>,
+    /// 所有路由的路径前缀，为空时挂载在根路径
+    pub base_path: String,
+    pub forwarded: Arc<ForwardedResolver>,
+    pub access: Arc<AccessPolicy>,
+}
+
+fn build_router(services: HttpServices) -> Router {
+    let HttpServices {
+        ws_server,
+        webrtc_server,
+        webtransport_certs,
+        admin_api,
+        metrics,
+        health,
+        auth,
+        consent,
+        pairing,
+        web_dir,
+        base_path,
+        forwarded: _,
+        access,
+    } = services;
+    let static_files = match web_dir {
+        Some(dir) => Router::new().fallback_service(
+            ServeDir::new(dir)
+                .append_index_html_on_directories(true)
+                .precompressed_br()
+                .precompressed_gzip(),
+        ),
+        None => Router::new().fallback(crate::server::assets::serve_embedded),
+    }
+    .layer(middleware::from_fn(crate::server::assets::cache_control));
+    let auth_for_offer = auth.clone();
 
     // To cleanly share states and isolate them, we need to apply router combination strategies in Axum.
     // Instead of chained .with_state on the same router (which requires state types to match),
@@ -52,10 +138,37 @@ fn build_router(
                 move |axum::extract::State(server): axum::extract::State<
                     Arc<crate::transport::webrtc::WebRtcServer>,
                 >,
-                      Json(payload): Json<WebRtcOfferRequest>| async move {
-                    match server.handle_offer(payload.sdp).await {
-                        Ok(sdp) => Ok(Json(WebRtcAnswerResponse { sdp })),
-                        Err(e) => Err((axum::http::StatusCode::INTERNAL_SERVER_ERROR, e)),
+                      ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+                      paired: Option<Extension<PairedClient>>,
+                      Json(payload): Json<WebRtcOfferRequest>| {
+                    let auth = auth_for_offer.clone();
+                    async move {
+                        let access = auth
+                            .authorize(
+                                peer_addr.ip(),
+                                payload.token.as_deref(),
+                                paired.as_ref().map(|Extension(device)| device),
+                            )
+                            .map_err(|e| {
+                                log::warn!("拒绝 WebRTC 连接 {}: {}", peer_addr, e);
+                                server.audit().record(AuditEvent::AccessDenied {
+                                    transport: "WebRTC",
+                                    peer_addr,
+                                    reason: e.to_string(),
+                                });
+                                (e.status_code(), e.to_string())
+                            })?;
+                        let grant = SessionGrant {
+                            peer_addr,
+                            role: SessionRole::negotiate(access.role, payload.role.as_deref()),
+                            max_role: access.role,
+                            share: access.share,
+                            identity: access.identity,
+                        };
+                        match server.handle_offer(payload.sdp, grant).await {
+                            Ok(sdp) => Ok(Json(WebRtcAnswerResponse { sdp })),
+                            Err(e) => Err((axum::http::StatusCode::INTERNAL_SERVER_ERROR, e)),
+                        }
                     }
                 },
             ),
@@ -67,47 +180,228 @@ fn build_router(
         .route(
             "/webtransport/hash",
             get(move || {
-                let hash = hash_for_route.clone();
+                let hashes: Vec<_> = webtransport_certs
+                    .hashes()
+                    .into_iter()
+                    .map(|value| WebTransportHash {
+                        algorithm: "sha-256",
+                        value,
+                    })
+                    .collect();
                 async move {
+                    let current = hashes.first().map(|hash| hash.value.clone());
                     Json(WebTransportHashResponse {
-                        algorithm: "sha-256",
-                        value: hash.as_ref().clone(),
+                        current: WebTransportHash {
+                            algorithm: "sha-256",
+                            value: current.unwrap_or_default(),
+                        },
+                        hashes,
                     })
                 }
             }),
         )
         .with_state(ws_server);
 
-    main_router
+    let app = main_router
         .merge(webrtc_router)
+        .merge(admin_api.router())
+        .merge(metrics.router())
+        .merge(health.router())
+        .merge(pairing.router(auth.clone()))
+        .merge(auth.router())
+        .merge(crate::server::consent::router(consent))
         .fallback_service(static_files)
         .layer(SetResponseHeaderLayer::if_not_present(
             header::CONTENT_SECURITY_POLICY,
             HeaderValue::from_static(CONTENT_SECURITY_POLICY),
         ))
-        .layer(SetResponseHeaderLayer::if_not_present(
-            header::ALT_SVC,
-            HeaderValue::from_static(ALT_SVC),
-        ))
+        .layer(middleware::from_fn_with_state(
+            access,
+            crate::server::access::enforce_address_rules,
+        ));
+    crate::server::proxy::with_base_path(app, &base_path)
 }
 
 pub async fn run_server(
+    listen: &[ListenEndpoint],
+    acceptor: tokio_rustls::TlsAcceptor,
+    services: HttpServices,
+    redirect_plain_http: bool,
+    shutdown: &Shutdown,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let pairing = services.pairing.clone();
+    let forwarded = services.forwarded.clone();
+    let access = services.access.clone();
+    let app = build_router(services);
+
+    // 先完成全部绑定，任一端点失败时直接报错退出
+    let mut listeners = JoinSet::new();
+    for endpoint in listen {
+        match endpoint {
+            ListenEndpoint::Tcp {
+                addr,
+                dual_stack,
+                tls: true,
+            } => {
+                let listener = bind_tcp_listener(*addr, *dual_stack)?;
+                // 告知浏览器同端口上的 HTTP/3（WebTransport）服务
+                let alt_svc = HeaderValue::from_str(&format!("h3=\":{}\"; ma=86400", addr.port()))?;
+                let app = app.clone().layer(SetResponseHeaderLayer::if_not_present(
+                    header::ALT_SVC,
+                    alt_svc,
+                ));
+                log::info!("HTTPS 服务器监听: {}", endpoint);
+                listeners.spawn(serve_tls_listener(
+                    listener,
+                    acceptor.clone(),
+                    app,
+                    pairing.clone(),
+                    forwarded.clone(),
+                    access.clone(),
+                    redirect_plain_http,
+                ));
+            }
+            ListenEndpoint::Tcp {
+                addr,
+                dual_stack,
+                tls: false,
+            } => {
+                let listener = bind_tcp_listener(*addr, *dual_stack)?;
+                log::info!("HTTP 服务器监听: {}（明文，由反向代理终止 TLS）", endpoint);
+                listeners.spawn(serve_plain_tcp_listener(
+                    listener,
+                    app.clone(),
+                    forwarded.clone(),
+                    access.clone(),
+                ));
+            }
+            #[cfg(unix)]
+            ListenEndpoint::Unix(path) => {
+                // 清理上次运行残留的套接字文件
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+                let listener = tokio::net::UnixListener::bind(path)?;
+                log::info!("HTTP 服务器监听: {}（明文，供本机反向代理）", endpoint);
+                listeners.spawn(serve_unix_listener(
+                    listener,
+                    app.clone(),
+                    forwarded.clone(),
+                ));
+            }
+            #[cfg(not(unix))]
+            ListenEndpoint::Unix(_) => {
+                return Err(format!("当前平台不支持 Unix 套接字监听: {}", endpoint).into());
+            }
+        }
+    }
+
+    // 各监听循环只会因错误退出；收到退出信号时丢弃全部监听，已建立的连接不受影响
+    tokio::select! {
+        Some(result) = listeners.join_next() => result??,
+        _ = shutdown.triggered() => log::info!("HTTP 服务已停止接受新连接"),
+    }
+    Ok(())
+}
+
+/// 绑定 TCP 监听；IPv6 地址显式设置是否双栈，避免依赖各平台的默认值
+fn bind_tcp_listener(addr: SocketAddr, dual_stack: bool) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(LISTEN_BACKLOG)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// 单个连接的对端信息
+#[derive(Clone)]
+struct ConnectionPeer {
     addr: SocketAddr,
+    /// 出示了配对 CA 签发证书的已配对设备
+    paired: Option<PairedClient>,
+    /// 经 Unix 套接字接入（只能来自本机反向代理，其转发头始终受信任）
+    via_unix_socket: bool,
+}
+
+/// 为连接上的每个请求注入客户端地址（及配对设备身份），供处理函数通过提取器获取
+async fn serve_connection<S>(
+    stream: S,
+    app: Router,
+    peer: ConnectionPeer,
+    forwarded: Arc<ForwardedResolver>,
+    http2: bool,
+) where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    let service = TowerToHyperService::new(app.map_request(
+        move |mut request: hyper::Request<hyper::body::Incoming>| {
+            let client_addr =
+                forwarded.client_addr(peer.addr, request.headers(), peer.via_unix_socket);
+            request.extensions_mut().insert(ConnectInfo(client_addr));
+            if let Some(device) = peer.paired.clone() {
+                request.extensions_mut().insert(device);
+            }
+            request
+        },
+    ));
+
+    // WebSocket 升级只走 HTTP/1.1：浏览器为 WebSocket 单独协商 http/1.1 连接
+    let io = TokioIo::new(stream);
+    let result = if http2 {
+        http2::Builder::new(TokioExecutor::new())
+            .serve_connection(io, service)
+            .await
+    } else {
+        http1::Builder::new()
+            .serve_connection(io, service)
+            .with_upgrades()
+            .await
+    };
+    if let Err(err) = result {
+        log::debug!("HTTP server connection error: {}", err);
+    }
+}
+
+async fn serve_tls_listener(
+    listener: TcpListener,
     acceptor: tokio_rustls::TlsAcceptor,
-    ws_server: Arc<WebSocketServer>,
-    webrtc_server: Arc<crate::transport::webrtc::WebRtcServer>,
-    webtransport_cert_hash: Arc<Vec<u8>>,
+    app: Router,
+    pairing: Arc<PairingStore>,
+    forwarded: Arc<ForwardedResolver>,
+    access: Arc<AccessPolicy>,
+    redirect_plain_http: bool,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let listener = TcpListener::bind(addr).await?;
-    let app = build_router(ws_server, webrtc_server, webtransport_cert_hash);
-    log::info!("HTTPS 服务器监听: https://{}", addr);
+    let redirect_app = redirect_router(listener.local_addr()?.port());
 
     loop {
-        let (stream, _) = listener.accept().await?;
+        let (stream, peer_addr) = listener.accept().await?;
+        // 在 TLS 握手前按地址规则拒绝，被拒连接不消耗握手开销；会话名额由串流会话自行占用
+        if !access.is_allowed(peer_addr.ip()) {
+            log::warn!("拒绝来自 {} 的连接: {}", peer_addr, AccessDenied::Blocked);
+            continue;
+        }
         let acceptor = acceptor.clone();
         let app = app.clone();
+        let redirect_app = redirect_app.clone();
+        let pairing = pairing.clone();
+        let forwarded = forwarded.clone();
 
         tokio::task::spawn(async move {
+            // 首字节不是 TLS 握手记录时按明文 HTTP 处理，重定向到 HTTPS
+            let mut first_byte = [0u8; 1];
+            let is_plain_http = redirect_plain_http
+                && matches!(stream.peek(&mut first_byte).await, Ok(1))
+                && first_byte[0] != TLS_HANDSHAKE_RECORD;
+            if is_plain_http {
+                serve_redirect(stream, redirect_app).await;
+                return;
+            }
+
             let stream = match acceptor.accept(stream).await {
                 Ok(s) => s,
                 Err(e) => {
@@ -116,16 +410,195 @@ pub async fn run_server(
                 }
             };
 
-            let io = TokioIo::new(stream);
-            let service = TowerToHyperService::new(app);
-
-            if let Err(err) = http1::Builder::new()
-                .serve_connection(io, service)
-                .with_upgrades()
-                .await
-            {
-                log::debug!("HTTP server connection error: {}", err);
+            // 出示了配对 CA 签发证书的连接，识别为已配对设备
+            let (_, connection) = stream.get_ref();
+            let paired = connection
+                .peer_certificates()
+                .and_then(|certs| pairing.identify(certs));
+            if let Some(device) = &paired {
+                log::info!("{} 以配对设备 #{} 身份连接", peer_addr, device.device_id);
             }
+            let is_http2 = connection.alpn_protocol() == Some(ALPN_HTTP2);
+
+            let peer = ConnectionPeer {
+                addr: peer_addr,
+                paired,
+                via_unix_socket: false,
+            };
+            serve_connection(stream, app, peer, forwarded, is_http2).await;
         });
     }
 }
+
+async fn serve_plain_tcp_listener(
+    listener: TcpListener,
+    app: Router,
+    forwarded: Arc<ForwardedResolver>,
+    access: Arc<AccessPolicy>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    loop {
+        let (stream, peer_addr) = listener.accept().await?;
+        // 受信任代理汇聚了多个客户端，不按连接检查，由请求级的地址规则按转发地址检查
+        if !forwarded.is_trusted(peer_addr.ip()) && !access.is_allowed(peer_addr.ip()) {
+            log::warn!("拒绝来自 {} 的连接: {}", peer_addr, AccessDenied::Blocked);
+            continue;
+        }
+        let peer = ConnectionPeer {
+            addr: peer_addr,
+            paired: None,
+            via_unix_socket: false,
+        };
+        tokio::task::spawn(serve_connection(
+            stream,
+            app.clone(),
+            peer,
+            forwarded.clone(),
+            false,
+        ));
+    }
+}
+
+#[cfg(unix)]
+async fn serve_unix_listener(
+    listener: tokio::net::UnixListener,
+    app: Router,
+    forwarded: Arc<ForwardedResolver>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        // 套接字没有对端 IP，未携带转发头时使用未指定地址：不会被当作本机回环，也不匹配任何 IP 规则
+        let peer = ConnectionPeer {
+            addr: UNIX_SOCKET_PEER_ADDR,
+            paired: None,
+            via_unix_socket: true,
+        };
+        tokio::task::spawn(serve_connection(
+            stream,
+            app.clone(),
+            peer,
+            forwarded.clone(),
+            false,
+        ));
+    }
+}
+
+/// 独立的明文 HTTP 监听，所有请求重定向到 HTTPS 端口
+pub async fn run_redirect_listener(
+    addr: SocketAddr,
+    https_port: u16,
+    access: Arc<AccessPolicy>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let listener = TcpListener::bind(addr).await?;
+    let redirect_app = redirect_router(https_port);
+    log::info!("HTTP 重定向监听: http://{} -> HTTPS :{}", addr, https_port);
+
+    loop {
+        let (stream, peer_addr) = listener.accept().await?;
+        if !access.is_allowed(peer_addr.ip()) {
+            log::warn!("拒绝来自 {} 的连接: {}", peer_addr, AccessDenied::Blocked);
+            continue;
+        }
+        tokio::task::spawn(serve_redirect(stream, redirect_app.clone()));
+    }
+}
+
+async fn serve_redirect(stream: TcpStream, redirect_app: Router) {
+    let service = TowerToHyperService::new(redirect_app);
+    if let Err(err) = http1::Builder::new()
+        .serve_connection(TokioIo::new(stream), service)
+        .await
+    {
+        log::debug!("HTTP redirect connection error: {}", err);
+    }
+}
+
+fn redirect_router(https_port: u16) -> Router {
+    Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move {
+        let host = headers
+            .get(header::HOST)
+            .and_then(|v| v.to_str().ok())
+            .map(strip_port)
+            .unwrap_or("localhost");
+        let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+
+        let location = if https_port == 443 {
+            format!("https://{}{}", host, path)
+        } else {
+            format!("https://{}:{}{}", host, https_port, path)
+        };
+        Redirect::permanent(&location)
+    })
+}
+
+/// 去掉 Host 头中的端口（兼容 IPv6 字面量 `[::1]:8080`）
+fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        return match host.find(']') {
+            Some(end) => &host[..=end],
+            None => host,
+        };
+    }
+    match host.rsplit_once(':') {
+        Some((name, _)) => name,
+        None => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+
+    async fn redirect_location(https_port: u16, host: Option<&str>, uri: &str) -> String {
+        let mut request = Request::builder().uri(uri);
+        if let Some(host) = host {
+            request = request.header(header::HOST, host);
+        }
+        let response = redirect_router(https_port)
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        response.headers()[header::LOCATION]
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn strips_host_ports() {
+        assert_eq!(strip_port("example.com:8080"), "example.com");
+        assert_eq!(strip_port("example.com"), "example.com");
+        assert_eq!(strip_port("192.168.1.10:80"), "192.168.1.10");
+        assert_eq!(strip_port("[::1]:8080"), "[::1]");
+        assert_eq!(strip_port("[::1]"), "[::1]");
+    }
+
+    #[tokio::test]
+    async fn redirects_to_https() {
+        assert_eq!(
+            redirect_location(8443, Some("desk.local:8080"), "/view?monitor=1").await,
+            "https://desk.local:8443/view?monitor=1"
+        );
+        assert_eq!(
+            redirect_location(443, Some("desk.local"), "/").await,
+            "https://desk.local/"
+        );
+        assert_eq!(
+            redirect_location(8443, None, "/").await,
+            "https://localhost:8443/"
+        );
+    }
+
+    #[tokio::test]
+    async fn binds_tcp_listeners() {
+        let listener = bind_tcp_listener(SocketAddr::from(([127, 0, 0, 1], 0)), false).unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert_ne!(addr.port(), 0);
+
+        let (client, accepted) = tokio::join!(TcpStream::connect(addr), listener.accept());
+        let (_, peer_addr) = accepted.unwrap();
+        assert_eq!(peer_addr, client.unwrap().local_addr().unwrap());
+    }
+}
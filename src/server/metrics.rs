@@ -0,0 +1,536 @@
+use crate::control::registry::SessionRegistry;
+use crate::server::auth::{Authenticator, constant_time_eq};
+use crate::server::config::{IpCidr, MetricsSection};
+use axum::Router;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 共享捕获（simulcast）的捕获与编码指标使用的标签，不属于任何单个传输
+pub const SIMULCAST_METRICS_LABEL: &str = "simulcast";
+
+/// 编码耗时直方图的桶上限（秒）
+const ENCODE_TIME_BUCKETS: &[f64] = &[
+    0.001, 0.002, 0.004, 0.008, 0.012, 0.016, 0.025, 0.033, 0.05, 0.1,
+];
+/// 往返时延直方图的桶上限（秒）
+const RTT_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+/// 固定桶的累计直方图
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// 每个桶（含 +Inf）各自的计数，导出时再累加
+    buckets: Vec<AtomicU64>,
+    sum_us: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum_us: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, value: Duration) {
+        let secs = value.as_secs_f64();
+        let index = self
+            .bounds
+            .iter()
+            .position(|&bound| secs <= bound)
+            .unwrap_or(self.bounds.len());
+        self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        self.sum_us
+            .fetch_add(value.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// 单个传输（或共享捕获）的指标
+pub struct TransportMetrics {
+    frames_captured: AtomicU64,
+    capture_timeouts: AtomicU64,
+    frames_encoded: AtomicU64,
+    frames_sent: AtomicU64,
+    frames_dropped: AtomicU64,
+    bytes_sent: AtomicU64,
+    keyframe_requests: AtomicU64,
+    encoder_reinits: AtomicU64,
+    encode_time: Histogram,
+    rtt: Histogram,
+}
+
+impl TransportMetrics {
+    fn new() -> Self {
+        Self {
+            frames_captured: AtomicU64::new(0),
+            capture_timeouts: AtomicU64::new(0),
+            frames_encoded: AtomicU64::new(0),
+            frames_sent: AtomicU64::new(0),
+            frames_dropped: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            keyframe_requests: AtomicU64::new(0),
+            encoder_reinits: AtomicU64::new(0),
+            encode_time: Histogram::new(ENCODE_TIME_BUCKETS),
+            rtt: Histogram::new(RTT_BUCKETS),
+        }
+    }
+
+    /// 一次捕获尝试：拿到新帧或等待超时
+    pub fn record_capture(&self, frame_ready: bool) {
+        let counter = if frame_ready {
+            &self.frames_captured
+        } else {
+            &self.capture_timeouts
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_encoded(&self, encode_time_us: u64) {
+        self.frames_encoded.fetch_add(1, Ordering::Relaxed);
+        self.encode_time
+            .observe(Duration::from_micros(encode_time_us));
+    }
+
+    pub fn record_sent(&self, bytes: usize) {
+        self.frames_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self, frames: u64) {
+        self.frames_dropped.fetch_add(frames, Ordering::Relaxed);
+    }
+
+    pub fn record_keyframe_request(&self) {
+        self.keyframe_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_encoder_reinit(&self) {
+        self.encoder_reinits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_rtt(&self, rtt: Duration) {
+        self.rtt.observe(rtt);
+    }
+}
+
+/// 以 Prometheus 文本格式导出的运行指标；各指标按 `transport` 标签区分，全局值可在查询时 sum
+pub struct Metrics {
+    transports: Mutex<BTreeMap<&'static str, Arc<TransportMetrics>>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            transports: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// 取得指定传输的指标，首次使用时创建
+    pub fn transport(&self, name: &'static str) -> Arc<TransportMetrics> {
+        match self.transports.lock() {
+            Ok(mut transports) => transports
+                .entry(name)
+                .or_insert_with(|| Arc::new(TransportMetrics::new()))
+                .clone(),
+            Err(_) => Arc::new(TransportMetrics::new()),
+        }
+    }
+
+    pub fn render(&self, sessions: &SessionRegistry) -> String {
+        let transports: Vec<_> = self
+            .transports
+            .lock()
+            .map(|t| t.iter().map(|(name, m)| (*name, m.clone())).collect())
+            .unwrap_or_default();
+
+        let mut active = BTreeMap::<&'static str, u64>::new();
+        for name in transports.iter().map(|(name, _)| *name) {
+            if name != SIMULCAST_METRICS_LABEL {
+                active.insert(name, 0);
+            }
+        }
+        for session in sessions.details() {
+            *active.entry(session.transport).or_default() += 1;
+        }
+
+        let mut out = String::new();
+        write_header(
+            &mut out,
+            "webdisplay_active_sessions",
+            "gauge",
+            "在线会话数",
+        );
+        for (transport, count) in &active {
+            let _ = writeln!(
+                out,
+                "webdisplay_active_sessions{{transport=\"{}\"}} {}",
+                transport, count
+            );
+        }
+
+        let counters: [(&str, &str, fn(&TransportMetrics) -> &AtomicU64); 8] = [
+            (
+                "webdisplay_frames_captured_total",
+                "捕获到的帧数",
+                |m| &m.frames_captured,
+            ),
+            (
+                "webdisplay_capture_timeouts_total",
+                "等待新帧超时次数",
+                |m| &m.capture_timeouts,
+            ),
+            (
+                "webdisplay_frames_encoded_total",
+                "编码输出的帧数",
+                |m| &m.frames_encoded,
+            ),
+            (
+                "webdisplay_frames_sent_total",
+                "发送给客户端的帧数",
+                |m| &m.frames_sent,
+            ),
+            (
+                "webdisplay_frames_dropped_total",
+                "因客户端落后等原因丢弃的帧数",
+                |m| &m.frames_dropped,
+            ),
+            (
+                "webdisplay_bytes_sent_total",
+                "发送的视频数据字节数",
+                |m| &m.bytes_sent,
+            ),
+            (
+                "webdisplay_keyframe_requests_total",
+                "关键帧请求次数",
+                |m| &m.keyframe_requests,
+            ),
+            (
+                "webdisplay_encoder_reinits_total",
+                "因切换显示器或编码参数重建编码器的次数",
+                |m| &m.encoder_reinits,
+            ),
+        ];
+        for (name, help, counter) in counters {
+            write_header(&mut out, name, "counter", help);
+            for (transport, metrics) in &transports {
+                let _ = writeln!(
+                    out,
+                    "{}{{transport=\"{}\"}} {}",
+                    name,
+                    transport,
+                    counter(metrics).load(Ordering::Relaxed)
+                );
+            }
+        }
+
+        let histograms: [(&str, &str, fn(&TransportMetrics) -> &Histogram); 2] = [
+            ("webdisplay_encode_seconds", "单帧编码耗时", |m| {
+                &m.encode_time
+            }),
+            (
+                "webdisplay_rtt_seconds",
+                "服务端 Ping 到客户端 Pong 的往返时延",
+                |m| &m.rtt,
+            ),
+        ];
+        for (name, help, histogram) in histograms {
+            write_header(&mut out, name, "histogram", help);
+            for (transport, metrics) in &transports {
+                write_histogram(&mut out, name, transport, histogram(metrics));
+            }
+        }
+        out
+    }
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn write_histogram(out: &mut String, name: &str, transport: &str, histogram: &Histogram) {
+    let mut cumulative = 0;
+    for (index, bucket) in histogram.buckets.iter().enumerate() {
+        cumulative += bucket.load(Ordering::Relaxed);
+        let le = histogram
+            .bounds
+            .get(index)
+            .map_or_else(|| "+Inf".to_string(), |bound| bound.to_string());
+        let _ = writeln!(
+            out,
+            "{}_bucket{{transport=\"{}\",le=\"{}\"}} {}",
+            name, transport, le, cumulative
+        );
+    }
+    let _ = writeln!(
+        out,
+        "{}_sum{{transport=\"{}\"}} {}",
+        name,
+        transport,
+        histogram.sum_us.load(Ordering::Relaxed) as f64 / 1_000_000.0
+    );
+    let _ = writeln!(
+        out,
+        "{}_count{{transport=\"{}\"}} {}",
+        name, transport, cumulative
+    );
+}
+
+/// Prometheus 文本格式的 Content-Type
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// GET /metrics：Prometheus 抓取接口，使用独立于会话管理接口的令牌与地址白名单
+pub struct MetricsEndpoint {
+    metrics: Arc<Metrics>,
+    sessions: Arc<SessionRegistry>,
+    /// 用于令牌错误的失败计数与锁定
+    auth: Arc<Authenticator>,
+    token: Option<String>,
+    allow: Vec<IpCidr>,
+}
+
+impl MetricsEndpoint {
+    pub fn new(
+        metrics: Arc<Metrics>,
+        sessions: Arc<SessionRegistry>,
+        auth: Arc<Authenticator>,
+        config: MetricsSection,
+    ) -> Self {
+        if config.token.is_none() && config.allow.is_empty() {
+            log::warn!("未设置 metrics.token 或 metrics.allow，/metrics 已禁用");
+        }
+        Self {
+            metrics,
+            sessions,
+            auth,
+            token: config.token,
+            allow: config.allow,
+        }
+    }
+
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/metrics", get(render_metrics))
+            .with_state(self)
+    }
+
+    /// 同时配置令牌与白名单时两者都需满足
+    fn authorize(&self, peer: IpAddr, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+        if self.token.is_none() && self.allow.is_empty() {
+            return Err((StatusCode::NOT_FOUND, "运行指标接口未启用".to_string()));
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|cidr| cidr.contains(peer)) {
+            log::warn!("拒绝来自 {} 的运行指标访问", peer);
+            return Err((
+                StatusCode::FORBIDDEN,
+                "地址不在运行指标白名单内".to_string(),
+            ));
+        }
+        let Some(expected) = &self.token else {
+            return Ok(());
+        };
+
+        self.auth
+            .check_lockout(peer)
+            .map_err(|e| (e.status_code(), e.to_string()))?;
+        let provided = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(str::trim)
+            .unwrap_or_default();
+        if constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+            return Ok(());
+        }
+
+        self.auth.record_failure(peer, "运行指标令牌无效");
+        Err((StatusCode::UNAUTHORIZED, "运行指标令牌无效".to_string()))
+    }
+}
+
+async fn render_metrics(
+    State(endpoint): State<Arc<MetricsEndpoint>>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    endpoint.authorize(peer_addr.ip(), &headers)?;
+    let body = endpoint.metrics.render(&endpoint.sessions);
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(METRICS_CONTENT_TYPE),
+        )],
+        body,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::role::SessionRole;
+
+    fn endpoint(token: Option<&str>, allow: &[&str]) -> MetricsEndpoint {
+        let credentials = std::env::temp_dir().join("webdisplay-metrics-no-credentials.txt");
+        let config = MetricsSection {
+            token: token.map(str::to_string),
+            allow: allow.iter().map(|cidr| cidr.parse().unwrap()).collect(),
+        };
+        MetricsEndpoint::new(
+            Arc::new(Metrics::new()),
+            Arc::new(SessionRegistry::new()),
+            Arc::new(Authenticator::load(&credentials).unwrap()),
+            config,
+        )
+    }
+
+    fn bearer(token: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static(token));
+        headers
+    }
+
+    fn ip(raw: &str) -> IpAddr {
+        raw.parse().unwrap()
+    }
+
+    fn status(result: Result<(), (StatusCode, String)>) -> StatusCode {
+        result.map_or_else(|(status, _)| status, |()| StatusCode::OK)
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let histogram = Histogram::new(&[0.01, 0.1]);
+        histogram.observe(Duration::from_millis(5));
+        histogram.observe(Duration::from_millis(10));
+        histogram.observe(Duration::from_millis(50));
+        histogram.observe(Duration::from_secs(2));
+
+        let mut out = String::new();
+        write_histogram(&mut out, "test_seconds", "WebSocket", &histogram);
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "test_seconds_bucket{transport=\"WebSocket\",le=\"0.01\"} 2",
+                "test_seconds_bucket{transport=\"WebSocket\",le=\"0.1\"} 3",
+                "test_seconds_bucket{transport=\"WebSocket\",le=\"+Inf\"} 4",
+                "test_seconds_sum{transport=\"WebSocket\"} 2.065",
+                "test_seconds_count{transport=\"WebSocket\"} 4",
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_per_transport_metrics() {
+        let metrics = Metrics::new();
+        let sessions = Arc::new(SessionRegistry::new());
+        let peer = SocketAddr::from(([192, 168, 1, 20], 50000));
+        let _session = sessions.register(
+            "WebSocket",
+            peer,
+            SessionRole::Viewer,
+            SessionRole::Viewer,
+            None,
+        );
+
+        let websocket = metrics.transport("WebSocket");
+        assert!(Arc::ptr_eq(&websocket, &metrics.transport("WebSocket")));
+        websocket.record_sent(1200);
+        websocket.record_sent(800);
+        websocket.record_dropped(3);
+        websocket.record_rtt(Duration::from_millis(20));
+        metrics.transport("WebRTC");
+        let simulcast = metrics.transport(SIMULCAST_METRICS_LABEL);
+        simulcast.record_capture(true);
+        simulcast.record_capture(false);
+        simulcast.record_encoded(3000);
+
+        let out = metrics.render(&sessions);
+        let lines: Vec<_> = out.lines().collect();
+        for expected in [
+            "# TYPE webdisplay_active_sessions gauge",
+            "webdisplay_active_sessions{transport=\"WebSocket\"} 1",
+            "webdisplay_active_sessions{transport=\"WebRTC\"} 0",
+            "webdisplay_frames_sent_total{transport=\"WebSocket\"} 2",
+            "webdisplay_bytes_sent_total{transport=\"WebSocket\"} 2000",
+            "webdisplay_frames_dropped_total{transport=\"WebSocket\"} 3",
+            "webdisplay_frames_captured_total{transport=\"simulcast\"} 1",
+            "webdisplay_capture_timeouts_total{transport=\"simulcast\"} 1",
+            "webdisplay_encode_seconds_count{transport=\"simulcast\"} 1",
+            "webdisplay_rtt_seconds_bucket{transport=\"WebSocket\",le=\"0.025\"} 1",
+            "# TYPE webdisplay_rtt_seconds histogram",
+        ] {
+            assert!(lines.contains(&expected), "缺少 {}", expected);
+        }
+        // 共享捕获不是传输，不计在线会话
+        assert!(!out.contains("webdisplay_active_sessions{transport=\"simulcast\"}"));
+    }
+
+    #[test]
+    fn disabled_without_token_or_allowlist() {
+        let endpoint = endpoint(None, &[]);
+        assert_eq!(
+            status(endpoint.authorize(ip("127.0.0.1"), &bearer("Bearer x"))),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn allowlist_only() {
+        let endpoint = endpoint(None, &["10.0.0.0/8"]);
+        assert_eq!(
+            status(endpoint.authorize(ip("10.1.2.3"), &HeaderMap::new())),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(endpoint.authorize(ip("192.168.1.5"), &HeaderMap::new())),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn token_and_allowlist_must_both_match() {
+        let endpoint = endpoint(Some("scrape-secret"), &["10.0.0.0/8"]);
+        assert_eq!(
+            status(endpoint.authorize(ip("10.1.2.3"), &bearer("Bearer scrape-secret"))),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(endpoint.authorize(ip("192.168.1.5"), &bearer("Bearer scrape-secret"))),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(endpoint.authorize(ip("10.1.2.3"), &HeaderMap::new())),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(endpoint.authorize(ip("10.1.2.3"), &bearer("scrape-secret"))),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[test]
+    fn wrong_tokens_lock_out_the_peer() {
+        let endpoint = endpoint(Some("scrape-secret"), &[]);
+        for _ in 0..10 {
+            let _ = endpoint.authorize(ip("203.0.113.5"), &bearer("Bearer guess"));
+        }
+        assert_ne!(
+            status(endpoint.authorize(ip("203.0.113.5"), &bearer("Bearer scrape-secret"))),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(endpoint.authorize(ip("203.0.113.6"), &bearer("Bearer scrape-secret"))),
+            StatusCode::OK
+        );
+    }
+}
@@ -1,2 +1,14 @@
+pub mod access;
+pub mod admin;
+pub mod assets;
+pub mod auth;
+pub mod config;
+pub mod consent;
+pub mod health;
 pub mod http;
+pub mod metrics;
+pub mod pairing;
+pub mod proxy;
+pub mod share;
+pub mod shutdown;
 pub mod tls;
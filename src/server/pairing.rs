@@ -0,0 +1,443 @@
+use crate::control::role::SessionRole;
+use crate::server::auth::{Authenticator, constant_time_eq, encode_hex, unix_now};
+use crate::server::tls::{PairingCa, certificate_fingerprint};
+use axum::extract::{ConnectInfo, State};
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use ring::rand::{SecureRandom, SystemRandom};
+use rustls::pki_types::CertificateDer;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_rustls::rustls;
+
+/// PIN 有效期与允许的错误次数
+const PAIRING_PIN_TTL: Duration = Duration::from_secs(120);
+const MAX_PIN_ATTEMPTS: u32 = 3;
+/// 同时等待输入 PIN 的配对请求上限
+const MAX_PENDING_PAIRINGS: usize = 8;
+const MAX_DEVICE_NAME_LEN: usize = 64;
+
+/// 配对设备默认授予的角色
+const PAIRED_DEVICE_ROLE: SessionRole = SessionRole::Controller;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairedDevice {
+    pub id: u64,
+    pub name: String,
+    pub role: SessionRole,
+    /// 客户端证书 SHA-256 指纹（hex）
+    pub fingerprint: String,
+    pub paired_at: u64,
+}
+
+/// TLS 握手后识别出的已配对设备，作为请求扩展注入
+#[derive(Debug, Clone)]
+pub struct PairedClient {
+    pub device_id: u64,
+    pub role: SessionRole,
+}
+
+struct PendingPairing {
+    name: String,
+    pin: String,
+    expires_at: Instant,
+    attempts: u32,
+}
+
+/// PIN 配对与已配对设备管理
+pub struct PairingStore {
+    ca: PairingCa,
+    rng: SystemRandom,
+    next_pairing_id: AtomicU64,
+    pending: Mutex<HashMap<u64, PendingPairing>>,
+    devices: Mutex<Vec<PairedDevice>>,
+    /// 已配对设备的持久化文件
+    devices_file: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct PairStartRequest {
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct PairStartResponse {
+    pairing_id: u64,
+    expires_in: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct PairCompleteRequest {
+    pairing_id: u64,
+    pin: String,
+}
+
+/// 配对成功后返回的客户端身份，私钥只在此时返回一次
+#[derive(Debug, Serialize)]
+struct PairCompleteResponse {
+    device_id: u64,
+    role: SessionRole,
+    certificate_pem: String,
+    private_key_pem: String,
+    ca_certificate_pem: String,
+}
+
+#[derive(Clone)]
+struct PairingApiState {
+    store: Arc<PairingStore>,
+    auth: Arc<Authenticator>,
+}
+
+impl PairingStore {
+    pub fn load(ca: PairingCa, devices_file: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let devices: Vec<PairedDevice> = if devices_file.exists() {
+            serde_json::from_str(&std::fs::read_to_string(devices_file)?)
+                .map_err(|e| format!("解析 {} 失败: {}", devices_file.display(), e))?
+        } else {
+            Vec::new()
+        };
+        if !devices.is_empty() {
+            log::info!("已加载 {} 台配对设备", devices.len());
+        }
+
+        Ok(Self {
+            ca,
+            rng: SystemRandom::new(),
+            next_pairing_id: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+            devices: Mutex::new(devices),
+            devices_file: devices_file.to_path_buf(),
+        })
+    }
+
+    pub fn router(self: Arc<Self>, auth: Arc<Authenticator>) -> Router {
+        Router::new()
+            .route("/api/pair/start", post(start_pairing))
+            .route("/api/pair/complete", post(complete_pairing))
+            .with_state(PairingApiState { store: self, auth })
+    }
+
+    /// 按 TLS 客户端证书识别已配对设备（证书链已由 mTLS 校验，这里确认未被撤销）
+    pub fn identify(&self, peer_certificates: &[CertificateDer<'_>]) -> Option<PairedClient> {
+        let leaf = peer_certificates.first()?;
+        let fingerprint = encode_hex(&certificate_fingerprint(leaf));
+
+        let devices = self.devices.lock().ok()?;
+        devices
+            .iter()
+            .find(|device| device.fingerprint == fingerprint)
+            .map(|device| PairedClient {
+                device_id: device.id,
+                role: device.role,
+            })
+    }
+
+    pub fn list(&self) -> Vec<PairedDevice> {
+        self.devices
+            .lock()
+            .map(|devices| devices.clone())
+            .unwrap_or_default()
+    }
+
+    /// 撤销配对设备，不存在时返回 false
+    pub fn revoke(&self, id: u64) -> Result<bool, String> {
+        let mut devices = self.devices.lock().map_err(|e| e.to_string())?;
+        let before = devices.len();
+        devices.retain(|device| device.id != id);
+        if devices.len() == before {
+            return Ok(false);
+        }
+
+        self.save_devices(&devices)?;
+        log::info!("配对设备 #{} 已撤销", id);
+        Ok(true)
+    }
+
+    /// 发起配对：生成 PIN 并显示在服务端控制台
+    fn start(&self, name: &str) -> Result<PairStartResponse, (StatusCode, String)> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_DEVICE_NAME_LEN {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("设备名称长度需在 1..={} 之间", MAX_DEVICE_NAME_LEN),
+            ));
+        }
+
+        let mut random = [0u8; 4];
+        self.rng.fill(&mut random).map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "生成 PIN 失败".to_string(),
+            )
+        })?;
+        let pin = format!("{:06}", u32::from_le_bytes(random) % 1_000_000);
+
+        let mut pending = self
+            .pending
+            .lock()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        let now = Instant::now();
+        pending.retain(|_, p| p.expires_at > now);
+        if pending.len() >= MAX_PENDING_PAIRINGS {
+            return Err((
+                StatusCode::TOO_MANY_REQUESTS,
+                "待完成的配对请求过多，请稍后再试".to_string(),
+            ));
+        }
+
+        let pairing_id = self.next_pairing_id.fetch_add(1, Ordering::Relaxed);
+        pending.insert(
+            pairing_id,
+            PendingPairing {
+                name: name.to_string(),
+                pin: pin.clone(),
+                expires_at: now + PAIRING_PIN_TTL,
+                attempts: 0,
+            },
+        );
+
+        log::warn!(
+            "设备 '{}' 请求配对 (#{})，PIN: {}（{} 秒内有效）",
+            name,
+            pairing_id,
+            pin,
+            PAIRING_PIN_TTL.as_secs()
+        );
+
+        Ok(PairStartResponse {
+            pairing_id,
+            expires_in: PAIRING_PIN_TTL.as_secs(),
+        })
+    }
+
+    /// 完成配对：校验 PIN 后签发客户端证书，返回 None 表示 PIN 错误或已失效
+    fn complete(&self, pairing_id: u64, pin: &str) -> Result<Option<PairCompleteResponse>, String> {
+        let name = {
+            let mut pending = self.pending.lock().map_err(|e| e.to_string())?;
+            let Some(request) = pending.get_mut(&pairing_id) else {
+                return Ok(None);
+            };
+
+            if request.expires_at <= Instant::now() {
+                pending.remove(&pairing_id);
+                return Ok(None);
+            }
+            if !constant_time_eq(pin.trim().as_bytes(), request.pin.as_bytes()) {
+                request.attempts += 1;
+                if request.attempts >= MAX_PIN_ATTEMPTS {
+                    pending.remove(&pairing_id);
+                    log::warn!("配对请求 #{} PIN 错误次数过多，已作废", pairing_id);
+                }
+                return Ok(None);
+            }
+
+            pending
+                .remove(&pairing_id)
+                .map(|request| request.name)
+                .unwrap_or_default()
+        };
+
+        let identity = self
+            .ca
+            .issue_client_identity(&name)
+            .map_err(|e| format!("签发客户端证书失败: {}", e))?;
+
+        let mut devices = self.devices.lock().map_err(|e| e.to_string())?;
+        let device_id = devices.iter().map(|d| d.id).max().unwrap_or(0) + 1;
+        devices.push(PairedDevice {
+            id: device_id,
+            name: name.clone(),
+            role: PAIRED_DEVICE_ROLE,
+            fingerprint: encode_hex(&identity.fingerprint),
+            paired_at: unix_now(),
+        });
+        self.save_devices(&devices)?;
+        log::info!("设备 '{}' 配对成功 (#{})", name, device_id);
+
+        Ok(Some(PairCompleteResponse {
+            device_id,
+            role: PAIRED_DEVICE_ROLE,
+            certificate_pem: identity.cert_pem,
+            private_key_pem: identity.key_pem,
+            ca_certificate_pem: self.ca.cert_pem().to_string(),
+        }))
+    }
+
+    fn save_devices(&self, devices: &[PairedDevice]) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(devices).map_err(|e| e.to_string())?;
+        std::fs::write(&self.devices_file, json)
+            .map_err(|e| format!("保存 {} 失败: {}", self.devices_file.display(), e))
+    }
+}
+
+async fn start_pairing(
+    State(state): State<PairingApiState>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    Json(request): Json<PairStartRequest>,
+) -> Result<Json<PairStartResponse>, (StatusCode, String)> {
+    state
+        .auth
+        .check_lockout(peer_addr.ip())
+        .map_err(|e| (e.status_code(), e.to_string()))?;
+
+    log::info!("{} 发起设备配对", peer_addr);
+    state.store.start(&request.name).map(Json)
+}
+
+async fn complete_pairing(
+    State(state): State<PairingApiState>,
+    ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+    Json(request): Json<PairCompleteRequest>,
+) -> Result<Json<PairCompleteResponse>, (StatusCode, String)> {
+    state
+        .auth
+        .check_lockout(peer_addr.ip())
+        .map_err(|e| (e.status_code(), e.to_string()))?;
+
+    // 证书签发与落盘放到阻塞线程池执行
+    let store = state.store.clone();
+    let result =
+        tokio::task::spawn_blocking(move || store.complete(request.pairing_id, &request.pin))
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    match result {
+        Ok(Some(response)) => Ok(Json(response)),
+        Ok(None) => {
+            state
+                .auth
+                .record_failure(peer_addr.ip(), "配对 PIN 错误或已失效");
+            Err((StatusCode::UNAUTHORIZED, "PIN 错误或已失效".to_string()))
+        }
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::config::PairingFiles;
+    use crate::server::tls::{load_certs, load_or_generate_pairing_ca};
+
+    /// 在临时目录中生成配对 CA 与设备文件，每个测试使用独立目录
+    fn pairing_dir(label: &str) -> (PathBuf, PairingFiles) {
+        let dir = std::env::temp_dir().join(format!(
+            "webdisplay-pairing-{}-{}",
+            label,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = PairingFiles {
+            devices: dir.join("paired_devices.json"),
+            ca_cert: dir.join("pairing_ca.pem"),
+            ca_key: dir.join("pairing_ca.key"),
+        };
+        (dir, files)
+    }
+
+    fn open_store(files: &PairingFiles) -> PairingStore {
+        let ca = load_or_generate_pairing_ca(files).unwrap();
+        PairingStore::load(ca, &files.devices).unwrap()
+    }
+
+    fn pending_pin(store: &PairingStore, pairing_id: u64) -> String {
+        store.pending.lock().unwrap()[&pairing_id].pin.clone()
+    }
+
+    #[test]
+    fn start_validates_device_name() {
+        let (dir, files) = pairing_dir("name");
+        let store = open_store(&files);
+
+        let (status, _) = store.start("   ").unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let long_name = "x".repeat(MAX_DEVICE_NAME_LEN + 1);
+        assert!(store.start(&long_name).is_err());
+
+        let started = store.start(" 客厅平板 ").unwrap();
+        assert_eq!(started.expires_in, PAIRING_PIN_TTL.as_secs());
+        assert_eq!(
+            store.pending.lock().unwrap()[&started.pairing_id].name,
+            "客厅平板"
+        );
+        assert_eq!(pending_pin(&store, started.pairing_id).len(), 6);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn limits_pending_pairings() {
+        let (dir, files) = pairing_dir("pending");
+        let store = open_store(&files);
+
+        for _ in 0..MAX_PENDING_PAIRINGS {
+            store.start("tablet").unwrap();
+        }
+        let (status, _) = store.start("tablet").unwrap_err();
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn wrong_pins_void_the_request() {
+        let (dir, files) = pairing_dir("wrong-pin");
+        let store = open_store(&files);
+
+        let pairing_id = store.start("tablet").unwrap().pairing_id;
+        let pin = pending_pin(&store, pairing_id);
+        let wrong = if pin == "000000" { "000001" } else { "000000" };
+
+        for _ in 0..MAX_PIN_ATTEMPTS {
+            assert!(store.complete(pairing_id, wrong).unwrap().is_none());
+        }
+        assert!(store.complete(pairing_id, &pin).unwrap().is_none());
+        assert!(store.complete(pairing_id + 1, &pin).unwrap().is_none());
+        assert!(store.list().is_empty());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn pairing_issues_identity_until_revoked() {
+        let (dir, files) = pairing_dir("complete");
+        let store = open_store(&files);
+
+        let pairing_id = store.start("tablet").unwrap().pairing_id;
+        let pin = pending_pin(&store, pairing_id);
+        let response = store.complete(pairing_id, &pin).unwrap().unwrap();
+        assert_eq!(response.device_id, 1);
+        assert_eq!(response.role, PAIRED_DEVICE_ROLE);
+        assert_eq!(response.ca_certificate_pem, store.ca.cert_pem());
+        // PIN 只能使用一次
+        assert!(store.complete(pairing_id, &pin).unwrap().is_none());
+
+        let cert_path = dir.join("client.pem");
+        std::fs::write(&cert_path, &response.certificate_pem).unwrap();
+        let certs = load_certs(&cert_path).unwrap();
+        let client = store.identify(&certs).unwrap();
+        assert_eq!(client.device_id, 1);
+        assert_eq!(client.role, PAIRED_DEVICE_ROLE);
+        assert!(store.identify(&[]).is_none());
+
+        // 重新加载后仍能识别
+        let reloaded = open_store(&files);
+        assert_eq!(reloaded.list().len(), 1);
+        assert_eq!(reloaded.list()[0].name, "tablet");
+        assert!(reloaded.identify(&certs).is_some());
+
+        assert!(store.revoke(1).unwrap());
+        assert!(!store.revoke(1).unwrap());
+        assert!(store.identify(&certs).is_none());
+        assert!(open_store(&files).list().is_empty());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
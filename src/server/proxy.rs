@@ -0,0 +1,271 @@
+use crate::server::config::IpCidr;
+use axum::Router;
+use axum::extract::Request;
+use axum::http::{HeaderMap, StatusCode, Uri, header};
+use axum::response::{IntoResponse, Redirect, Response};
+use std::net::{IpAddr, SocketAddr};
+use tower::ServiceExt;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// 根据受信任代理转发的头部还原真实客户端地址
+pub struct ForwardedResolver {
+    trusted_proxies: Vec<IpCidr>,
+}
+
+impl ForwardedResolver {
+    pub fn new(trusted_proxies: Vec<IpCidr>) -> Self {
+        Self { trusted_proxies }
+    }
+
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    /// 直连对端不受信任时忽略转发头；否则从右向左跳过受信任的代理，
+    /// 取第一个不受信任的地址作为客户端（Unix 套接字连接视为受信任）
+    pub fn client_addr(
+        &self,
+        peer_addr: SocketAddr,
+        headers: &HeaderMap,
+        peer_trusted: bool,
+    ) -> SocketAddr {
+        if !peer_trusted && !self.is_trusted(peer_addr.ip()) {
+            return peer_addr;
+        }
+
+        let mut client = peer_addr;
+        for hop in forwarded_chain(headers).into_iter().rev() {
+            // 无法解析的节点（如 `unknown` 或混淆标识）之后的地址均不可信
+            let Some(hop) = hop else {
+                break;
+            };
+            client = hop;
+            if !self.is_trusted(hop.ip()) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+/// 按从客户端到代理的顺序列出转发链；优先使用标准 `Forwarded` 头
+fn forwarded_chain(headers: &HeaderMap) -> Vec<Option<SocketAddr>> {
+    let forwarded: Vec<_> = headers
+        .get_all(header::FORWARDED)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|element| {
+            element
+                .split(';')
+                .filter_map(|pair| pair.trim().split_once('='))
+                .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                .and_then(|(_, value)| parse_node(value.trim().trim_matches('"')))
+        })
+        .collect();
+    if !forwarded.is_empty() {
+        return forwarded;
+    }
+
+    headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|node| parse_node(node.trim()))
+        .collect()
+}
+
+/// 解析 `192.0.2.1`、`192.0.2.1:4711`、`[2001:db8::1]:4711` 或 `2001:db8::1`，未知端口记为 0
+fn parse_node(raw: &str) -> Option<SocketAddr> {
+    if let Ok(addr) = raw.parse::<SocketAddr>() {
+        return Some(addr);
+    }
+    let ip = raw.trim_start_matches('[').trim_end_matches(']');
+    ip.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, 0))
+}
+
+/// 把整个应用挂载到路径前缀下；前缀本身重定向到带斜杠的地址，使前端的相对资源路径正确解析
+pub fn with_base_path(app: Router, base_path: &str) -> Router {
+    if base_path.is_empty() {
+        return app;
+    }
+
+    let base_path = base_path.to_string();
+    Router::new().fallback(move |mut request: Request| {
+        let app = app.clone();
+        let base_path = base_path.clone();
+        async move {
+            let path = request.uri().path();
+            let Some(rest) = path.strip_prefix(base_path.as_str()) else {
+                return StatusCode::NOT_FOUND.into_response();
+            };
+            if rest.is_empty() {
+                let query = request
+                    .uri()
+                    .query()
+                    .map(|q| format!("?{}", q))
+                    .unwrap_or_default();
+                return Redirect::permanent(&format!("{}/{}", base_path, query)).into_response();
+            }
+            if !rest.starts_with('/') {
+                return StatusCode::NOT_FOUND.into_response();
+            }
+
+            let stripped = match request.uri().query() {
+                Some(query) => format!("{}?{}", rest, query),
+                None => rest.to_string(),
+            };
+            // 保留 HTTP/2 请求 URI 中的 scheme 与 authority
+            let mut parts = request.uri().clone().into_parts();
+            parts.path_and_query = stripped.parse().ok();
+            match Uri::from_parts(parts) {
+                Ok(uri) => *request.uri_mut() = uri,
+                Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+            }
+            forward(app, request).await
+        }
+    })
+}
+
+async fn forward(app: Router, request: Request) -> Response {
+    match app.oneshot(request).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::HeaderValue;
+    use axum::routing::get;
+
+    fn resolver() -> ForwardedResolver {
+        ForwardedResolver::new(vec!["10.0.0.0/8".parse().unwrap(), "::1".parse().unwrap()])
+    }
+
+    fn headers(entries: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in entries {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn addr(raw: &str) -> SocketAddr {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_forwarded_nodes() {
+        assert_eq!(parse_node("192.0.2.1"), Some(addr("192.0.2.1:0")));
+        assert_eq!(parse_node("192.0.2.1:4711"), Some(addr("192.0.2.1:4711")));
+        assert_eq!(
+            parse_node("[2001:db8::1]:4711"),
+            Some(addr("[2001:db8::1]:4711"))
+        );
+        assert_eq!(parse_node("2001:db8::1"), Some(addr("[2001:db8::1]:0")));
+        assert_eq!(parse_node("[2001:db8::1]"), Some(addr("[2001:db8::1]:0")));
+        assert_eq!(parse_node("unknown"), None);
+        assert_eq!(parse_node("_hidden"), None);
+    }
+
+    #[test]
+    fn ignores_headers_from_untrusted_peers() {
+        let peer = addr("203.0.113.5:5000");
+        let spoofed = headers(&[(X_FORWARDED_FOR, "198.51.100.7")]);
+        assert_eq!(resolver().client_addr(peer, &spoofed, false), peer);
+    }
+
+    #[test]
+    fn skips_trusted_hops() {
+        let peer = addr("10.0.0.2:5000");
+        let chain = headers(&[(X_FORWARDED_FOR, "198.51.100.7, 203.0.113.9, 10.0.0.3")]);
+        assert_eq!(
+            resolver().client_addr(peer, &chain, false),
+            addr("203.0.113.9:0")
+        );
+
+        let only_proxies = headers(&[(X_FORWARDED_FOR, "10.0.0.3")]);
+        assert_eq!(
+            resolver().client_addr(peer, &only_proxies, false),
+            addr("10.0.0.3:0")
+        );
+        assert_eq!(resolver().client_addr(peer, &HeaderMap::new(), false), peer);
+    }
+
+    #[test]
+    fn prefers_standard_forwarded_header() {
+        let peer = addr("[::1]:5000");
+        let both = headers(&[
+            (
+                "forwarded",
+                "for=198.51.100.7;proto=https, For=\"[2001:db8::1]:4711\"",
+            ),
+            (X_FORWARDED_FOR, "203.0.113.9"),
+        ]);
+        assert_eq!(
+            resolver().client_addr(peer, &both, false),
+            addr("[2001:db8::1]:4711")
+        );
+    }
+
+    #[test]
+    fn stops_at_unparsable_hops() {
+        let peer = addr("10.0.0.2:5000");
+        let obfuscated = headers(&[("forwarded", "for=198.51.100.7, for=unknown")]);
+        assert_eq!(resolver().client_addr(peer, &obfuscated, false), peer);
+    }
+
+    #[test]
+    fn unix_socket_peers_are_trusted() {
+        let peer = addr("0.0.0.0:0");
+        let chain = headers(&[(X_FORWARDED_FOR, "198.51.100.7")]);
+        assert_eq!(
+            resolver().client_addr(peer, &chain, true),
+            addr("198.51.100.7:0")
+        );
+        assert_eq!(resolver().client_addr(peer, &chain, false), peer);
+    }
+
+    async fn request(app: &Router, uri: &str) -> Response {
+        app.clone()
+            .oneshot(
+                axum::http::Request::builder()
+                    .uri(uri)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn mounts_under_base_path() {
+        let app = Router::new().route("/status", get(|uri: Uri| async move { uri.to_string() }));
+        let app = with_base_path(app, "/desk");
+
+        let response = request(&app, "/desk/status?verbose=1").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"/status?verbose=1");
+
+        let response = request(&app, "/desk?token=abc").await;
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[header::LOCATION], "/desk/?token=abc");
+
+        assert_eq!(
+            request(&app, "/status").await.status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            request(&app, "/desktop/status").await.status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+}
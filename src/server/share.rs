@@ -0,0 +1,259 @@
+use crate::control::role::SessionRole;
+use crate::server::auth::{decode_hex, encode_hex, unix_now};
+use ring::hmac;
+use ring::rand::SystemRandom;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 分享令牌前缀，用于与登录会话令牌区分
+pub const SHARE_TOKEN_PREFIX: &str = "s1";
+
+/// 分享链接默认有效期与上限（秒）
+pub const DEFAULT_SHARE_TTL_SECS: u64 = 30 * 60;
+pub const MAX_SHARE_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+pub const DEFAULT_SHARE_MAX_USES: u32 = 1;
+
+/// 分享链接授予会话的访问范围
+#[derive(Debug, Clone, Copy)]
+pub struct ShareScope {
+    pub id: u64,
+    pub monitor: u32,
+    pub expires_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareLinkInfo {
+    pub id: u64,
+    pub monitor: u32,
+    pub role: SessionRole,
+    pub expires_at: u64,
+    pub max_uses: u32,
+    pub uses: u32,
+    pub revoked: bool,
+}
+
+/// 新建分享链接的结果，令牌只在创建时返回一次
+#[derive(Debug, Serialize)]
+pub struct CreatedShareLink {
+    #[serde(flatten)]
+    pub info: ShareLinkInfo,
+    pub token: String,
+}
+
+/// 带签名、可过期、可撤销的分享链接
+pub struct ShareLinks {
+    next_id: AtomicU64,
+    signing_key: hmac::Key,
+    links: Mutex<HashMap<u64, ShareLinkInfo>>,
+}
+
+impl ShareLinks {
+    pub fn new(rng: &SystemRandom) -> Result<Self, Box<dyn std::error::Error>> {
+        let signing_key =
+            hmac::Key::generate(hmac::HMAC_SHA256, rng).map_err(|_| "生成分享链接签名密钥失败")?;
+
+        Ok(Self {
+            next_id: AtomicU64::new(1),
+            signing_key,
+            links: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn create(
+        &self,
+        monitor: u32,
+        role: SessionRole,
+        ttl_secs: u64,
+        max_uses: u32,
+    ) -> Result<CreatedShareLink, String> {
+        if ttl_secs == 0 || ttl_secs > MAX_SHARE_TTL_SECS {
+            return Err(format!("有效期需在 1..={} 秒之间", MAX_SHARE_TTL_SECS));
+        }
+        if max_uses == 0 {
+            return Err("使用次数至少为 1".to_string());
+        }
+        // 分享链接不授予管理员权限
+        let role = role.min(SessionRole::Controller);
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let info = ShareLinkInfo {
+            id,
+            monitor,
+            role,
+            expires_at: unix_now() + ttl_secs,
+            max_uses,
+            uses: 0,
+            revoked: false,
+        };
+
+        let body = format!(
+            "{}.{}.{}.{}.{}",
+            SHARE_TOKEN_PREFIX,
+            id,
+            role.as_client_name(),
+            monitor,
+            info.expires_at
+        );
+        let signature = hmac::sign(&self.signing_key, body.as_bytes());
+        let token = format!("{}.{}", body, encode_hex(signature.as_ref()));
+
+        self.links
+            .lock()
+            .map_err(|_| "分享链接表不可用".to_string())?
+            .insert(id, info.clone());
+        log::info!(
+            "已创建分享链接 #{}: monitor {}, 角色 {}, {} 秒, 最多 {} 次",
+            id,
+            monitor,
+            role,
+            ttl_secs,
+            max_uses
+        );
+
+        Ok(CreatedShareLink { info, token })
+    }
+
+    /// 校验分享令牌并消耗一次使用次数
+    pub fn redeem(&self, token: &str) -> Option<(SessionRole, ShareScope)> {
+        let (body, signature) = token.rsplit_once('.')?;
+        let signature = decode_hex(signature)?;
+        hmac::verify(&self.signing_key, body.as_bytes(), &signature).ok()?;
+
+        let mut parts = body.split('.');
+        if parts.next()? != SHARE_TOKEN_PREFIX {
+            return None;
+        }
+        let id: u64 = parts.next()?.parse().ok()?;
+
+        let mut links = self.links.lock().ok()?;
+        let link = links.get_mut(&id)?;
+        if link.revoked || link.expires_at <= unix_now() || link.uses >= link.max_uses {
+            return None;
+        }
+        link.uses += 1;
+        log::info!("分享链接 #{} 已使用 {}/{} 次", id, link.uses, link.max_uses);
+
+        Some((
+            link.role,
+            ShareScope {
+                id,
+                monitor: link.monitor,
+                expires_at: link.expires_at,
+            },
+        ))
+    }
+
+    pub fn list(&self) -> Vec<ShareLinkInfo> {
+        let Ok(mut links) = self.links.lock() else {
+            return Vec::new();
+        };
+
+        // 顺带清理已过期的链接
+        let now = unix_now();
+        links.retain(|_, link| link.expires_at > now);
+
+        let mut list: Vec<_> = links.values().cloned().collect();
+        list.sort_by_key(|link| link.id);
+        list
+    }
+
+    /// 撤销分享链接，不存在时返回 false
+    pub fn revoke(&self, id: u64) -> bool {
+        let Ok(mut links) = self.links.lock() else {
+            return false;
+        };
+
+        match links.get_mut(&id) {
+            Some(link) => {
+                link.revoked = true;
+                log::info!("分享链接 #{} 已撤销", id);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn share_links() -> ShareLinks {
+        ShareLinks::new(&SystemRandom::new()).unwrap()
+    }
+
+    #[test]
+    fn redeems_until_max_uses() {
+        let links = share_links();
+        let created = links.create(1, SessionRole::Viewer, 60, 2).unwrap();
+        assert!(created.token.starts_with(SHARE_TOKEN_PREFIX));
+
+        let (role, scope) = links.redeem(&created.token).unwrap();
+        assert_eq!(role, SessionRole::Viewer);
+        assert_eq!(scope.id, created.info.id);
+        assert_eq!(scope.monitor, 1);
+        assert!(links.redeem(&created.token).is_some());
+        assert!(links.redeem(&created.token).is_none());
+        assert_eq!(links.list()[0].uses, 2);
+    }
+
+    #[test]
+    fn never_grants_admin() {
+        let links = share_links();
+        let created = links.create(0, SessionRole::Admin, 60, 1).unwrap();
+        assert_eq!(created.info.role, SessionRole::Controller);
+        assert_eq!(
+            links.redeem(&created.token).map(|(role, _)| role),
+            Some(SessionRole::Controller)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_parameters() {
+        let links = share_links();
+        assert!(links.create(0, SessionRole::Viewer, 0, 1).is_err());
+        assert!(
+            links
+                .create(0, SessionRole::Viewer, MAX_SHARE_TTL_SECS + 1, 1)
+                .is_err()
+        );
+        assert!(links.create(0, SessionRole::Viewer, 60, 0).is_err());
+    }
+
+    #[test]
+    fn revoked_and_tampered_tokens_are_rejected() {
+        let links = share_links();
+        let created = links.create(2, SessionRole::Viewer, 60, 5).unwrap();
+
+        let tampered = created.token.replacen(".viewer.", ".controller.", 1);
+        assert_ne!(tampered, created.token);
+        assert!(links.redeem(&tampered).is_none());
+
+        // 其他实例签发的令牌
+        let other = share_links();
+        assert!(other.redeem(&created.token).is_none());
+
+        assert!(links.revoke(created.info.id));
+        assert!(!links.revoke(999));
+        assert!(links.redeem(&created.token).is_none());
+        assert!(links.list()[0].revoked);
+    }
+
+    #[test]
+    fn expired_links_are_rejected_and_pruned() {
+        let links = share_links();
+        let created = links.create(0, SessionRole::Viewer, 60, 1).unwrap();
+        links
+            .links
+            .lock()
+            .unwrap()
+            .get_mut(&created.info.id)
+            .unwrap()
+            .expires_at = unix_now() - 1;
+
+        assert!(links.redeem(&created.token).is_none());
+        assert!(links.list().is_empty());
+    }
+}
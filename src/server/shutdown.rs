@@ -0,0 +1,174 @@
+use crate::control::registry::{SessionCommand, SessionRegistry};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// 退出时等待会话自行结束的最长时间
+pub const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+/// 等待期间检查剩余会话数的间隔
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 退出信号：触发后各监听循环停止接受新连接
+pub struct Shutdown {
+    sender: watch::Sender<bool>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            sender: watch::Sender::new(false),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    /// 等待退出信号触发
+    pub async fn triggered(&self) {
+        let mut receiver = self.sender.subscribe();
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+}
+
+/// 等待 SIGINT（Ctrl+C）或 SIGTERM；Windows 下对应关闭控制台窗口与系统关机
+pub async fn wait_for_signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            log::warn!("监听 Ctrl+C 失败: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                log::warn!("监听 SIGTERM 失败: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(windows)]
+    let terminate = async {
+        use tokio::signal::windows::{ctrl_close, ctrl_shutdown};
+        match (ctrl_close(), ctrl_shutdown()) {
+            (Ok(mut close), Ok(mut shutdown)) => {
+                tokio::select! {
+                    _ = close.recv() => {}
+                    _ = shutdown.recv() => {}
+                }
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                log::warn!("监听控制台关闭事件失败: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = interrupt => log::info!("收到 SIGINT，开始关闭服务"),
+        _ = terminate => log::info!("收到 SIGTERM，开始关闭服务"),
+    }
+}
+
+/// 通知所有会话结束（发送提示、松开按键），在宽限期内等待其退出；返回仍未结束的会话数
+pub async fn drain_sessions(sessions: &SessionRegistry, grace_period: Duration) -> usize {
+    let notified = sessions.broadcast_command(SessionCommand::Shutdown);
+    if notified == 0 {
+        return 0;
+    }
+    log::info!("等待 {} 个会话结束...", notified);
+
+    let deadline = Instant::now() + grace_period;
+    loop {
+        let remaining = sessions.count();
+        if remaining == 0 || Instant::now() >= deadline {
+            return remaining;
+        }
+        tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::role::SessionRole;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    fn peer() -> SocketAddr {
+        SocketAddr::from(([192, 168, 1, 20], 50000))
+    }
+
+    #[tokio::test]
+    async fn trigger_wakes_waiters() {
+        let shutdown = Arc::new(Shutdown::new());
+        let waiter = tokio::spawn({
+            let shutdown = Arc::clone(&shutdown);
+            async move { shutdown.triggered().await }
+        });
+        shutdown.trigger();
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // 触发后才开始等待的也立即返回
+        tokio::time::timeout(Duration::from_secs(1), shutdown.triggered())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn drain_returns_once_sessions_leave() {
+        let sessions = Arc::new(SessionRegistry::new());
+        assert_eq!(drain_sessions(&sessions, Duration::from_secs(1)).await, 0);
+
+        let handle = sessions.register(
+            "WebSocket",
+            peer(),
+            SessionRole::Controller,
+            SessionRole::Controller,
+            None,
+        );
+        let session = std::thread::spawn(move || {
+            loop {
+                let commands = handle.take_commands();
+                if commands
+                    .iter()
+                    .any(|command| matches!(command, SessionCommand::Shutdown))
+                {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        });
+
+        let started = Instant::now();
+        assert_eq!(drain_sessions(&sessions, Duration::from_secs(5)).await, 0);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        session.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn drain_gives_up_after_grace_period() {
+        let sessions = Arc::new(SessionRegistry::new());
+        let _stuck = sessions.register(
+            "WebRTC",
+            peer(),
+            SessionRole::Viewer,
+            SessionRole::Viewer,
+            None,
+        );
+
+        assert_eq!(
+            drain_sessions(&sessions, Duration::from_millis(200)).await,
+            1
+        );
+    }
+}
@@ -1,13 +1,50 @@
+use crate::server::config::{PairingFiles, TlsFiles};
+use crate::server::http::{ALPN_HTTP1, ALPN_HTTP2};
 use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
 use sha2::{Digest, Sha256};
 use std::fs::{self, File};
 use std::io::{BufReader, Write};
-use std::path::Path;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
 use tokio_rustls::rustls;
 
-const CERT_VERSION_MARKER_FILE: &str = "cert.version";
-const REQUIRED_CERT_VERSION: &str = "2";
+/// 自动生成的 HTTPS 证书版本；版本变化时重新生成
+const REQUIRED_CERT_VERSION: &str = "3";
+/// 自动生成的 HTTPS 证书有效期，以及提前续期的天数
+const SELF_SIGNED_VALIDITY_DAYS: i64 = 90;
+const SELF_SIGNED_RENEW_BEFORE_DAYS: i64 = 14;
+/// 检查 HTTPS 证书文件变化的周期
+const SERVER_CERT_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// WebTransport 的 serverCertificateHashes 仅支持短期证书（<= 14 天）
+const WEBTRANSPORT_CERT_VALIDITY_DAYS: i64 = 13;
+/// WebTransport 证书轮换周期，需保证后备证书启用后仍有足够有效期
+const WEBTRANSPORT_CERT_ROTATE_INTERVAL: Duration = Duration::from_secs(5 * 24 * 60 * 60);
+
+/// 签发配对设备客户端证书的本地 CA
+const PAIRING_CA_COMMON_NAME: &str = "webdisplay pairing CA";
+const PAIRING_CA_VALIDITY_DAYS: i64 = 20 * 365;
+/// 配对设备客户端证书有效期
+const CLIENT_CERT_VALIDITY_DAYS: i64 = 5 * 365;
+
+/// 配对 CA：签发客户端证书，并作为 mTLS 的信任根
+pub struct PairingCa {
+    issuer: rcgen::Issuer<'static, rcgen::KeyPair>,
+    cert_pem: String,
+    cert_der: CertificateDer<'static>,
+}
+
+/// 新签发的客户端身份
+pub struct ClientIdentity {
+    pub cert_pem: String,
+    pub key_pem: String,
+    /// 证书 DER 的 SHA-256 指纹
+    pub fingerprint: Vec<u8>,
+}
 
 pub fn load_certs(path: &Path) -> std::io::Result<Vec<CertificateDer<'static>>> {
     let file = File::open(path)?;
@@ -24,22 +61,43 @@ pub fn load_key(path: &Path) -> std::io::Result<PrivateKeyDer<'static>> {
     Ok(key)
 }
 
-pub fn generate_self_signed_cert() -> Result<(), Box<dyn std::error::Error>> {
-    if Path::new("cert.pem").exists() && Path::new("key.pem").exists() {
-        if let Ok(version) = fs::read_to_string(CERT_VERSION_MARKER_FILE) {
-            if version.trim() == REQUIRED_CERT_VERSION {
-                return Ok(());
-            }
+/// 自动生成证书的版本标记文件（与证书同名、扩展名为 .version）；
+/// 缺少标记说明证书由用户提供，不做任何改动
+fn cert_marker_path(files: &TlsFiles) -> PathBuf {
+    files.cert.with_extension("version")
+}
+
+/// 证书与私钥中较新的修改时间，用于判断文件是否被替换
+fn files_modified_at(files: &TlsFiles) -> Option<SystemTime> {
+    let cert = fs::metadata(&files.cert).and_then(|m| m.modified()).ok()?;
+    let key = fs::metadata(&files.key).and_then(|m| m.modified()).ok()?;
+    Some(cert.max(key))
+}
+
+/// 确保 HTTPS 证书可用：缺失时生成自签名证书，自动生成的证书临近过期时续期
+pub fn ensure_server_cert(files: &TlsFiles) -> Result<bool, Box<dyn std::error::Error>> {
+    if files.cert.exists() && files.key.exists() {
+        let Ok(version) = fs::read_to_string(cert_marker_path(files)) else {
+            return Ok(false);
+        };
+
+        let age = fs::metadata(&files.cert)?
+            .modified()?
+            .elapsed()
+            .unwrap_or_default();
+        let renew_after = Duration::from_secs(
+            (SELF_SIGNED_VALIDITY_DAYS - SELF_SIGNED_RENEW_BEFORE_DAYS) as u64 * 86400,
+        );
+        if version.trim() == REQUIRED_CERT_VERSION && age < renew_after {
+            return Ok(false);
         }
     }
 
-    // WebTransport 的 serverCertificateHashes 仅支持短期证书（<= 14 天），
-    // 因此这里在启动时统一生成短期开发证书。
     log::info!("正在生成自签名证书...");
     let mut params =
         rcgen::CertificateParams::new(vec!["localhost".to_string(), "127.0.0.1".to_string()])?;
     params.not_before = time::OffsetDateTime::now_utc() - time::Duration::hours(1);
-    params.not_after = params.not_before + time::Duration::days(13);
+    params.not_after = params.not_before + time::Duration::days(SELF_SIGNED_VALIDITY_DAYS);
     params.insert_extended_key_usage(rcgen::ExtendedKeyUsagePurpose::ServerAuth);
     params
         .distinguished_name
@@ -48,38 +106,389 @@ pub fn generate_self_signed_cert() -> Result<(), Box<dyn std::error::Error>> {
     let signing_key = rcgen::KeyPair::generate_for(&rcgen::PKCS_ECDSA_P256_SHA256)?;
     let cert = params.self_signed(&signing_key)?;
 
-    let mut cert_file = File::create("cert.pem")?;
+    let mut cert_file = File::create(&files.cert)?;
     cert_file.write_all(cert.pem().as_bytes())?;
 
-    let mut key_file = File::create("key.pem")?;
+    let mut key_file = File::create(&files.key)?;
     key_file.write_all(signing_key.serialize_pem().as_bytes())?;
 
-    fs::write(CERT_VERSION_MARKER_FILE, REQUIRED_CERT_VERSION)?;
+    fs::write(cert_marker_path(files), REQUIRED_CERT_VERSION)?;
+
+    log::info!(
+        "自签名证书已生成 ({}, {})",
+        files.cert.display(),
+        files.key.display()
+    );
+    Ok(true)
+}
+
+fn load_certified_key(files: &TlsFiles) -> Result<CertifiedKey, Box<dyn std::error::Error>> {
+    let certs = load_certs(&files.cert)?;
+    if certs.is_empty() {
+        return Err(format!("{} 中没有证书", files.cert.display()).into());
+    }
+    let key = load_key(&files.key)?;
+    let signing_key = rustls::crypto::ring::sign::any_supported_type(&key)?;
+    Ok(CertifiedKey::new(certs, signing_key))
+}
+
+/// 可热重载的 HTTPS 证书：文件被替换或自动生成的证书续期后，新握手即使用新证书
+#[derive(Debug)]
+pub struct ServerCertResolver {
+    files: TlsFiles,
+    current: RwLock<Arc<CertifiedKey>>,
+    modified_at: Mutex<Option<SystemTime>>,
+}
+
+impl ServerCertResolver {
+    pub fn load(files: TlsFiles) -> Result<Self, Box<dyn std::error::Error>> {
+        ensure_server_cert(&files)?;
+        let certified_key = load_certified_key(&files)?;
+        let modified_at = files_modified_at(&files);
+
+        Ok(Self {
+            files,
+            current: RwLock::new(Arc::new(certified_key)),
+            modified_at: Mutex::new(modified_at),
+        })
+    }
+
+    /// 检查证书文件，发生变化时重新加载；返回是否已替换
+    pub fn reload_if_changed(&self) -> Result<bool, Box<dyn std::error::Error>> {
+        ensure_server_cert(&self.files)?;
+
+        let modified_at = files_modified_at(&self.files);
+        let mut loaded_at = self.modified_at.lock().map_err(|e| e.to_string())?;
+        if modified_at == *loaded_at {
+            return Ok(false);
+        }
+
+        // 证书与私钥可能被分两次写入，加载失败时保留旧证书，下次检查再试
+        let certified_key = load_certified_key(&self.files)?;
+        *self.current.write().map_err(|e| e.to_string())? = Arc::new(certified_key);
+        *loaded_at = modified_at;
+        log::info!("HTTPS 证书已重新加载 ({})", self.files.cert.display());
+        Ok(true)
+    }
 
-    log::info!("自签名证书已生成 (cert.pem, key.pem)");
-    Ok(())
+    /// 定期检查证书文件
+    pub fn spawn_reload_watcher(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(SERVER_CERT_RELOAD_INTERVAL);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.reload_if_changed() {
+                    log::warn!("重新加载 HTTPS 证书失败，继续使用当前证书: {}", e);
+                }
+            }
+        });
+    }
+
+    /// 当前证书的过期时间（Unix 秒）
+    pub fn expires_at(&self) -> Option<u64> {
+        let current = self.current.read().ok()?.clone();
+        let (_, cert) = x509_parser::parse_x509_certificate(current.cert.first()?).ok()?;
+        u64::try_from(cert.validity().not_after.timestamp()).ok()
+    }
 }
 
-pub fn get_webtransport_certificate_hash_sha256() -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let certs = load_certs(Path::new("cert.pem"))?;
-    let leaf = certs.first().ok_or_else(|| {
-        std::io::Error::new(std::io::ErrorKind::InvalidData, "cert.pem 中没有证书")
+impl ResolvesServerCert for ServerCertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.current.read().ok().map(|current| current.clone())
+    }
+}
+
+/// WebTransport 使用的内存短期证书
+#[derive(Clone)]
+pub struct EphemeralCert {
+    pub cert_der: Vec<u8>,
+    /// PKCS#8 私钥
+    pub key_der: Vec<u8>,
+    /// 证书 DER 的 SHA-256，供浏览器 serverCertificateHashes 固定
+    pub hash: Vec<u8>,
+}
+
+impl EphemeralCert {
+    fn generate() -> Result<Self, Box<dyn std::error::Error>> {
+        let mut params =
+            rcgen::CertificateParams::new(vec!["localhost".to_string(), "127.0.0.1".to_string()])?;
+        params.not_before = time::OffsetDateTime::now_utc() - time::Duration::hours(1);
+        params.not_after =
+            params.not_before + time::Duration::days(WEBTRANSPORT_CERT_VALIDITY_DAYS);
+        params.insert_extended_key_usage(rcgen::ExtendedKeyUsagePurpose::ServerAuth);
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "localhost");
+
+        let signing_key = rcgen::KeyPair::generate_for(&rcgen::PKCS_ECDSA_P256_SHA256)?;
+        let cert = params.self_signed(&signing_key)?;
+
+        Ok(Self {
+            cert_der: cert.der().to_vec(),
+            key_der: signing_key.serialize_der(),
+            hash: certificate_fingerprint(cert.der()),
+        })
+    }
+}
+
+/// 自动轮换的 WebTransport 证书。下一张证书提前生成并一同公布哈希，
+/// 轮换前刚取得哈希的客户端在轮换后仍能连接
+pub struct WebTransportCerts {
+    current: RwLock<EphemeralCert>,
+    next: RwLock<EphemeralCert>,
+    /// 每次轮换递增，各 WebTransport 端点据此重新加载证书
+    rotations: watch::Sender<u64>,
+}
+
+impl WebTransportCerts {
+    pub fn generate() -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            current: RwLock::new(EphemeralCert::generate()?),
+            next: RwLock::new(EphemeralCert::generate()?),
+            rotations: watch::Sender::new(0),
+        })
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.rotations.subscribe()
+    }
+
+    /// 按固定周期轮换证书
+    pub fn spawn_rotation(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(WEBTRANSPORT_CERT_ROTATE_INTERVAL);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match self.rotate() {
+                    Ok(()) => log::info!("WebTransport 证书已轮换"),
+                    Err(e) => log::warn!("轮换 WebTransport 证书失败，继续使用当前证书: {}", e),
+                }
+            }
+        });
+    }
+
+    pub fn current(&self) -> Option<EphemeralCert> {
+        self.current.read().ok().map(|cert| cert.clone())
+    }
+
+    /// 当前可接受的证书哈希，当前证书在前
+    pub fn hashes(&self) -> Vec<Vec<u8>> {
+        [&self.current, &self.next]
+            .iter()
+            .filter_map(|cert| cert.read().ok().map(|cert| cert.hash.clone()))
+            .collect()
+    }
+
+    /// 启用预先生成的下一张证书，并生成新的后备证书
+    fn rotate(&self) -> Result<(), String> {
+        let upcoming = EphemeralCert::generate().map_err(|e| e.to_string())?;
+        {
+            let mut next = self.next.write().map_err(|e| e.to_string())?;
+            let mut current = self.current.write().map_err(|e| e.to_string())?;
+            *current = std::mem::replace(&mut *next, upcoming);
+        }
+        self.rotations.send_modify(|generation| *generation += 1);
+        Ok(())
+    }
+}
+
+fn pairing_ca_params() -> Result<rcgen::CertificateParams, rcgen::Error> {
+    let mut params = rcgen::CertificateParams::new(Vec::<String>::new())?;
+    params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Constrained(0));
+    params.key_usages = vec![
+        rcgen::KeyUsagePurpose::KeyCertSign,
+        rcgen::KeyUsagePurpose::CrlSign,
+        rcgen::KeyUsagePurpose::DigitalSignature,
+    ];
+    params
+        .distinguished_name
+        .push(rcgen::DnType::CommonName, PAIRING_CA_COMMON_NAME);
+    Ok(params)
+}
+
+/// 加载配对 CA，不存在时生成（长期有效，丢失后所有已配对设备需重新配对）
+pub fn load_or_generate_pairing_ca(
+    files: &PairingFiles,
+) -> Result<PairingCa, Box<dyn std::error::Error>> {
+    let cert_path = files.ca_cert.as_path();
+    let key_path = files.ca_key.as_path();
+
+    if !cert_path.exists() || !key_path.exists() {
+        log::info!("正在生成配对 CA...");
+        let mut params = pairing_ca_params()?;
+        params.not_before = time::OffsetDateTime::now_utc() - time::Duration::hours(1);
+        params.not_after = params.not_before + time::Duration::days(PAIRING_CA_VALIDITY_DAYS);
+
+        let signing_key = rcgen::KeyPair::generate_for(&rcgen::PKCS_ECDSA_P256_SHA256)?;
+        let cert = params.self_signed(&signing_key)?;
+        fs::write(cert_path, cert.pem())?;
+        fs::write(key_path, signing_key.serialize_pem())?;
+        log::info!(
+            "配对 CA 已生成 ({}, {})",
+            cert_path.display(),
+            key_path.display()
+        );
+    }
+
+    let cert_pem = fs::read_to_string(cert_path)?;
+    let cert_der = load_certs(cert_path)?.into_iter().next().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} 中没有证书", cert_path.display()),
+        )
     })?;
+    let signing_key = rcgen::KeyPair::from_pem(&fs::read_to_string(key_path)?)?;
 
-    let mut hasher = Sha256::new();
-    hasher.update(leaf.as_ref());
-    Ok(hasher.finalize().to_vec())
+    // 以相同的主题与密钥重建签发者，签出的证书可由已保存的 CA 证书验证
+    let issuer = rcgen::Issuer::new(pairing_ca_params()?, signing_key);
+
+    Ok(PairingCa {
+        issuer,
+        cert_pem,
+        cert_der,
+    })
 }
 
-pub fn get_tls_config() -> Result<Arc<rustls::ServerConfig>, Box<dyn std::error::Error>> {
-    generate_self_signed_cert()?;
+impl PairingCa {
+    pub fn cert_pem(&self) -> &str {
+        &self.cert_pem
+    }
+
+    /// 为配对设备签发客户端证书
+    pub fn issue_client_identity(
+        &self,
+        device_name: &str,
+    ) -> Result<ClientIdentity, Box<dyn std::error::Error>> {
+        let mut params = rcgen::CertificateParams::new(Vec::<String>::new())?;
+        params.not_before = time::OffsetDateTime::now_utc() - time::Duration::hours(1);
+        params.not_after = params.not_before + time::Duration::days(CLIENT_CERT_VALIDITY_DAYS);
+        params.insert_extended_key_usage(rcgen::ExtendedKeyUsagePurpose::ClientAuth);
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, device_name);
+
+        let key = rcgen::KeyPair::generate_for(&rcgen::PKCS_ECDSA_P256_SHA256)?;
+        let cert = params.signed_by(&key, &self.issuer)?;
 
-    let certs = load_certs(Path::new("cert.pem"))?;
-    let key = load_key(Path::new("key.pem"))?;
+        Ok(ClientIdentity {
+            cert_pem: cert.pem(),
+            key_pem: key.serialize_pem(),
+            fingerprint: certificate_fingerprint(cert.der()),
+        })
+    }
+}
 
-    let config = rustls::ServerConfig::builder()
-        .with_no_client_auth()
-        .with_single_cert(certs, key)?;
+pub fn certificate_fingerprint(der: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(der);
+    hasher.finalize().to_vec()
+}
+
+pub fn get_tls_config(
+    server_cert: Arc<ServerCertResolver>,
+    pairing_ca: &PairingCa,
+) -> Result<Arc<rustls::ServerConfig>, Box<dyn std::error::Error>> {
+    // 客户端证书可选：已配对设备出示配对 CA 签发的证书，浏览器等其他客户端走令牌认证
+    let mut client_roots = rustls::RootCertStore::empty();
+    client_roots.add(pairing_ca.cert_der.clone())?;
+    let client_verifier = rustls::server::WebPkiClientVerifier::builder(Arc::new(client_roots))
+        .allow_unauthenticated()
+        .build()?;
+
+    let mut config = rustls::ServerConfig::builder()
+        .with_client_cert_verifier(client_verifier)
+        .with_cert_resolver(server_cert);
+    config.alpn_protocols = vec![ALPN_HTTP2.to_vec(), ALPN_HTTP1.to_vec()];
 
     Ok(Arc::new(config))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_tls_files(label: &str) -> (PathBuf, TlsFiles) {
+        let dir =
+            std::env::temp_dir().join(format!("webdisplay-tls-{}-{}", label, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let files = TlsFiles {
+            cert: dir.join("cert.pem"),
+            key: dir.join("key.pem"),
+        };
+        (dir, files)
+    }
+
+    #[test]
+    fn generates_self_signed_cert_once() {
+        let (dir, files) = temp_tls_files("generate");
+
+        assert!(ensure_server_cert(&files).unwrap());
+        assert_eq!(
+            fs::read_to_string(cert_marker_path(&files)).unwrap(),
+            REQUIRED_CERT_VERSION
+        );
+        assert!(!ensure_server_cert(&files).unwrap());
+
+        // 版本标记过期时重新生成
+        fs::write(cert_marker_path(&files), "0").unwrap();
+        assert!(ensure_server_cert(&files).unwrap());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn leaves_user_certs_untouched() {
+        let (dir, files) = temp_tls_files("user");
+        ensure_server_cert(&files).unwrap();
+        fs::remove_file(cert_marker_path(&files)).unwrap();
+        let cert = fs::read(&files.cert).unwrap();
+
+        assert!(!ensure_server_cert(&files).unwrap());
+        assert_eq!(fs::read(&files.cert).unwrap(), cert);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn resolver_reports_expiry() {
+        let (dir, files) = temp_tls_files("resolver");
+        let resolver = ServerCertResolver::load(files).unwrap();
+
+        let now = crate::server::auth::unix_now();
+        let expires_at = resolver.expires_at().unwrap();
+        let validity = SELF_SIGNED_VALIDITY_DAYS as u64 * 86400;
+        assert!(expires_at > now + validity - 2 * 3600);
+        assert!(expires_at <= now + validity);
+        assert!(!resolver.reload_if_changed().unwrap());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn rotation_promotes_the_next_cert() {
+        let certs = WebTransportCerts::generate().unwrap();
+        let rotations = certs.subscribe();
+        let hashes = certs.hashes();
+        assert_eq!(hashes.len(), 2);
+        assert_ne!(hashes[0], hashes[1]);
+        assert_eq!(certs.current().unwrap().hash, hashes[0]);
+
+        certs.rotate().unwrap();
+        let rotated = certs.hashes();
+        assert_eq!(rotated[0], hashes[1]);
+        assert!(!hashes.contains(&rotated[1]));
+        assert_eq!(*rotations.borrow(), 1);
+    }
+
+    #[test]
+    fn fingerprint_is_sha256() {
+        let fingerprint = certificate_fingerprint(b"");
+        assert_eq!(
+            crate::server::auth::encode_hex(&fingerprint),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
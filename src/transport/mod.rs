@@ -1,5 +1,6 @@
-mod session;
+pub(crate) mod session;
 
+pub mod simulcast;
 pub mod webrtc;
 pub mod websocket;
 pub mod webtransport;
@@ -1,45 +1,97 @@
 use crate::capture::dda::{DdaCapture, MonitorInfo};
+use crate::control::audit::{AuditEvent, AuditLog, InputActivity, SessionAudit};
+use crate::control::consent::ConsentGate;
+use crate::control::floor::{FloorDecision, FloorRequest, InputFloor};
+use crate::control::registry::{
+    EncodingUpdate, RoleRequest, SessionCommand, SessionHandle, SessionMedia, SessionRegistry,
+    SessionSummary,
+};
+use crate::control::role::SessionRole;
 use crate::encode::amf::{AmfEncoder, EncoderConfig, VideoCodec};
-use crate::input::win32::{ActiveMonitor, InputInjector};
+use crate::input::pointer::{ContactState, PenSample, PenState, TouchContact};
+use crate::input::win32::{ActiveMonitor, InputInjector, LockKeys, wait_for_lock_keys};
 use crate::protocol::frame::{FrameFlags, FrameHeader, FrameType};
+use crate::server::access::AccessPolicy;
+use crate::server::auth::{Authenticator, SessionIdentity, unix_now};
+use crate::server::config::EncodingLimits;
+use crate::server::metrics::Metrics;
+use crate::server::share::ShareScope;
+use crate::transport::simulcast::{SimulcastHubs, SimulcastSubscription};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-/// 默认目标帧率
-const DEFAULT_TARGET_FPS: u32 = 60;
-const MIN_TARGET_FPS: u32 = 24;
-const MAX_TARGET_FPS: u32 = 120;
-
-/// 默认目标码率 (bps)
-const DEFAULT_TARGET_BITRATE: usize = 20_000_000;
-const MIN_TARGET_BITRATE: usize = 2_000_000;
-const MAX_TARGET_BITRATE: usize = 80_000_000;
-
-/// 默认关键帧间隔（秒）
-const DEFAULT_KEYFRAME_INTERVAL_SECS: u32 = 2;
-const MIN_KEYFRAME_INTERVAL_SECS: u32 = 1;
-const MAX_KEYFRAME_INTERVAL_SECS: u32 = 10;
-
 /// 控制消息轮询超时（使用零超时避免浪费帧时间预算）
 const CONTROL_POLL_TIMEOUT: Duration = Duration::ZERO;
 
-#[derive(Debug, Clone, Copy)]
+/// 输入被拒绝提示的最小间隔，避免观看者持续操作时刷屏
+const INPUT_REJECT_NOTICE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 往返时延探测间隔（服务端发送 Ping，客户端回送同序号的 Pong）
+const RTT_PROBE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 单条文本输入消息最多键入的字符数，客户端需将较长的粘贴内容分段发送
+const MAX_TEXT_INPUT_CHARS: usize = 1024;
+
+/// 各传输共享的会话依赖
+pub(crate) struct SessionContext {
+    /// 缓存的显示器列表 JSON 数据
+    pub monitor_list_json: Arc<Vec<u8>>,
+    /// 显示器元数据（用于输入坐标映射）
+    pub monitors: Arc<Vec<MonitorInfo>>,
+    /// 共享捕获的多档编码源（simulcast 会话使用）
+    pub simulcast_hubs: Arc<SimulcastHubs>,
+    /// 在线会话登记表
+    pub sessions: Arc<SessionRegistry>,
+    /// 多控制者之间的输入控制权仲裁
+    pub input_floor: Arc<InputFloor>,
+    /// 连接建立前校验会话令牌
+    pub auth: Arc<Authenticator>,
+    /// 单 IP 并发会话上限与新会话速率限制
+    pub access: Arc<AccessPolicy>,
+    /// 新会话接入前的主机端确认
+    pub consent: Arc<ConsentGate>,
+    /// 编码参数的默认值与上下限（来自配置）
+    pub encoding_limits: EncodingLimits,
+    /// 会话与远程输入的审计日志
+    pub audit: Arc<AuditLog>,
+    /// Prometheus 运行指标
+    pub metrics: Arc<Metrics>,
+}
+
+/// 传输层在握手阶段确定的会话参数
+#[derive(Debug, Clone)]
+pub(crate) struct SessionGrant {
+    pub peer_addr: SocketAddr,
+    pub role: SessionRole,
+    /// 令牌或分享链接授予的最高角色（会话中的角色变更不能超出）
+    pub max_role: SessionRole,
+    /// 通过分享链接接入时限定的显示器与有效期
+    pub share: Option<ShareScope>,
+    /// 认证来源（写入审计日志）
+    pub identity: SessionIdentity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 struct EncodingSettings {
     codec: VideoCodec,
     fps: u32,
     bitrate: usize,
     keyframe_interval_secs: u32,
+    /// 是否订阅共享捕获的多档编码流（此时 fps/码率由共享源决定）
+    simulcast: bool,
 }
 
-impl Default for EncodingSettings {
-    fn default() -> Self {
+impl EncodingSettings {
+    fn from_limits(limits: &EncodingLimits) -> Self {
         Self {
             codec: VideoCodec::Av1,
-            fps: DEFAULT_TARGET_FPS,
-            bitrate: DEFAULT_TARGET_BITRATE,
-            keyframe_interval_secs: DEFAULT_KEYFRAME_INTERVAL_SECS,
+            fps: limits.default_fps,
+            bitrate: limits.default_bitrate,
+            keyframe_interval_secs: limits.default_keyframe_interval,
+            simulcast: false,
         }
     }
 }
@@ -51,6 +103,8 @@ struct EncodingSettingsPayload {
     keyframe_interval: u32,
     #[serde(default)]
     codec: Option<String>,
+    #[serde(default)]
+    simulcast: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
@@ -59,6 +113,60 @@ struct EncodingSettingsStatePayload {
     bitrate: u32,
     keyframe_interval: u32,
     codec: &'static str,
+    simulcast: bool,
+    /// simulcast 模式下当前转发的档位（0 为最高质量）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    layer: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+}
+
+/// 视频来源：会话独占的捕获 + 编码管线，或共享捕获的多档编码订阅
+enum VideoSource {
+    Dedicated {
+        capturer: DdaCapture,
+        encoder: AmfEncoder,
+    },
+    Simulcast(SimulcastSubscription),
+}
+
+impl VideoSource {
+    fn open(
+        monitor_index: u32,
+        settings: EncodingSettings,
+        simulcast_hubs: &SimulcastHubs,
+    ) -> Result<Self, String> {
+        if settings.simulcast {
+            return simulcast_hubs
+                .subscribe(monitor_index, settings.codec)
+                .map(Self::Simulcast);
+        }
+
+        let capturer = DdaCapture::new(monitor_index).map_err(|e| e.to_string())?;
+        let encoder = AmfEncoder::new(&encoder_config(
+            capturer.width(),
+            capturer.height(),
+            settings,
+        ))
+        .map_err(|e| e.to_string())?;
+        Ok(Self::Dedicated { capturer, encoder })
+    }
+
+    fn width(&self) -> u32 {
+        match self {
+            Self::Dedicated { capturer, .. } => capturer.width(),
+            Self::Simulcast(subscription) => subscription.width(),
+        }
+    }
+
+    fn height(&self) -> u32 {
+        match self {
+            Self::Dedicated { capturer, .. } => capturer.height(),
+            Self::Simulcast(subscription) => subscription.height(),
+        }
+    }
 }
 
 enum ClientConnectionState {
@@ -66,6 +174,53 @@ enum ClientConnectionState {
     Closed,
 }
 
+/// 一轮控制消息轮询中收集到的待处理请求
+#[derive(Default)]
+struct PendingControl {
+    force_keyframe: bool,
+    monitor_switch: Option<u32>,
+    encoding_settings: Option<EncodingSettingsPayload>,
+    role_request: Option<RoleRequest>,
+    floor_request: Option<FloorRequest>,
+    /// 因角色不足被拒绝的输入帧数
+    rejected_inputs: u32,
+    /// 因控制权被其他会话持有而丢弃的输入帧数
+    floor_busy_inputs: u32,
+    /// 已注入的输入次数（写入审计汇总）
+    input_activity: InputActivity,
+    /// 收到的 Pong 序号及到达时间
+    pong: Option<(u32, Instant)>,
+    /// 客户端请求松开全部按键（如窗口失去焦点）
+    release_inputs: bool,
+    /// 已注入切换的锁定键目标状态，待确认生效后回报给客户端
+    lock_keys: Option<LockKeysPayload>,
+    /// 客户端切换了鼠标模式，待回执
+    mouse_mode_changed: bool,
+}
+
+/// 处理输入帧所需的会话上下文
+struct InputGate<'a> {
+    injector: Option<&'a InputInjector>,
+    active_monitor: ActiveMonitor,
+    role: SessionRole,
+    session_id: u64,
+    floor: &'a InputFloor,
+    audit: &'a SessionAudit,
+}
+
+#[derive(Debug, Serialize)]
+struct SessionRoleStatePayload<'a> {
+    session_id: u64,
+    role: SessionRole,
+    sessions: &'a [SessionSummary],
+}
+
+#[derive(Debug, Serialize)]
+struct NoticePayload<'a> {
+    kind: &'static str,
+    message: &'a str,
+}
+
 #[derive(Debug, Deserialize)]
 struct MonitorSelectPayload {
     index: u32,
@@ -79,6 +234,35 @@ struct KeyboardInputPayload {
     code: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct TextInputPayload {
+    text: String,
+    /// 来自“按文字键入粘贴”而非输入法
+    #[serde(default)]
+    paste: bool,
+}
+
+/// 客户端的锁定键状态；缺省的键不做同步
+#[derive(Debug, Deserialize)]
+struct LockKeysPayload {
+    #[serde(default)]
+    caps_lock: Option<bool>,
+    #[serde(default)]
+    num_lock: Option<bool>,
+    #[serde(default)]
+    scroll_lock: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TouchInputPayload {
+    contacts: Vec<TouchContact>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MouseModePayload {
+    relative: bool,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(tag = "kind", rename_all = "snake_case")]
 enum MouseInputPayload {
@@ -86,6 +270,11 @@ enum MouseInputPayload {
         x: f32,
         y: f32,
     },
+    /// 相对位移（主机像素，可带小数）
+    MoveRelative {
+        dx: f32,
+        dy: f32,
+    },
     Button {
         x: f32,
         y: f32,
@@ -117,75 +306,166 @@ pub(crate) trait TransportIo {
 pub(crate) fn run_client_service<T: TransportIo>(
     runtime: tokio::runtime::Handle,
     mut io: T,
-    monitor_list_json: Arc<Vec<u8>>,
-    monitors: Arc<Vec<MonitorInfo>>,
+    context: Arc<SessionContext>,
+    grant: SessionGrant,
     transport_name: &'static str,
 ) -> Result<(), String> {
+    let monitors = context.monitors.clone();
+    let simulcast_hubs = context.simulcast_hubs.clone();
+    let metrics = context.metrics.transport(transport_name);
+
+    // 会话名额在整个会话期间保持占用；普通 HTTP 请求与连接不计入
+    let _slot = match context.access.admit(grant.peer_addr.ip()) {
+        Ok(slot) => slot,
+        Err(e) => {
+            log::warn!("拒绝 {} 会话 {}: {}", transport_name, grant.peer_addr, e);
+            context.audit.record(AuditEvent::AccessDenied {
+                transport: transport_name,
+                peer_addr: grant.peer_addr,
+                reason: e.to_string(),
+            });
+            let _ = send_notice(&runtime, &mut io, "access_denied", &e.to_string());
+            return Ok(());
+        }
+    };
+
+    // 分享链接会话只能看到并切换到被授权的显示器
+    let locked_monitor = grant.share.map(|share| share.monitor);
+    let monitor_list_json = match locked_monitor {
+        Some(index) => {
+            let scoped: Vec<_> = monitors.iter().filter(|m| m.index == index).collect();
+            Arc::new(serde_json::to_vec(&scoped).unwrap_or_default())
+        }
+        None => context.monitor_list_json.clone(),
+    };
+
+    // 主机端确认通过前不向客户端发送任何显示器信息
+    if !context.consent.is_off() {
+        send_notice(
+            &runtime,
+            &mut io,
+            "consent_pending",
+            "等待主机端确认连接...",
+        )?;
+        if !context
+            .consent
+            .wait_for_approval(transport_name, grant.peer_addr, grant.role)
+        {
+            log::info!("{} 连接 {} 未获主机端确认", transport_name, grant.peer_addr);
+            context.audit.record(AuditEvent::ConsentRejected {
+                transport: transport_name,
+                peer_addr: grant.peer_addr,
+                identity: &grant.identity,
+            });
+            let _ = send_notice(
+                &runtime,
+                &mut io,
+                "consent_rejected",
+                "主机端拒绝了连接请求",
+            );
+            return Ok(());
+        }
+    }
+
     // 建立连接后立即发送显示器列表
     send_monitor_list(&runtime, &mut io, monitor_list_json.as_ref())?;
 
-    let mut encoding_settings = EncodingSettings::default();
+    let session = context.sessions.register(
+        transport_name,
+        grant.peer_addr,
+        grant.role,
+        grant.max_role,
+        grant.share.map(|share| share.id),
+    );
+    let _floor_guard = context.input_floor.guard(session.id());
+    let mut session_audit = SessionAudit::start(
+        context.audit.clone(),
+        session.id(),
+        transport_name,
+        grant.peer_addr,
+        grant.role,
+        &grant.identity,
+    );
+    // 会话线程上一轮看到的角色，用于发现其他会话或管理接口修改了本会话的角色
+    let mut current_role = session.role();
+    let mut session_generation = None::<u64>;
+    let mut floor_generation = None::<u64>;
+    let mut last_reject_notice = None::<Instant>;
+
+    let encoding_limits = context.encoding_limits;
+    let mut encoding_settings = EncodingSettings::from_limits(&encoding_limits);
     let mut frame_interval = frame_interval_for_fps(encoding_settings.fps);
     let mut capture_timeout_ms = capture_timeout_ms_for_fps(encoding_settings.fps);
 
-    let mut current_monitor_index = 0;
-    let mut capturer = DdaCapture::new(current_monitor_index).map_err(|e| e.to_string())?;
-    let mut encoder = AmfEncoder::new(&encoder_config(
-        capturer.width(),
-        capturer.height(),
+    let mut current_monitor_index = locked_monitor.unwrap_or(0);
+    let mut source = VideoSource::open(
+        current_monitor_index,
         encoding_settings,
-    ))
-    .map_err(|e| e.to_string())?;
+        simulcast_hubs.as_ref(),
+    )?;
     let mut active_monitor = resolve_active_monitor(
         monitors.as_ref(),
         current_monitor_index,
-        capturer.width(),
-        capturer.height(),
+        source.width(),
+        source.height(),
     );
 
     let input_injector = match InputInjector::new() {
-        Ok(injector) => Some(injector),
+        Ok(injector) => {
+            session.attach_held_inputs(injector.held_inputs());
+            Some(injector)
+        }
         Err(e) => {
             log::warn!("初始化输入注入失败，将禁用远程输入: {}", e);
             None
         }
     };
 
-    let mut force_keyframe = true;
-    let mut pending_monitor_switch = None::<u32>;
-    let mut pending_encoding_settings = None::<EncodingSettingsPayload>;
+    let mut pending = PendingControl {
+        force_keyframe: true,
+        ..Default::default()
+    };
+    // 锁定键切换由后台线程确认，会话线程只取回结果
+    let (lock_keys_sender, lock_keys_reports) = std::sync::mpsc::channel::<LockKeys>();
     let mut frame_seq = 0u32;
+    let mut reported_simulcast_layer = None::<usize>;
+    let mut rtt_probe_seq = 0u32;
+    let mut rtt_probe = None::<(u32, Instant)>;
+    let mut last_rtt_probe = Instant::now();
 
     let mut stats_interval = Instant::now();
     let mut frames_encoded: u64 = 0;
     let mut total_encode_time_us: u64 = 0;
 
     log::info!(
-        "{} 客户端独立服务启动: monitor {}, {}x{} @{}fps, codec {}",
+        "{} 客户端独立服务启动: 会话 #{} ({}), monitor {}, {}x{} @{}fps, codec {}",
         transport_name,
+        session.id(),
+        session.role(),
         current_monitor_index,
-        capturer.width(),
-        capturer.height(),
+        source.width(),
+        source.height(),
         encoding_settings.fps,
         encoding_settings.codec
     );
 
-    if let Err(e) = send_encoding_settings_state(&runtime, &mut io, encoding_settings) {
+    if let Err(e) = send_encoding_settings_state(&runtime, &mut io, encoding_settings, &source) {
         log::warn!("发送初始编码设置失败: {}", e);
         return Ok(());
     }
+    publish_session_media(&session, current_monitor_index, encoding_settings);
 
     loop {
-        match drain_control_messages(
-            &runtime,
-            &mut io,
-            &mut force_keyframe,
-            &mut pending_monitor_switch,
-            &mut pending_encoding_settings,
-            input_injector.as_ref(),
+        let input_gate = InputGate {
+            injector: input_injector.as_ref(),
             active_monitor,
-            transport_name,
-        )? {
+            role: session.role(),
+            session_id: session.id(),
+            floor: context.input_floor.as_ref(),
+            audit: &session_audit,
+        };
+        match drain_control_messages(&runtime, &mut io, &mut pending, &input_gate, transport_name)?
+        {
             ClientConnectionState::Alive => {}
             ClientConnectionState::Closed => {
                 log::info!("{} 客户端已断开", transport_name);
@@ -193,38 +473,286 @@ pub(crate) fn run_client_service<T: TransportIo>(
             }
         }
 
-        if let Some(new_index) = pending_monitor_switch.take() {
+        if grant
+            .share
+            .is_some_and(|share| unix_now() >= share.expires_at)
+        {
+            log::info!("会话 #{} 的分享链接已过期", session.id());
+            session_audit.set_end_reason("share_expired");
+            let _ = send_notice(&runtime, &mut io, "share_expired", "分享链接已过期");
+            return Ok(());
+        }
+
+        for command in session.take_commands() {
+            match command {
+                SessionCommand::SelectMonitor(index) => pending.monitor_switch = Some(index),
+                SessionCommand::UpdateEncoding(update) => {
+                    pending.encoding_settings = Some(merge_encoding_update(
+                        pending.encoding_settings.take(),
+                        encoding_settings,
+                        update,
+                    ));
+                }
+                SessionCommand::Disconnect { reason } => {
+                    log::info!("会话 #{} 被管理接口断开: {}", session.id(), reason);
+                    session_audit.set_end_reason(format!("admin: {}", reason));
+                    let _ = send_notice(&runtime, &mut io, "disconnected", &reason);
+                    return Ok(());
+                }
+                SessionCommand::Shutdown => {
+                    log::info!("服务端关闭，结束会话 #{}", session.id());
+                    session_audit.set_end_reason("shutdown");
+                    release_held_inputs(input_injector.as_ref(), session.id());
+                    let _ = send_notice(&runtime, &mut io, "server_shutdown", "服务端正在关闭");
+                    return Ok(());
+                }
+            }
+        }
+
+        if let Some(request) = pending.role_request.take() {
+            if let Err(e) = context.sessions.apply_role_request(session.id(), request) {
+                log::warn!("会话 #{} 角色变更请求被拒绝: {}", session.id(), e);
+                if send_notice(&runtime, &mut io, "role_rejected", &e).is_err() {
+                    log::info!("{} 客户端已断开", transport_name);
+                    return Ok(());
+                }
+            }
+        }
+
+        let generation = context.sessions.generation();
+        if session_generation != Some(generation) {
+            session_generation = Some(generation);
+            let sessions = context.sessions.snapshot();
+            if send_session_role_state(&runtime, &mut io, session.id(), session.role(), &sessions)
+                .is_err()
+            {
+                log::info!("{} 客户端已断开", transport_name);
+                return Ok(());
+            }
+        }
+
+        if let Some(request) = pending.floor_request.take() {
+            if let Err(e) = context
+                .input_floor
+                .apply_request(session.id(), session.role(), request)
+            {
+                if send_notice(&runtime, &mut io, "floor_denied", &e).is_err() {
+                    log::info!("{} 客户端已断开", transport_name);
+                    return Ok(());
+                }
+            }
+        }
+
+        if let Some((sequence, received_at)) = pending.pong.take() {
+            if let Some((probe, sent_at)) = rtt_probe {
+                if probe == sequence {
+                    metrics.record_rtt(received_at.duration_since(sent_at));
+                    rtt_probe = None;
+                }
+            }
+        }
+
+        // 未收到回应的探测直接被下一次替换
+        if last_rtt_probe.elapsed() >= RTT_PROBE_INTERVAL {
+            last_rtt_probe = Instant::now();
+            rtt_probe_seq = rtt_probe_seq.wrapping_add(1);
+            rtt_probe = Some((rtt_probe_seq, last_rtt_probe));
+            if send_ping(&runtime, &mut io, rtt_probe_seq).is_err() {
+                log::info!("{} 客户端已断开", transport_name);
+                return Ok(());
+            }
+        }
+
+        // 失去控制角色时同时交出控制权，并松开此前按下的按键（只在角色变化时处理一次）
+        let role = session.role();
+        if role != current_role {
+            session_audit.observe_role(role);
+            if current_role.can_control() && !role.can_control() {
+                log::info!(
+                    "会话 #{} 角色由 {} 变为 {}，交出输入控制权",
+                    session.id(),
+                    current_role,
+                    role
+                );
+                context.input_floor.release(session.id());
+                pending.release_inputs = true;
+            }
+            current_role = role;
+        }
+        session_audit.add_input(std::mem::take(&mut pending.input_activity));
+
+        let generation = context.input_floor.generation();
+        if floor_generation != Some(generation) {
+            floor_generation = Some(generation);
+            let snapshot = context.input_floor.snapshot();
+            // 控制权被其他会话接管
+            if snapshot.holder.is_some_and(|holder| holder != session.id()) {
+                pending.release_inputs = true;
+            }
+            if send_json_packet(&runtime, &mut io, FrameType::FloorControl, &snapshot).is_err() {
+                log::info!("{} 客户端已断开", transport_name);
+                return Ok(());
+            }
+        }
+
+        if std::mem::take(&mut pending.release_inputs) {
+            release_held_inputs(input_injector.as_ref(), session.id());
+        }
+
+        let floor_busy_inputs = std::mem::take(&mut pending.floor_busy_inputs);
+        if floor_busy_inputs > 0
+            && last_reject_notice.is_none_or(|t| t.elapsed() >= INPUT_REJECT_NOTICE_INTERVAL)
+        {
+            last_reject_notice = Some(Instant::now());
+            let message = match context.input_floor.snapshot().holder {
+                Some(holder) => format!("输入控制权由会话 #{} 持有，可请求接管", holder),
+                None => "输入控制权暂不可用".to_string(),
+            };
+            log::debug!(
+                "{} 会话 #{} 未持有控制权，已丢弃 {} 帧输入",
+                transport_name,
+                session.id(),
+                floor_busy_inputs
+            );
+            if send_notice(&runtime, &mut io, "floor_busy", &message).is_err() {
+                log::info!("{} 客户端已断开", transport_name);
+                return Ok(());
+            }
+        }
+
+        let rejected_inputs = std::mem::take(&mut pending.rejected_inputs);
+        if rejected_inputs > 0
+            && last_reject_notice.is_none_or(|t| t.elapsed() >= INPUT_REJECT_NOTICE_INTERVAL)
+        {
+            last_reject_notice = Some(Instant::now());
+            log::warn!(
+                "{} 会话 #{} 以 {} 角色发送输入，已拒绝 {} 帧",
+                transport_name,
+                session.id(),
+                session.role(),
+                rejected_inputs
+            );
+            if send_notice(
+                &runtime,
+                &mut io,
+                "input_rejected",
+                "当前为观看者角色，远程输入已被拒绝",
+            )
+            .is_err()
+            {
+                log::info!("{} 客户端已断开", transport_name);
+                return Ok(());
+            }
+        }
+
+        if std::mem::take(&mut pending.mouse_mode_changed) {
+            let relative = input_injector
+                .as_ref()
+                .is_some_and(|injector| injector.relative_mouse());
+            log::info!(
+                "会话 #{} 鼠标模式: {}",
+                session.id(),
+                if relative { "相对" } else { "绝对" }
+            );
+            if send_json_packet(
+                &runtime,
+                &mut io,
+                FrameType::MouseMode,
+                &MouseModePayload { relative },
+            )
+            .is_err()
+            {
+                log::info!("{} 客户端已断开", transport_name);
+                return Ok(());
+            }
+        }
+
+        if let Some(target) = pending.lock_keys.take() {
+            let sender = lock_keys_sender.clone();
+            runtime.spawn_blocking(move || {
+                let state =
+                    wait_for_lock_keys(target.caps_lock, target.num_lock, target.scroll_lock);
+                let _ = sender.send(state);
+            });
+        }
+        while let Ok(lock_keys) = lock_keys_reports.try_recv() {
+            if send_notice(
+                &runtime,
+                &mut io,
+                "lock_keys",
+                &describe_lock_keys(lock_keys),
+            )
+            .is_err()
+            {
+                log::info!("{} 客户端已断开", transport_name);
+                return Ok(());
+            }
+        }
+
+        if let Some(new_index) = pending
+            .monitor_switch
+            .take()
+            .filter(|&index| locked_monitor.is_none_or(|locked| locked == index))
+        {
+            let previous_monitor_index = current_monitor_index;
             if switch_monitor(
                 new_index,
                 &mut current_monitor_index,
-                &mut capturer,
-                &mut encoder,
+                &mut source,
                 encoding_settings,
+                simulcast_hubs.as_ref(),
             )? {
-                force_keyframe = true;
+                pending.force_keyframe = true;
+                reported_simulcast_layer = None;
+                if !encoding_settings.simulcast {
+                    metrics.record_encoder_reinit();
+                }
+                // 按键坐标系随显示器变化，切换前按下的按钮不再对应原位置
+                release_held_inputs(input_injector.as_ref(), session.id());
                 active_monitor = resolve_active_monitor(
                     monitors.as_ref(),
                     current_monitor_index,
-                    capturer.width(),
-                    capturer.height(),
+                    source.width(),
+                    source.height(),
                 );
+                publish_session_media(&session, current_monitor_index, encoding_settings);
+                session_audit.record(AuditEvent::MonitorSwitch {
+                    session: session_audit.session(),
+                    from: previous_monitor_index,
+                    to: current_monitor_index,
+                });
             }
         }
 
-        if let Some(payload) = pending_encoding_settings.take() {
+        if let Some(payload) = pending.encoding_settings.take() {
             if apply_encoding_settings(
                 payload,
+                &encoding_limits,
                 &mut encoding_settings,
-                &mut encoder,
-                capturer.width(),
-                capturer.height(),
+                &mut source,
+                current_monitor_index,
+                simulcast_hubs.as_ref(),
             ) {
                 frame_interval = frame_interval_for_fps(encoding_settings.fps);
                 capture_timeout_ms = capture_timeout_ms_for_fps(encoding_settings.fps);
-                force_keyframe = true;
+                pending.force_keyframe = true;
+                reported_simulcast_layer = None;
+                if !encoding_settings.simulcast {
+                    metrics.record_encoder_reinit();
+                }
+                publish_session_media(&session, current_monitor_index, encoding_settings);
+                session_audit.record(AuditEvent::EncodingChange {
+                    session: session_audit.session(),
+                    codec: encoding_settings.codec.as_client_name(),
+                    fps: encoding_settings.fps,
+                    bitrate: encoding_settings.bitrate,
+                    keyframe_interval: encoding_settings.keyframe_interval_secs,
+                    simulcast: encoding_settings.simulcast,
+                });
             }
 
-            if send_encoding_settings_state(&runtime, &mut io, encoding_settings).is_err() {
+            if send_encoding_settings_state(&runtime, &mut io, encoding_settings, &source).is_err()
+            {
                 log::info!("{} 客户端已断开", transport_name);
                 return Ok(());
             }
@@ -232,38 +760,94 @@ pub(crate) fn run_client_service<T: TransportIo>(
 
         let frame_start = Instant::now();
 
-        let requesting_kf = std::mem::take(&mut force_keyframe);
+        let requesting_kf = std::mem::take(&mut pending.force_keyframe);
         if requesting_kf {
             log::info!("客户端请求关键帧");
         }
 
-        let frame_ready = capturer
-            .capture_frame(capture_timeout_ms)
-            .map_err(|e| e.to_string())?;
+        match &mut source {
+            VideoSource::Dedicated { capturer, encoder } => {
+                let frame_ready = capturer
+                    .capture_frame(capture_timeout_ms)
+                    .map_err(|e| e.to_string())?;
+                metrics.record_capture(frame_ready);
+
+                if !frame_ready {
+                    pending.force_keyframe = requesting_kf; // 未捕获到帧，恢复关键帧请求
+                    pace_frame(frame_start, frame_interval);
+                    continue;
+                }
+                if requesting_kf {
+                    metrics.record_keyframe_request();
+                }
 
-        if !frame_ready {
-            force_keyframe = requesting_kf; // 未捕获到帧，恢复关键帧请求
-            pace_frame(frame_start, frame_interval);
-            continue;
-        }
+                let nv12_data = capturer.read_nv12().map_err(|e| e.to_string())?;
 
-        let nv12_data = capturer.read_nv12().map_err(|e| e.to_string())?;
+                let encoded_frames = encoder
+                    .encode(nv12_data, requesting_kf)
+                    .map_err(|e| e.to_string())?;
 
-        let encoded_frames = encoder
-            .encode(&nv12_data, requesting_kf)
-            .map_err(|e| e.to_string())?;
+                for ef in encoded_frames {
+                    metrics.record_encoded(ef.encode_time_us);
+                    let packet =
+                        build_video_packet(&ef.data, frame_seq, ef.pts as u32, ef.is_keyframe);
+                    frame_seq = frame_seq.wrapping_add(1);
 
-        for ef in encoded_frames {
-            let packet = build_video_packet(&ef.data, frame_seq, ef.pts as u32, ef.is_keyframe);
-            frame_seq = frame_seq.wrapping_add(1);
+                    let packet_len = packet.len();
+                    if send_binary_packet(&runtime, &mut io, packet).is_err() {
+                        log::info!("{} 客户端已断开", transport_name);
+                        return Ok(());
+                    }
+                    session.record_frame_sent(packet_len);
+                    metrics.record_sent(packet_len);
 
-            if send_binary_packet(&runtime, &mut io, packet).is_err() {
-                log::info!("{} 客户端已断开", transport_name);
-                return Ok(());
+                    frames_encoded += 1;
+                    total_encode_time_us += ef.encode_time_us;
+                }
+
+                pace_frame(frame_start, frame_interval);
             }
+            VideoSource::Simulcast(subscription) => {
+                if requesting_kf {
+                    subscription.request_keyframe();
+                    metrics.record_keyframe_request();
+                }
+
+                // 共享捕获线程负责节拍，这里只需等待下一帧
+                let layer_frames = subscription.recv_frames(&runtime, frame_interval)?;
+                metrics.record_dropped(subscription.take_dropped_frames());
+
+                for lf in layer_frames {
+                    let ef = &lf.frame;
+                    let packet =
+                        build_video_packet(&ef.data, frame_seq, ef.pts as u32, ef.is_keyframe);
+                    frame_seq = frame_seq.wrapping_add(1);
+
+                    let packet_len = packet.len();
+                    let send_start = Instant::now();
+                    if send_binary_packet(&runtime, &mut io, packet).is_err() {
+                        log::info!("{} 客户端已断开", transport_name);
+                        return Ok(());
+                    }
+                    subscription.record_send(packet_len, send_start.elapsed());
+                    session.record_frame_sent(packet_len);
+                    metrics.record_sent(packet_len);
+
+                    frames_encoded += 1;
+                    total_encode_time_us += ef.encode_time_us;
+                }
 
-            frames_encoded += 1;
-            total_encode_time_us += ef.encode_time_us;
+                let active_layer = subscription.active_layer().map(|(index, _)| index);
+                if active_layer.is_some() && active_layer != reported_simulcast_layer {
+                    reported_simulcast_layer = active_layer;
+                    if send_encoding_settings_state(&runtime, &mut io, encoding_settings, &source)
+                        .is_err()
+                    {
+                        log::info!("{} 客户端已断开", transport_name);
+                        return Ok(());
+                    }
+                }
+            }
         }
 
         if stats_interval.elapsed() >= Duration::from_secs(5) {
@@ -289,8 +873,6 @@ pub(crate) fn run_client_service<T: TransportIo>(
             frames_encoded = 0;
             total_encode_time_us = 0;
         }
-
-        pace_frame(frame_start, frame_interval);
     }
 }
 
@@ -317,21 +899,122 @@ fn send_monitor_list<T: TransportIo>(
     Ok(())
 }
 
+fn send_ping<T: TransportIo>(
+    runtime: &tokio::runtime::Handle,
+    io: &mut T,
+    sequence: u32,
+) -> Result<(), String> {
+    let header = FrameHeader {
+        frame_type: FrameType::Ping,
+        flags: FrameFlags::empty(),
+        sequence,
+        pts: 0,
+        payload_len: 0,
+    };
+    send_binary_packet(runtime, io, header.to_bytes().to_vec())
+}
+
 fn send_encoding_settings_state<T: TransportIo>(
     runtime: &tokio::runtime::Handle,
     io: &mut T,
     settings: EncodingSettings,
+    source: &VideoSource,
 ) -> Result<(), String> {
-    let payload = EncodingSettingsStatePayload {
+    let mut payload = EncodingSettingsStatePayload {
         fps: settings.fps,
         bitrate: settings.bitrate as u32,
         keyframe_interval: settings.keyframe_interval_secs,
         codec: settings.codec.as_client_name(),
+        simulcast: settings.simulcast,
+        layer: None,
+        width: None,
+        height: None,
+    };
+
+    if let VideoSource::Simulcast(subscription) = source {
+        if let Some((index, layer)) = subscription.active_layer() {
+            payload.bitrate = layer.bitrate as u32;
+            payload.layer = Some(index as u32);
+            payload.width = Some(layer.width);
+            payload.height = Some(layer.height);
+        }
+    }
+
+    send_json_packet(runtime, io, FrameType::EncodingSettings, &payload)
+}
+
+/// 将会话当前的显示器与编码参数同步到登记表，供管理接口查询
+fn publish_session_media(session: &SessionHandle, monitor_index: u32, settings: EncodingSettings) {
+    session.update_media(SessionMedia {
+        monitor: monitor_index,
+        codec: settings.codec.as_client_name(),
+        fps: settings.fps,
+        bitrate: settings.bitrate as u32,
+        simulcast: settings.simulcast,
+    });
+}
+
+/// 将管理接口的部分修改合并为完整的编码设置请求
+fn merge_encoding_update(
+    pending: Option<EncodingSettingsPayload>,
+    current: EncodingSettings,
+    update: EncodingUpdate,
+) -> EncodingSettingsPayload {
+    let base = pending.unwrap_or(EncodingSettingsPayload {
+        fps: current.fps,
+        bitrate: current.bitrate as u32,
+        keyframe_interval: current.keyframe_interval_secs,
+        codec: Some(current.codec.as_client_name().to_string()),
+        simulcast: Some(current.simulcast),
+    });
+
+    EncodingSettingsPayload {
+        fps: update.fps.unwrap_or(base.fps),
+        bitrate: update.bitrate.unwrap_or(base.bitrate),
+        keyframe_interval: update.keyframe_interval.unwrap_or(base.keyframe_interval),
+        codec: update.codec.or(base.codec),
+        simulcast: update.simulcast.or(base.simulcast),
+    }
+}
+
+fn send_session_role_state<T: TransportIo>(
+    runtime: &tokio::runtime::Handle,
+    io: &mut T,
+    session_id: u64,
+    role: SessionRole,
+    sessions: &[SessionSummary],
+) -> Result<(), String> {
+    let payload = SessionRoleStatePayload {
+        session_id,
+        role,
+        sessions,
     };
+    send_json_packet(runtime, io, FrameType::SessionRole, &payload)
+}
 
-    let payload_bytes = serde_json::to_vec(&payload).map_err(|e| e.to_string())?;
+fn send_notice<T: TransportIo>(
+    runtime: &tokio::runtime::Handle,
+    io: &mut T,
+    kind: &'static str,
+    message: &str,
+) -> Result<(), String> {
+    send_json_packet(
+        runtime,
+        io,
+        FrameType::Notice,
+        &NoticePayload { kind, message },
+    )
+}
+
+fn send_json_packet<T: TransportIo, P: Serialize>(
+    runtime: &tokio::runtime::Handle,
+    io: &mut T,
+    frame_type: FrameType,
+    payload: &P,
+) -> Result<(), String> {
+    let payload_bytes = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
     let header = FrameHeader {
-        frame_type: FrameType::EncodingSettings,
+        frame_type,
         flags: FrameFlags::empty(),
         sequence: 0,
         pts: 0,
@@ -356,11 +1039,8 @@ fn send_binary_packet<T: TransportIo>(
 fn drain_control_messages<T: TransportIo>(
     runtime: &tokio::runtime::Handle,
     io: &mut T,
-    force_keyframe: &mut bool,
-    pending_monitor_switch: &mut Option<u32>,
-    pending_encoding_settings: &mut Option<EncodingSettingsPayload>,
-    input_injector: Option<&InputInjector>,
-    active_monitor: ActiveMonitor,
+    pending: &mut PendingControl,
+    input_gate: &InputGate,
     transport_name: &'static str,
 ) -> Result<ClientConnectionState, String> {
     loop {
@@ -376,24 +1056,14 @@ fn drain_control_messages<T: TransportIo>(
             return Ok(ClientConnectionState::Alive);
         };
 
-        handle_binary_control_message(
-            &data,
-            force_keyframe,
-            pending_monitor_switch,
-            pending_encoding_settings,
-            input_injector,
-            active_monitor,
-        );
+        handle_binary_control_message(&data, pending, input_gate);
     }
 }
 
 fn handle_binary_control_message(
     data: &[u8],
-    force_keyframe: &mut bool,
-    pending_monitor_switch: &mut Option<u32>,
-    pending_encoding_settings: &mut Option<EncodingSettingsPayload>,
-    input_injector: Option<&InputInjector>,
-    active_monitor: ActiveMonitor,
+    pending: &mut PendingControl,
+    input_gate: &InputGate,
 ) {
     if data.len() < FrameHeader::SIZE {
         return;
@@ -408,35 +1078,97 @@ fn handle_binary_control_message(
 
     match header.frame_type {
         FrameType::KeyframeRequest => {
-            *force_keyframe = true;
+            pending.force_keyframe = true;
+        }
+        FrameType::Pong => {
+            pending.pong = Some((header.sequence, Instant::now()));
+        }
+        // 只松开本会话按下的键，不受角色与控制权限制
+        FrameType::InputRelease => {
+            pending.release_inputs = true;
+        }
+        // 只影响本会话的鼠标注入方式，不受角色与控制权限制
+        FrameType::MouseMode => {
+            if let Some(mode) = parse_json_payload::<MouseModePayload>(data, header.payload_len) {
+                if let Some(injector) = input_gate.injector {
+                    injector.set_relative_mouse(mode.relative);
+                }
+                pending.mouse_mode_changed = true;
+            }
         }
         FrameType::MonitorSelect => {
             if let Some(index) = parse_monitor_index(data, header.payload_len) {
-                *pending_monitor_switch = Some(index);
+                pending.monitor_switch = Some(index);
             }
         }
         FrameType::EncodingSettings => {
             if let Some(payload) =
                 parse_json_payload::<EncodingSettingsPayload>(data, header.payload_len)
             {
-                *pending_encoding_settings = Some(payload);
+                pending.encoding_settings = Some(payload);
             }
         }
+        FrameType::SessionRole => {
+            if let Some(request) = parse_json_payload::<RoleRequest>(data, header.payload_len) {
+                pending.role_request = Some(request);
+            }
+        }
+        FrameType::FloorControl => {
+            if let Some(request) = parse_json_payload::<FloorRequest>(data, header.payload_len) {
+                pending.floor_request = Some(request);
+            }
+        }
+        FrameType::MouseInput
+        | FrameType::KeyboardInput
+        | FrameType::TextInput
+        | FrameType::LockKeys
+        | FrameType::TouchInput
+        | FrameType::PenInput
+            if !input_gate.role.can_control() =>
+        {
+            pending.rejected_inputs += 1;
+        }
+        FrameType::MouseInput
+        | FrameType::KeyboardInput
+        | FrameType::TextInput
+        | FrameType::LockKeys
+        | FrameType::TouchInput
+        | FrameType::PenInput
+            if input_gate.floor.try_use(input_gate.session_id) != FloorDecision::Granted =>
+        {
+            pending.floor_busy_inputs += 1;
+        }
         FrameType::MouseInput => {
             if let (Some(injector), Some(mouse_input)) = (
-                input_injector,
+                input_gate.injector,
                 parse_json_payload::<MouseInputPayload>(data, header.payload_len),
             ) {
-                if let Err(e) = apply_mouse_input(injector, active_monitor, mouse_input) {
+                match &mouse_input {
+                    MouseInputPayload::Button { down: true, .. } => {
+                        pending.input_activity.clicks += 1
+                    }
+                    MouseInputPayload::Wheel { .. } => pending.input_activity.wheel += 1,
+                    _ => {}
+                }
+                if let Err(e) = apply_mouse_input(injector, input_gate.active_monitor, mouse_input)
+                {
                     log::debug!("处理鼠标输入失败: {}", e);
                 }
             }
         }
         FrameType::KeyboardInput => {
             if let (Some(injector), Some(keyboard_input)) = (
-                input_injector,
+                input_gate.injector,
                 parse_json_payload::<KeyboardInputPayload>(data, header.payload_len),
             ) {
+                if keyboard_input.down {
+                    pending.input_activity.keys += 1;
+                }
+                input_gate.audit.keystroke(
+                    keyboard_input.key_code,
+                    keyboard_input.code.as_deref(),
+                    keyboard_input.down,
+                );
                 if let Err(e) = injector.keyboard_key(
                     keyboard_input.key_code,
                     keyboard_input.code.as_deref(),
@@ -446,10 +1178,102 @@ fn handle_binary_control_message(
                 }
             }
         }
+        FrameType::TextInput => {
+            if let (Some(injector), Some(mut text_input)) = (
+                input_gate.injector,
+                parse_json_payload::<TextInputPayload>(data, header.payload_len),
+            ) {
+                if truncate_text_input(&mut text_input.text) {
+                    log::debug!("文本输入超过 {} 个字符，已截断", MAX_TEXT_INPUT_CHARS);
+                }
+                input_gate
+                    .audit
+                    .text_input(&text_input.text, text_input.paste);
+                match injector.type_text(&text_input.text) {
+                    Ok(typed) => pending.input_activity.text_chars += typed as u64,
+                    Err(e) => log::debug!("处理文本输入失败: {}", e),
+                }
+            }
+        }
+        FrameType::TouchInput => {
+            if let (Some(injector), Some(touch_input)) = (
+                input_gate.injector,
+                parse_json_payload::<TouchInputPayload>(data, header.payload_len),
+            ) {
+                pending.input_activity.clicks += touch_input
+                    .contacts
+                    .iter()
+                    .filter(|contact| contact.state == ContactState::Down)
+                    .count() as u64;
+                if let Err(e) = injector.touch(input_gate.active_monitor, &touch_input.contacts) {
+                    log::debug!("处理触控输入失败: {}", e);
+                }
+            }
+        }
+        FrameType::PenInput => {
+            if let (Some(injector), Some(pen_input)) = (
+                input_gate.injector,
+                parse_json_payload::<PenSample>(data, header.payload_len),
+            ) {
+                if pen_input.state == PenState::Down {
+                    pending.input_activity.clicks += 1;
+                }
+                if let Err(e) = injector.pen(input_gate.active_monitor, &pen_input) {
+                    log::debug!("处理笔输入失败: {}", e);
+                }
+            }
+        }
+        FrameType::LockKeys => {
+            if let (Some(injector), Some(lock_keys)) = (
+                input_gate.injector,
+                parse_json_payload::<LockKeysPayload>(data, header.payload_len),
+            ) {
+                match injector.toggle_lock_keys(
+                    lock_keys.caps_lock,
+                    lock_keys.num_lock,
+                    lock_keys.scroll_lock,
+                ) {
+                    Ok(()) => pending.lock_keys = Some(lock_keys),
+                    Err(e) => log::debug!("同步锁定键失败: {}", e),
+                }
+            }
+        }
         _ => {}
     }
 }
 
+fn release_held_inputs(injector: Option<&InputInjector>, session_id: u64) {
+    let Some(injector) = injector else {
+        return;
+    };
+    match injector.release_all() {
+        Ok(0) => {}
+        Ok(released) => log::info!("会话 #{} 已松开 {} 个按下的按键/按钮", session_id, released),
+        Err(e) => log::warn!("会话 #{} 松开按键失败: {}", session_id, e),
+    }
+}
+
+/// 截断到最多 MAX_TEXT_INPUT_CHARS 个字符（按字符边界），返回是否发生截断
+fn truncate_text_input(text: &mut String) -> bool {
+    match text.char_indices().nth(MAX_TEXT_INPUT_CHARS) {
+        Some((end, _)) => {
+            text.truncate(end);
+            true
+        }
+        None => false,
+    }
+}
+
+fn describe_lock_keys(state: LockKeys) -> String {
+    let on_off = |on: bool| if on { "开" } else { "关" };
+    format!(
+        "主机锁定键：CapsLock {} · NumLock {} · ScrollLock {}",
+        on_off(state.caps_lock),
+        on_off(state.num_lock),
+        on_off(state.scroll_lock)
+    )
+}
+
 fn apply_mouse_input(
     injector: &InputInjector,
     active_monitor: ActiveMonitor,
@@ -457,6 +1281,7 @@ fn apply_mouse_input(
 ) -> Result<(), String> {
     match mouse_input {
         MouseInputPayload::Move { x, y } => injector.move_mouse(active_monitor, x, y),
+        MouseInputPayload::MoveRelative { dx, dy } => injector.move_mouse_relative(dx, dy),
         MouseInputPayload::Button { x, y, button, down } => {
             injector.mouse_button(active_monitor, x, y, button, down)
         }
@@ -475,10 +1300,11 @@ fn parse_monitor_index(data: &[u8], payload_len: u32) -> Option<u32> {
 
 fn apply_encoding_settings(
     payload: EncodingSettingsPayload,
+    limits: &EncodingLimits,
     encoding_settings: &mut EncodingSettings,
-    encoder: &mut AmfEncoder,
-    width: u32,
-    height: u32,
+    source: &mut VideoSource,
+    monitor_index: u32,
+    simulcast_hubs: &SimulcastHubs,
 ) -> bool {
     let next_codec = match payload.codec.as_deref() {
         Some(raw_codec) => match VideoCodec::from_client_name(raw_codec) {
@@ -493,31 +1319,50 @@ fn apply_encoding_settings(
 
     let next_settings = EncodingSettings {
         codec: next_codec,
-        fps: payload.fps.clamp(MIN_TARGET_FPS, MAX_TARGET_FPS),
-        bitrate: (payload.bitrate as usize).clamp(MIN_TARGET_BITRATE, MAX_TARGET_BITRATE),
-        keyframe_interval_secs: payload
-            .keyframe_interval
-            .clamp(MIN_KEYFRAME_INTERVAL_SECS, MAX_KEYFRAME_INTERVAL_SECS),
+        fps: limits.clamp_fps(payload.fps),
+        bitrate: limits.clamp_bitrate(payload.bitrate as usize),
+        keyframe_interval_secs: limits.clamp_keyframe_interval(payload.keyframe_interval),
+        simulcast: payload.simulcast.unwrap_or(encoding_settings.simulcast),
     };
 
-    if next_settings.codec == encoding_settings.codec
-        && next_settings.fps == encoding_settings.fps
-        && next_settings.bitrate == encoding_settings.bitrate
-        && next_settings.keyframe_interval_secs == encoding_settings.keyframe_interval_secs
-    {
+    if next_settings == *encoding_settings {
         return false;
     }
 
-    match AmfEncoder::new(&encoder_config(width, height, next_settings)) {
-        Ok(new_encoder) => {
-            *encoder = new_encoder;
+    // 独占管线下仅需重建编码器；切换 simulcast 或在 simulcast 下换编码格式则需重建来源
+    let result = match source {
+        VideoSource::Dedicated { capturer, encoder } if !next_settings.simulcast => {
+            AmfEncoder::new(&encoder_config(
+                capturer.width(),
+                capturer.height(),
+                next_settings,
+            ))
+            .map(|new_encoder| *encoder = new_encoder)
+            .map_err(|e| e.to_string())
+        }
+        VideoSource::Simulcast(subscription)
+            if next_settings.simulcast && subscription.codec() == next_settings.codec =>
+        {
+            Ok(())
+        }
+        _ => VideoSource::open(monitor_index, next_settings, simulcast_hubs)
+            .map(|new_source| *source = new_source),
+    };
+
+    match result {
+        Ok(()) => {
             *encoding_settings = next_settings;
             log::info!(
-                "编码设置已更新: {}, {}fps, {}Mbps, 关键帧间隔 {}s",
+                "编码设置已更新: {}, {}fps, {}Mbps, 关键帧间隔 {}s{}",
                 next_settings.codec,
                 next_settings.fps,
                 next_settings.bitrate / 1_000_000,
-                next_settings.keyframe_interval_secs
+                next_settings.keyframe_interval_secs,
+                if next_settings.simulcast {
+                    " [simulcast]"
+                } else {
+                    ""
+                }
             );
             true
         }
@@ -541,40 +1386,27 @@ fn parse_json_payload<T: DeserializeOwned>(data: &[u8], payload_len: u32) -> Opt
 fn switch_monitor(
     new_index: u32,
     current_monitor_index: &mut u32,
-    capturer: &mut DdaCapture,
-    encoder: &mut AmfEncoder,
+    source: &mut VideoSource,
     encoding_settings: EncodingSettings,
+    simulcast_hubs: &SimulcastHubs,
 ) -> Result<bool, String> {
     if new_index == *current_monitor_index {
         return Ok(false);
     }
 
     log::info!("客户端请求切换屏幕到 {}", new_index);
-    let new_capturer = match DdaCapture::new(new_index) {
-        Ok(c) => c,
+    let new_source = match VideoSource::open(new_index, encoding_settings, simulcast_hubs) {
+        Ok(s) => s,
         Err(e) => {
             log::error!("切换显示器失败: {}", e);
             return Ok(false);
         }
     };
 
-    let new_encoder = match AmfEncoder::new(&encoder_config(
-        new_capturer.width(),
-        new_capturer.height(),
-        encoding_settings,
-    )) {
-        Ok(e) => e,
-        Err(e) => {
-            log::error!("切换显示器后初始化编码器失败: {}", e);
-            return Ok(false);
-        }
-    };
-
-    *capturer = new_capturer;
-    *encoder = new_encoder;
+    *source = new_source;
     *current_monitor_index = new_index;
 
-    log::info!("显示器切换成功：{}x{}", capturer.width(), capturer.height());
+    log::info!("显示器切换成功：{}x{}", source.width(), source.height());
     Ok(true)
 }
 
@@ -607,11 +1439,11 @@ fn encoder_config(width: u32, height: u32, settings: EncodingSettings) -> Encode
     }
 }
 
-fn frame_interval_for_fps(fps: u32) -> Duration {
+pub(crate) fn frame_interval_for_fps(fps: u32) -> Duration {
     Duration::from_micros(1_000_000 / fps as u64)
 }
 
-fn capture_timeout_ms_for_fps(fps: u32) -> u32 {
+pub(crate) fn capture_timeout_ms_for_fps(fps: u32) -> u32 {
     // 向上取整并额外加 1ms，降低周期性超时概率
     (1_000u32 + fps - 1) / fps + 1
 }
@@ -636,7 +1468,7 @@ fn build_video_packet(encoded_data: &[u8], sequence: u32, pts: u32, is_keyframe:
     packet
 }
 
-fn pace_frame(frame_start: Instant, frame_interval: Duration) {
+pub(crate) fn pace_frame(frame_start: Instant, frame_interval: Duration) {
     let elapsed = frame_start.elapsed();
     if elapsed < frame_interval {
         let sleep_duration = frame_interval - elapsed;
@@ -648,3 +1480,54 @@ fn pace_frame(frame_start: Instant, frame_interval: Duration) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncates_long_text_on_char_boundaries() {
+        let mut short = "你好".repeat(10);
+        assert!(!truncate_text_input(&mut short));
+        assert_eq!(short.chars().count(), 20);
+
+        let mut exact = "a".repeat(MAX_TEXT_INPUT_CHARS);
+        assert!(!truncate_text_input(&mut exact));
+        assert_eq!(exact.len(), MAX_TEXT_INPUT_CHARS);
+
+        let mut long = "中".repeat(MAX_TEXT_INPUT_CHARS + 5);
+        assert!(truncate_text_input(&mut long));
+        assert_eq!(long.chars().count(), MAX_TEXT_INPUT_CHARS);
+        assert_eq!(long.len(), MAX_TEXT_INPUT_CHARS * "中".len());
+    }
+
+    #[test]
+    fn describes_lock_keys() {
+        let state = LockKeys {
+            caps_lock: true,
+            num_lock: false,
+            scroll_lock: true,
+        };
+        assert_eq!(
+            describe_lock_keys(state),
+            "主机锁定键：CapsLock 开 · NumLock 关 · ScrollLock 开"
+        );
+    }
+
+    #[test]
+    fn parses_mouse_payloads() {
+        let relative: MouseInputPayload =
+            serde_json::from_str(r#"{"kind":"move_relative","dx":1.5,"dy":-2}"#).unwrap();
+        assert!(matches!(
+            relative,
+            MouseInputPayload::MoveRelative { dx, dy } if dx == 1.5 && dy == -2.0
+        ));
+
+        let mode: MouseModePayload = serde_json::from_str(r#"{"relative":true}"#).unwrap();
+        assert!(mode.relative);
+        assert_eq!(
+            serde_json::to_string(&MouseModePayload { relative: false }).unwrap(),
+            r#"{"relative":false}"#
+        );
+    }
+}
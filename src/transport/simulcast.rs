@@ -0,0 +1,630 @@
+use super::session::{capture_timeout_ms_for_fps, frame_interval_for_fps, pace_frame};
+use crate::capture::dda::DdaCapture;
+use crate::encode::amf::{AmfEncoder, EncodedFrame, EncoderConfig, VideoCodec};
+use crate::server::metrics::TransportMetrics;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak, mpsc};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// 共享捕获的帧率与关键帧间隔（各档位一致，便于在关键帧处切换）
+const SIMULCAST_FPS: u32 = 60;
+const SIMULCAST_KEYFRAME_INTERVAL_SECS: u32 = 2;
+
+/// 广播队列深度（约 0.5 秒），订阅者落后超过该值时需等待下一个关键帧
+const SIMULCAST_CHANNEL_CAPACITY: usize = 32;
+
+/// 档位定义：相对源分辨率的缩放比例与目标码率，按质量从高到低排列
+const SIMULCAST_LAYER_SPECS: [SimulcastLayerSpec; 3] = [
+    SimulcastLayerSpec {
+        scale_num: 1,
+        scale_den: 1,
+        bitrate: 20_000_000,
+    },
+    SimulcastLayerSpec {
+        scale_num: 2,
+        scale_den: 3,
+        bitrate: 8_000_000,
+    },
+    SimulcastLayerSpec {
+        scale_num: 1,
+        scale_den: 2,
+        bitrate: 3_000_000,
+    },
+];
+
+/// 带宽评估窗口
+const BANDWIDTH_WINDOW: Duration = Duration::from_secs(2);
+/// 发送阻塞时间占比超过该值视为拥塞，降一档
+const CONGESTED_BUSY_RATIO: f64 = 0.5;
+/// 发送阻塞时间占比低于该值才考虑升档
+const IDLE_BUSY_RATIO: f64 = 0.15;
+/// 升档要求估计容量至少为上一档码率的倍数
+const UPGRADE_HEADROOM: f64 = 1.5;
+/// 连续多少个空闲窗口后才升档，避免在两档之间来回抖动
+const UPGRADE_STABLE_WINDOWS: u32 = 2;
+
+#[derive(Debug, Clone, Copy)]
+struct SimulcastLayerSpec {
+    scale_num: u32,
+    scale_den: u32,
+    bitrate: usize,
+}
+
+/// 已按源分辨率解析的档位
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SimulcastLayer {
+    pub width: u32,
+    pub height: u32,
+    pub bitrate: usize,
+}
+
+/// 广播给订阅者的单档编码帧
+pub(crate) struct LayerFrame {
+    pub layer: usize,
+    pub frame: EncodedFrame,
+}
+
+type HubKey = (u32, VideoCodec);
+
+/// 单个 (显示器, 编码格式) 的 hub 槽位；启动 hub 时只持有槽位锁，不阻塞其他显示器与编码格式
+type HubSlot = Arc<Mutex<Option<Arc<SimulcastHub>>>>;
+
+type HubMap = Mutex<HashMap<HubKey, HubSlot>>;
+
+/// 按 (显示器, 编码格式) 复用的共享捕获 + 多档编码源
+pub struct SimulcastHubs {
+    hubs: Arc<HubMap>,
+    /// 共享捕获线程的捕获与编码指标
+    metrics: Arc<TransportMetrics>,
+}
+
+impl SimulcastHubs {
+    pub fn new(metrics: Arc<TransportMetrics>) -> Self {
+        Self {
+            hubs: Arc::new(Mutex::new(HashMap::new())),
+            metrics,
+        }
+    }
+
+    /// 订阅指定显示器的多档编码流，必要时启动新的捕获线程（阻塞直到初始化完成）
+    pub(crate) fn subscribe(
+        &self,
+        monitor_index: u32,
+        codec: VideoCodec,
+    ) -> Result<SimulcastSubscription, String> {
+        let key = (monitor_index, codec);
+        loop {
+            let slot = self
+                .hubs
+                .lock()
+                .map_err(|e| e.to_string())?
+                .entry(key)
+                .or_default()
+                .clone();
+            let mut current = slot.lock().map_err(|e| e.to_string())?;
+
+            if let Some(hub) = current.as_ref() {
+                if let Some(receiver) = hub.subscribe() {
+                    return Ok(SimulcastSubscription::new(hub.clone(), receiver));
+                }
+            }
+
+            // 取到槽位后 hub 恰好停止并移出了映射表，重新取槽位，避免同一显示器启动两个 hub
+            if !self.is_registered(key, &slot)? {
+                continue;
+            }
+
+            let (hub, receiver) = SimulcastHub::start(
+                monitor_index,
+                codec,
+                self.metrics.clone(),
+                Arc::downgrade(&self.hubs),
+            )?;
+            *current = Some(hub.clone());
+            return Ok(SimulcastSubscription::new(hub, receiver));
+        }
+    }
+
+    fn is_registered(&self, key: HubKey, slot: &HubSlot) -> Result<bool, String> {
+        let hubs = self.hubs.lock().map_err(|e| e.to_string())?;
+        Ok(hubs
+            .get(&key)
+            .is_some_and(|registered| Arc::ptr_eq(registered, slot)))
+    }
+}
+
+/// 捕获线程退出后将已关闭的 hub 移出映射表；槽位正被其他订阅者使用时交由其重新启动
+fn unregister_hub(hubs: &HubMap, hub: &Arc<SimulcastHub>) {
+    let Ok(mut hubs) = hubs.lock() else {
+        return;
+    };
+    let key = (hub.monitor_index, hub.codec);
+    let Some(slot) = hubs.get(&key) else {
+        return;
+    };
+    let is_current = match slot.try_lock() {
+        Ok(current) => current
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, hub)),
+        Err(_) => false,
+    };
+    if is_current {
+        hubs.remove(&key);
+    }
+}
+
+pub(crate) struct SimulcastHub {
+    monitor_index: u32,
+    codec: VideoCodec,
+    source_width: u32,
+    source_height: u32,
+    layers: Vec<SimulcastLayer>,
+    /// 捕获线程存活期间为 Some；线程退出时取走，使所有订阅者收到 Closed
+    sender: Mutex<Option<broadcast::Sender<Arc<LayerFrame>>>>,
+    keyframe_requests: Vec<AtomicBool>,
+}
+
+impl SimulcastHub {
+    /// 启动捕获线程，返回 hub 及首个订阅（在线程开始检查订阅者之前创建）
+    fn start(
+        monitor_index: u32,
+        codec: VideoCodec,
+        metrics: Arc<TransportMetrics>,
+        registry: Weak<HubMap>,
+    ) -> Result<(Arc<Self>, broadcast::Receiver<Arc<LayerFrame>>), String> {
+        let (init_tx, init_rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let capturer = match DdaCapture::new(monitor_index) {
+                Ok(c) => c,
+                Err(e) => {
+                    let _ = init_tx.send(Err(format!("simulcast 初始化捕获失败: {}", e)));
+                    return;
+                }
+            };
+
+            let source_width = capturer.width();
+            let source_height = capturer.height();
+            let layers = resolve_layers(source_width, source_height);
+
+            let mut encoders = Vec::with_capacity(layers.len());
+            for layer in &layers {
+                let config = EncoderConfig {
+                    codec,
+                    width: layer.width,
+                    height: layer.height,
+                    fps: SIMULCAST_FPS,
+                    bitrate: layer.bitrate,
+                    keyframe_interval: SIMULCAST_KEYFRAME_INTERVAL_SECS,
+                };
+                match AmfEncoder::new_scaled(&config, source_width, source_height) {
+                    Ok(encoder) => encoders.push(encoder),
+                    Err(e) => {
+                        let _ = init_tx.send(Err(format!("simulcast 初始化编码器失败: {}", e)));
+                        return;
+                    }
+                }
+            }
+
+            let (sender, _) = broadcast::channel(SIMULCAST_CHANNEL_CAPACITY);
+            let hub = Arc::new(SimulcastHub {
+                monitor_index,
+                codec,
+                source_width,
+                source_height,
+                keyframe_requests: layers.iter().map(|_| AtomicBool::new(true)).collect(),
+                layers,
+                sender: Mutex::new(Some(sender.clone())),
+            });
+
+            if init_tx.send(Ok((hub.clone(), sender.subscribe()))).is_err() {
+                return;
+            }
+
+            if let Err(e) = hub.run_capture_loop(capturer, encoders, sender, &metrics) {
+                log::error!(
+                    "simulcast 捕获线程异常退出 (monitor {}): {}",
+                    monitor_index,
+                    e
+                );
+            }
+            hub.close();
+            if let Some(hubs) = registry.upgrade() {
+                unregister_hub(&hubs, &hub);
+            }
+        });
+
+        let (hub, receiver) = init_rx
+            .recv()
+            .map_err(|_| "simulcast 捕获线程启动失败".to_string())??;
+
+        log::info!(
+            "simulcast 共享捕获已启动: monitor {}, {}x{}, codec {}, 档位 {:?}",
+            hub.monitor_index,
+            hub.source_width,
+            hub.source_height,
+            hub.codec,
+            hub.layers
+                .iter()
+                .map(|l| format!("{}x{}@{}Mbps", l.width, l.height, l.bitrate / 1_000_000))
+                .collect::<Vec<_>>()
+        );
+        Ok((hub, receiver))
+    }
+
+    fn run_capture_loop(
+        &self,
+        mut capturer: DdaCapture,
+        mut encoders: Vec<AmfEncoder>,
+        sender: broadcast::Sender<Arc<LayerFrame>>,
+        metrics: &TransportMetrics,
+    ) -> Result<(), String> {
+        let frame_interval = frame_interval_for_fps(SIMULCAST_FPS);
+        let capture_timeout_ms = capture_timeout_ms_for_fps(SIMULCAST_FPS);
+
+        loop {
+            if !self.has_subscribers()? {
+                log::info!(
+                    "simulcast 共享捕获已停止 (monitor {}): 无订阅者",
+                    self.monitor_index
+                );
+                return Ok(());
+            }
+
+            let frame_start = Instant::now();
+
+            let frame_ready = capturer
+                .capture_frame(capture_timeout_ms)
+                .map_err(|e| e.to_string())?;
+            metrics.record_capture(frame_ready);
+            if !frame_ready {
+                pace_frame(frame_start, frame_interval);
+                continue;
+            }
+
+            let nv12_data = capturer.read_nv12().map_err(|e| e.to_string())?;
+
+            for (layer, encoder) in encoders.iter_mut().enumerate() {
+                let force_keyframe = self.keyframe_requests[layer].swap(false, Ordering::AcqRel);
+                let encoded_frames = encoder
+                    .encode(nv12_data, force_keyframe)
+                    .map_err(|e| e.to_string())?;
+
+                for frame in encoded_frames {
+                    metrics.record_encoded(frame.encode_time_us);
+                    // 没有订阅者时 send 返回 Err，下一轮循环会退出
+                    let _ = sender.send(Arc::new(LayerFrame { layer, frame }));
+                }
+            }
+
+            pace_frame(frame_start, frame_interval);
+        }
+    }
+
+    /// 检查是否仍有订阅者；没有时在锁内关闭，保证与 subscribe 不发生竞争
+    fn has_subscribers(&self) -> Result<bool, String> {
+        let mut sender = self.sender.lock().map_err(|e| e.to_string())?;
+        let alive = sender.as_ref().is_some_and(|s| s.receiver_count() > 0);
+        if !alive {
+            *sender = None;
+        }
+        Ok(alive)
+    }
+
+    fn subscribe(&self) -> Option<broadcast::Receiver<Arc<LayerFrame>>> {
+        self.sender
+            .lock()
+            .ok()
+            .and_then(|sender| sender.as_ref().map(|s| s.subscribe()))
+    }
+
+    fn close(&self) {
+        if let Ok(mut sender) = self.sender.lock() {
+            *sender = None;
+        }
+    }
+
+    fn request_keyframe(&self, layer: usize) {
+        if let Some(flag) = self.keyframe_requests.get(layer) {
+            flag.store(true, Ordering::Release);
+        }
+    }
+}
+
+/// 单个会话对共享多档编码流的订阅
+///
+/// 会话根据发送阻塞情况评估带宽，选择目标档位；实际切换发生在目标档位的下一个关键帧。
+pub(crate) struct SimulcastSubscription {
+    hub: Arc<SimulcastHub>,
+    receiver: broadcast::Receiver<Arc<LayerFrame>>,
+    /// 当前正在转发的档位（None 表示正在等待关键帧）
+    active_layer: Option<usize>,
+    target_layer: usize,
+    window_start: Instant,
+    window_bytes: u64,
+    window_busy: Duration,
+    stable_windows: u32,
+    /// 因落后被跳过的帧数，由会话取走计入指标
+    dropped_frames: u64,
+}
+
+impl SimulcastSubscription {
+    fn new(hub: Arc<SimulcastHub>, receiver: broadcast::Receiver<Arc<LayerFrame>>) -> Self {
+        let target_layer = 0;
+        hub.request_keyframe(target_layer);
+
+        Self {
+            hub,
+            receiver,
+            active_layer: None,
+            target_layer,
+            window_start: Instant::now(),
+            window_bytes: 0,
+            window_busy: Duration::ZERO,
+            stable_windows: 0,
+            dropped_frames: 0,
+        }
+    }
+
+    pub fn codec(&self) -> VideoCodec {
+        self.hub.codec
+    }
+
+    pub fn width(&self) -> u32 {
+        self.hub.source_width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.hub.source_height
+    }
+
+    pub fn active_layer(&self) -> Option<(usize, SimulcastLayer)> {
+        self.active_layer
+            .map(|index| (index, self.hub.layers[index]))
+    }
+
+    pub fn request_keyframe(&self) {
+        self.hub.request_keyframe(self.target_layer);
+    }
+
+    /// 等待并取出当前档位的帧；遇到目标档位关键帧时完成切换
+    pub fn recv_frames(
+        &mut self,
+        runtime: &tokio::runtime::Handle,
+        timeout: Duration,
+    ) -> Result<Vec<Arc<LayerFrame>>, String> {
+        let mut frames = Vec::new();
+
+        let first = runtime.block_on(tokio::time::timeout(timeout, self.receiver.recv()));
+        match first {
+            Err(_) => return Ok(frames),
+            Ok(Ok(frame)) => self.accept_frame(frame, &mut frames),
+            Ok(Err(broadcast::error::RecvError::Lagged(skipped))) => self.on_lagged(skipped),
+            Ok(Err(broadcast::error::RecvError::Closed)) => {
+                return Err("simulcast 共享捕获已停止".to_string());
+            }
+        }
+
+        loop {
+            match self.receiver.try_recv() {
+                Ok(frame) => self.accept_frame(frame, &mut frames),
+                Err(broadcast::error::TryRecvError::Empty) => return Ok(frames),
+                Err(broadcast::error::TryRecvError::Lagged(skipped)) => self.on_lagged(skipped),
+                Err(broadcast::error::TryRecvError::Closed) => {
+                    return Err("simulcast 共享捕获已停止".to_string());
+                }
+            }
+        }
+    }
+
+    pub fn take_dropped_frames(&mut self) -> u64 {
+        std::mem::take(&mut self.dropped_frames)
+    }
+
+    /// 记录一次发送的字节数与阻塞时长，窗口结束时重新评估目标档位
+    pub fn record_send(&mut self, bytes: usize, busy: Duration) {
+        self.window_bytes += bytes as u64;
+        self.window_busy += busy;
+
+        let elapsed = self.window_start.elapsed();
+        if elapsed < BANDWIDTH_WINDOW {
+            return;
+        }
+
+        let elapsed_secs = elapsed.as_secs_f64();
+        let busy_ratio = self.window_busy.as_secs_f64() / elapsed_secs;
+        let sent_bps = self.window_bytes as f64 * 8.0 / elapsed_secs;
+        // 发送几乎不阻塞时无法测出上限，视为容量充足
+        let capacity_bps = if busy_ratio > 0.0 {
+            sent_bps / busy_ratio
+        } else {
+            f64::INFINITY
+        };
+
+        self.window_start = Instant::now();
+        self.window_bytes = 0;
+        self.window_busy = Duration::ZERO;
+
+        if let Some(layer) = choose_target_layer(
+            &self.hub.layers,
+            self.target_layer,
+            busy_ratio,
+            capacity_bps,
+            &mut self.stable_windows,
+        ) {
+            self.set_target_layer(layer, capacity_bps);
+        }
+    }
+
+    fn set_target_layer(&mut self, layer: usize, capacity_bps: f64) {
+        let spec = self.hub.layers[layer];
+        log::info!(
+            "simulcast 目标档位 {} → {} ({}x{} @{}Mbps, 估计容量 {:.1}Mbps)",
+            self.target_layer,
+            layer,
+            spec.width,
+            spec.height,
+            spec.bitrate / 1_000_000,
+            capacity_bps / 1_000_000.0
+        );
+        self.target_layer = layer;
+        self.hub.request_keyframe(layer);
+    }
+
+    fn accept_frame(&mut self, frame: Arc<LayerFrame>, frames: &mut Vec<Arc<LayerFrame>>) {
+        if frame.layer == self.target_layer
+            && frame.frame.is_keyframe
+            && self.active_layer != Some(self.target_layer)
+        {
+            self.active_layer = Some(self.target_layer);
+        }
+
+        if self.active_layer == Some(frame.layer) {
+            frames.push(frame);
+        }
+    }
+
+    fn on_lagged(&mut self, skipped: u64) {
+        log::debug!("simulcast 订阅者落后，丢弃 {} 帧，等待关键帧", skipped);
+        self.dropped_frames += skipped;
+        self.active_layer = None;
+        self.hub.request_keyframe(self.target_layer);
+    }
+}
+
+/// 按一个评估窗口的发送情况选择新的目标档位，保持不变时返回 `None`
+fn choose_target_layer(
+    layers: &[SimulcastLayer],
+    current: usize,
+    busy_ratio: f64,
+    capacity_bps: f64,
+    stable_windows: &mut u32,
+) -> Option<usize> {
+    let lowest_layer = layers.len() - 1;
+    if busy_ratio > CONGESTED_BUSY_RATIO {
+        *stable_windows = 0;
+        return (current < lowest_layer).then_some(current + 1);
+    }
+
+    if busy_ratio < IDLE_BUSY_RATIO && current > 0 {
+        let next_bitrate = layers[current - 1].bitrate as f64;
+        if capacity_bps >= next_bitrate * UPGRADE_HEADROOM {
+            *stable_windows += 1;
+            if *stable_windows >= UPGRADE_STABLE_WINDOWS {
+                *stable_windows = 0;
+                return Some(current - 1);
+            }
+            return None;
+        }
+    }
+
+    *stable_windows = 0;
+    None
+}
+
+fn resolve_layers(source_width: u32, source_height: u32) -> Vec<SimulcastLayer> {
+    SIMULCAST_LAYER_SPECS
+        .iter()
+        .map(|spec| SimulcastLayer {
+            width: scaled_dimension(source_width, spec),
+            height: scaled_dimension(source_height, spec),
+            bitrate: spec.bitrate,
+        })
+        .collect()
+}
+
+/// NV12 要求宽高为偶数
+fn scaled_dimension(value: u32, spec: &SimulcastLayerSpec) -> u32 {
+    ((value as u64 * spec.scale_num as u64 / spec.scale_den as u64) as u32 & !1).max(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_layers_from_source_resolution() {
+        let layers = resolve_layers(1920, 1080);
+        let sizes: Vec<_> = layers.iter().map(|l| (l.width, l.height)).collect();
+        assert_eq!(sizes, vec![(1920, 1080), (1280, 720), (960, 540)]);
+        assert!(
+            layers
+                .windows(2)
+                .all(|pair| pair[0].bitrate > pair[1].bitrate)
+        );
+    }
+
+    #[test]
+    fn scaled_dimensions_are_even_and_non_zero() {
+        let layers = resolve_layers(1366, 769);
+        for layer in &layers {
+            assert_eq!(layer.width % 2, 0);
+            assert_eq!(layer.height % 2, 0);
+        }
+        assert_eq!((layers[1].width, layers[1].height), (910, 512));
+
+        let tiny = resolve_layers(1, 1);
+        assert!(tiny.iter().all(|l| l.width == 2 && l.height == 2));
+    }
+
+    #[test]
+    fn congestion_steps_down_one_layer() {
+        let layers = resolve_layers(1920, 1080);
+        let mut stable = 1;
+        assert_eq!(
+            choose_target_layer(&layers, 0, 0.8, 10_000_000.0, &mut stable),
+            Some(1)
+        );
+        assert_eq!(stable, 0);
+
+        // 已是最低档时保持不变
+        assert_eq!(
+            choose_target_layer(&layers, 2, 0.9, 1_000_000.0, &mut stable),
+            None
+        );
+    }
+
+    #[test]
+    fn upgrade_requires_headroom_and_stable_windows() {
+        let layers = resolve_layers(1920, 1080);
+        let enough = layers[1].bitrate as f64 * UPGRADE_HEADROOM;
+        let mut stable = 0;
+
+        for _ in 1..UPGRADE_STABLE_WINDOWS {
+            assert_eq!(
+                choose_target_layer(&layers, 2, 0.05, enough, &mut stable),
+                None
+            );
+        }
+        assert_eq!(
+            choose_target_layer(&layers, 2, 0.05, enough, &mut stable),
+            Some(1)
+        );
+        assert_eq!(stable, 0);
+
+        // 容量不足时重新计数
+        stable = 1;
+        assert_eq!(
+            choose_target_layer(&layers, 1, 0.05, enough, &mut stable),
+            None
+        );
+        assert_eq!(stable, 0);
+    }
+
+    #[test]
+    fn moderate_load_keeps_layer() {
+        let layers = resolve_layers(1920, 1080);
+        let mut stable = 1;
+        assert_eq!(
+            choose_target_layer(&layers, 1, 0.3, f64::INFINITY, &mut stable),
+            None
+        );
+        assert_eq!(stable, 0);
+        assert_eq!(
+            choose_target_layer(&layers, 0, 0.01, f64::INFINITY, &mut stable),
+            None
+        );
+    }
+}
@@ -1,5 +1,5 @@
-use crate::capture::dda::MonitorInfo;
-use crate::transport::session::{TransportIo, run_client_service};
+use crate::control::audit::AuditLog;
+use crate::transport::session::{SessionContext, SessionGrant, TransportIo, run_client_service};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc;
@@ -50,19 +50,23 @@ impl TransportIo for WebRtcIo {
 }
 
 pub struct WebRtcServer {
-    monitor_list_json: Arc<Vec<u8>>,
-    monitors: Arc<Vec<MonitorInfo>>,
+    context: Arc<SessionContext>,
 }
 
 impl WebRtcServer {
-    pub fn new(monitor_list_json: Arc<Vec<u8>>, monitors: Arc<Vec<MonitorInfo>>) -> Self {
-        Self {
-            monitor_list_json,
-            monitors,
-        }
+    pub(crate) fn new(context: Arc<SessionContext>) -> Self {
+        Self { context }
+    }
+
+    pub(crate) fn audit(&self) -> &AuditLog {
+        &self.context.audit
     }
 
-    pub async fn handle_offer(&self, offer_string: String) -> Result<String, String> {
+    pub(crate) async fn handle_offer(
+        &self,
+        offer_string: String,
+        grant: SessionGrant,
+    ) -> Result<String, String> {
         let api = APIBuilder::new().build();
         let config = RTCConfiguration::default();
         let peer_connection = Arc::new(
@@ -83,17 +87,16 @@ impl WebRtcServer {
             },
         ));
 
-        let monitor_list_json = self.monitor_list_json.clone();
-        let monitors = self.monitors.clone();
+        let context = self.context.clone();
         let runtime = tokio::runtime::Handle::current();
 
         peer_connection.on_data_channel(Box::new(move |d: Arc<RTCDataChannel>| {
             log::info!("WebRTC DataChannel 已捕获: {}", d.label());
             let d_clone = Arc::clone(&d);
 
-            let monitor_list_json = monitor_list_json.clone();
-            let monitors = monitors.clone();
+            let context = context.clone();
             let runtime = runtime.clone();
+            let grant = grant.clone();
 
             Box::pin(async move {
                 let (io_tx, io_rx) = mpsc::channel(256); // from client to server (received events)
@@ -144,11 +147,10 @@ impl WebRtcServer {
                     };
 
                     let rt = runtime.clone();
-                    let ml = monitor_list_json.clone();
-                    let m = monitors.clone();
+                    let ctx = context.clone();
 
                     tokio::task::spawn_blocking(move || {
-                        if let Err(e) = run_client_service(rt, io, ml, m, "WebRTC") {
+                        if let Err(e) = run_client_service(rt, io, ctx, grant, "WebRTC") {
                             log::warn!("WebRTC 客户端服务线程异常: {}", e);
                         }
                     });
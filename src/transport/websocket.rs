@@ -1,48 +1,85 @@
-use super::session::{TransportIo, run_client_service};
-use crate::capture::dda::MonitorInfo;
-use axum::extract::State;
+use super::session::{SessionContext, SessionGrant, TransportIo, run_client_service};
+use crate::control::audit::AuditEvent;
+use crate::control::role::SessionRole;
+use crate::server::pairing::PairedClient;
+use axum::Extension;
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
-use axum::response::IntoResponse;
+use axum::extract::{ConnectInfo, Query, State};
+use axum::response::{IntoResponse, Response};
 use futures_util::StreamExt;
+use serde::Deserialize;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
 /// WebSocket 串流服务器
 pub struct WebSocketServer {
-    /// 缓存的显示器列表 JSON 数据
-    monitor_list_json: Arc<Vec<u8>>,
-    /// 显示器元数据（用于输入坐标映射）
-    monitors: Arc<Vec<MonitorInfo>>,
+    /// 各传输共享的会话依赖
+    context: Arc<SessionContext>,
+}
+
+/// WebSocket 连接参数（URL query）
+#[derive(Debug, Deserialize)]
+pub struct WebSocketConnectParams {
+    /// 客户端请求的角色（不得高于授予角色）
+    #[serde(default)]
+    role: Option<String>,
+    /// 登录后获得的会话令牌（浏览器 WebSocket 无法设置请求头）
+    #[serde(default)]
+    token: Option<String>,
 }
 
 impl WebSocketServer {
-    pub fn new(monitor_list_json: Arc<Vec<u8>>, monitors: Arc<Vec<MonitorInfo>>) -> Self {
-        Self {
-            monitor_list_json,
-            monitors,
-        }
+    pub(crate) fn new(context: Arc<SessionContext>) -> Self {
+        Self { context }
     }
 
     pub async fn websocket_upgrade(
         State(server): State<Arc<WebSocketServer>>,
+        ConnectInfo(peer_addr): ConnectInfo<SocketAddr>,
+        paired: Option<Extension<PairedClient>>,
+        Query(params): Query<WebSocketConnectParams>,
         ws: WebSocketUpgrade,
-    ) -> impl IntoResponse {
+    ) -> Response {
+        let access = match server.context.auth.authorize(
+            peer_addr.ip(),
+            params.token.as_deref(),
+            paired.as_ref().map(|Extension(device)| device),
+        ) {
+            Ok(access) => access,
+            Err(e) => {
+                log::warn!("拒绝 WebSocket 连接 {}: {}", peer_addr, e);
+                server.context.audit.record(AuditEvent::AccessDenied {
+                    transport: "WebSocket",
+                    peer_addr,
+                    reason: e.to_string(),
+                });
+                return (e.status_code(), e.to_string()).into_response();
+            }
+        };
+        let grant = SessionGrant {
+            peer_addr,
+            role: SessionRole::negotiate(access.role, params.role.as_deref()),
+            max_role: access.role,
+            share: access.share,
+            identity: access.identity,
+        };
         ws.on_upgrade(move |socket| async move {
-            if let Err(e) = server.handle_client(socket).await {
+            if let Err(e) = server.handle_client(socket, grant).await {
                 log::warn!("WebSocket 客户端断开: {}", e);
             }
         })
+        .into_response()
     }
 
     /// 为单个客户端启动独立服务（捕获 + 编码 + 发送 + 控制）
-    async fn handle_client(&self, socket: WebSocket) -> Result<(), String> {
-        let monitor_list_json = self.monitor_list_json.clone();
-        let monitors = self.monitors.clone();
+    async fn handle_client(&self, socket: WebSocket, grant: SessionGrant) -> Result<(), String> {
+        let context = self.context.clone();
         let runtime = tokio::runtime::Handle::current();
         let io = WebSocketIo::new(socket);
 
         let task = tokio::task::spawn_blocking(move || {
-            run_client_service(runtime, io, monitor_list_json, monitors, "WebSocket")
+            run_client_service(runtime, io, context, grant, "WebSocket")
         });
 
         match task.await {
@@ -1,8 +1,17 @@
-use super::session::{TransportIo, run_client_service};
-use crate::capture::dda::MonitorInfo;
+use super::session::{SessionContext, SessionGrant, TransportIo, run_client_service};
+use crate::control::audit::AuditEvent;
+use crate::control::role::SessionRole;
+use crate::server::access::{AccessDenied, AccessPolicy};
+use crate::server::health::{EndpointStatus, Health};
+use crate::server::shutdown::Shutdown;
+use crate::server::tls::{EphemeralCert, WebTransportCerts};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use wtransport::config::Ipv6DualStackConfig;
 use wtransport::endpoint::IncomingSession;
+use wtransport::endpoint::endpoint_side::Server;
+use wtransport::tls::{Certificate, CertificateChain, PrivateKey};
 use wtransport::{Connection, Endpoint, Identity, RecvStream, SendStream, ServerConfig};
 
 /// WebTransport 读取块大小
@@ -125,55 +134,92 @@ impl TransportIo for WebTransportIo {
 
 /// WebTransport 串流服务器（QUIC/HTTP3）
 pub struct WebTransportServer {
-    /// 缓存的显示器列表 JSON 数据
-    monitor_list_json: Arc<Vec<u8>>,
-    /// 显示器元数据（用于输入坐标映射）
-    monitors: Arc<Vec<MonitorInfo>>,
+    /// 各传输共享的会话依赖
+    context: Arc<SessionContext>,
+    /// 与 HTTP 路由一致的路径前缀
+    base_path: String,
+    access: Arc<AccessPolicy>,
+    shutdown: Arc<Shutdown>,
 }
 
 impl WebTransportServer {
-    pub fn new(monitor_list_json: Arc<Vec<u8>>, monitors: Arc<Vec<MonitorInfo>>) -> Self {
+    pub(crate) fn new(
+        context: Arc<SessionContext>,
+        base_path: String,
+        access: Arc<AccessPolicy>,
+        shutdown: Arc<Shutdown>,
+    ) -> Self {
         Self {
-            monitor_list_json,
-            monitors,
+            context,
+            base_path,
+            access,
+            shutdown,
         }
     }
 
-    pub fn spawn(self: Arc<Self>, port: u16) {
+    /// 在 TCP 监听端点的同一地址与端口上启动 WebTransport（UDP/QUIC）服务
+    pub fn spawn(
+        self: Arc<Self>,
+        addr: SocketAddr,
+        dual_stack: bool,
+        certs: Arc<WebTransportCerts>,
+        health: Arc<Health>,
+    ) {
+        health.set_webtransport_status(addr, EndpointStatus::Starting);
         tokio::spawn(async move {
-            if let Err(e) = self.run(port).await {
+            if let Err(e) = self.run(addr, dual_stack, &certs, &health).await {
                 log::warn!("WebTransport 服务不可用，将仅使用 WebSocket: {}", e);
+                health.set_webtransport_status(addr, EndpointStatus::Failed { error: e });
             }
         });
     }
 
-    async fn run(self: Arc<Self>, port: u16) -> Result<(), String> {
-        let identity = Identity::load_pemfiles("cert.pem", "key.pem")
-            .await
-            .map_err(|e| format!("加载 WebTransport TLS 证书失败: {}", e))?;
-
-        let config = ServerConfig::builder()
-            .with_bind_default(port)
-            .with_identity(identity)
-            .keep_alive_interval(Some(Duration::from_secs(3)))
-            .build();
-
-        let endpoint = Endpoint::server(config).map_err(|e| e.to_string())?;
+    async fn run(
+        self: Arc<Self>,
+        addr: SocketAddr,
+        dual_stack: bool,
+        certs: &WebTransportCerts,
+        health: &Health,
+    ) -> Result<(), String> {
+        let mut rotations = certs.subscribe();
+        let current = certs
+            .current()
+            .ok_or_else(|| "WebTransport 证书不可用".to_string())?;
+        let endpoint = Endpoint::server(server_config(addr, dual_stack, &current)?)
+            .map_err(|e| e.to_string())?;
+        health.set_webtransport_status(addr, EndpointStatus::Listening);
         log::info!(
-            "WebTransport 服务器监听: https://localhost:{}/webtransport (UDP/QUIC)",
-            port
+            "WebTransport 服务器监听: https://{}/webtransport (UDP/QUIC{})",
+            addr,
+            if dual_stack { ", 双栈" } else { "" }
         );
 
         loop {
-            let incoming_session = endpoint.accept().await;
-            let server = Arc::clone(&self);
-
-            tokio::spawn(async move {
-                if let Err(e) = server.handle_incoming_session(incoming_session).await {
-                    log::warn!("WebTransport 客户端断开: {}", e);
+            tokio::select! {
+                incoming_session = endpoint.accept() => {
+                    let server = Arc::clone(&self);
+
+                    tokio::spawn(async move {
+                        if let Err(e) = server.handle_incoming_session(incoming_session).await {
+                            log::warn!("WebTransport 客户端断开: {}", e);
+                        }
+                    });
                 }
-            });
+                Ok(()) = rotations.changed() => {
+                    if let Err(e) = reload_certificate(&endpoint, addr, dual_stack, certs) {
+                        log::warn!("WebTransport {} 加载新证书失败，继续使用当前证书: {}", addr, e);
+                    }
+                }
+                _ = self.shutdown.triggered() => {
+                    log::info!("WebTransport {} 已停止接受新会话", addr);
+                    break;
+                }
+            }
         }
+
+        // 不关闭端点：已建立的会话仍可收到结束提示，待会话结束、连接随之关闭后再释放端点
+        endpoint.wait_idle().await;
+        Ok(())
     }
 
     async fn handle_incoming_session(
@@ -181,13 +227,51 @@ impl WebTransportServer {
         incoming_session: IncomingSession,
     ) -> Result<(), String> {
         let session_request = incoming_session.await.map_err(|e| e.to_string())?;
+        let peer_addr = session_request.remote_address();
+        // 会话名额在会话线程中占用，这里只检查地址规则
+        if !self.access.is_allowed(peer_addr.ip()) {
+            session_request.forbidden().await;
+            return Err(format!(
+                "拒绝 WebTransport 会话 {}: {}",
+                peer_addr,
+                AccessDenied::Blocked
+            ));
+        }
         let authority = session_request.authority().to_owned();
-        let path = session_request.path().to_owned();
+        let (full_path, query) = split_path_query(session_request.path());
+        let (full_path, query) = (full_path.to_owned(), query.map(str::to_owned));
 
+        let path = full_path
+            .strip_prefix(self.base_path.as_str())
+            .map(|rest| if rest.is_empty() { "/" } else { rest })
+            .unwrap_or_default();
         if path != "/" && path != "/webtransport" && !path.starts_with("/webtransport/") {
-            return Err(format!("不支持的 WebTransport 路径: {}", path));
+            return Err(format!("不支持的 WebTransport 路径: {}", full_path));
         }
 
+        let token = query.as_deref().and_then(|q| query_param(q, "token"));
+        let access = match self.context.auth.authorize(peer_addr.ip(), token, None) {
+            Ok(access) => access,
+            Err(e) => {
+                self.context.audit.record(AuditEvent::AccessDenied {
+                    transport: "WebTransport",
+                    peer_addr,
+                    reason: e.to_string(),
+                });
+                session_request.forbidden().await;
+                return Err(format!("拒绝 WebTransport 会话 {}: {}", peer_addr, e));
+            }
+        };
+
+        let requested_role = query.as_deref().and_then(|q| query_param(q, "role"));
+        let grant = SessionGrant {
+            peer_addr,
+            role: SessionRole::negotiate(access.role, requested_role),
+            max_role: access.role,
+            share: access.share,
+            identity: access.identity,
+        };
+
         let connection = session_request.accept().await.map_err(|e| e.to_string())?;
         log::info!(
             "WebTransport 会话已建立: authority='{}', path='{}'",
@@ -195,13 +279,16 @@ impl WebTransportServer {
             path
         );
 
-        self.handle_client(connection).await
+        self.handle_client(connection, grant).await
     }
 
     /// 为单个客户端启动独立服务（捕获 + 编码 + 发送 + 控制）
-    async fn handle_client(&self, connection: Connection) -> Result<(), String> {
-        let monitor_list_json = self.monitor_list_json.clone();
-        let monitors = self.monitors.clone();
+    async fn handle_client(
+        &self,
+        connection: Connection,
+        grant: SessionGrant,
+    ) -> Result<(), String> {
+        let context = self.context.clone();
         let runtime = tokio::runtime::Handle::current();
 
         let (send_stream, recv_stream) = connection
@@ -212,7 +299,7 @@ impl WebTransportServer {
         let io = WebTransportIo::new(send_stream, recv_stream);
 
         let task = tokio::task::spawn_blocking(move || {
-            run_client_service(runtime, io, monitor_list_json, monitors, "WebTransport")
+            run_client_service(runtime, io, context, grant, "WebTransport")
         });
 
         match task.await {
@@ -221,3 +308,67 @@ impl WebTransportServer {
         }
     }
 }
+
+fn server_config(
+    addr: SocketAddr,
+    dual_stack: bool,
+    cert: &EphemeralCert,
+) -> Result<ServerConfig, String> {
+    let certificate = Certificate::from_der(cert.cert_der.clone())
+        .map_err(|e| format!("加载 WebTransport TLS 证书失败: {}", e))?;
+    let identity = Identity::new(
+        CertificateChain::single(certificate),
+        PrivateKey::from_der_pkcs8(cert.key_der.clone()),
+    );
+
+    let builder = ServerConfig::builder();
+    let builder = match addr {
+        // 与 TCP 监听保持一致，显式指定是否双栈
+        SocketAddr::V6(v6_addr) => builder.with_bind_address_v6(
+            v6_addr,
+            if dual_stack {
+                Ipv6DualStackConfig::Allow
+            } else {
+                Ipv6DualStackConfig::Deny
+            },
+        ),
+        SocketAddr::V4(_) => builder.with_bind_address(addr),
+    };
+
+    Ok(builder
+        .with_identity(identity)
+        .keep_alive_interval(Some(Duration::from_secs(3)))
+        .build())
+}
+
+/// 证书轮换后重新加载；已建立的会话不受影响，只有新握手使用新证书
+fn reload_certificate(
+    endpoint: &Endpoint<Server>,
+    addr: SocketAddr,
+    dual_stack: bool,
+    certs: &WebTransportCerts,
+) -> Result<(), String> {
+    let current = certs
+        .current()
+        .ok_or_else(|| "WebTransport 证书不可用".to_string())?;
+    endpoint
+        .reload_config(server_config(addr, dual_stack, &current)?, false)
+        .map_err(|e| e.to_string())
+}
+
+/// 拆分请求路径与 query 部分（WebTransport 的 :path 含 query）
+fn split_path_query(raw: &str) -> (&str, Option<&str>) {
+    match raw.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (raw, None),
+    }
+}
+
+/// 读取 query 中的单个参数（仅处理 key=value，不做百分号解码）
+fn query_param<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v)
+}
@@ -1,11 +1,35 @@
 import { reactive } from 'vue'
 
 const HEADER_SIZE = 16
-const BASE_CONTROL_HINT = 'Moonlight 快捷键: Ctrl+Alt+Shift+Z 接管/释放 · S 统计 · X 全屏 · M 显示器 · E 编码 · Q 断开/重连'
+const BASE_CONTROL_HINT = 'Moonlight 快捷键: Ctrl+Alt+Shift+Z 接管/释放 · S 统计 · X 全屏 · M 显示器 · E 编码 · V 粘贴 · R 相对鼠标 · Q 断开/重连'
 const RECONNECT_DELAY_MS = 3000
-const WEBTRANSPORT_PATH = '/webtransport'
-const WEBTRANSPORT_HASH_PATH = '/webtransport/hash'
+// 页面所在目录即服务端的路径前缀（反向代理部署时可能挂载在子路径下）
+const BASE_PATH = location.pathname.replace(/\/[^/]*$/, '')
+const WEBTRANSPORT_PATH = `${BASE_PATH}/webtransport`
+const WEBTRANSPORT_HASH_PATH = `${BASE_PATH}/webtransport/hash`
+const WEBSOCKET_PATH = `${BASE_PATH}/ws`
 const MAX_WEBTRANSPORT_PACKET_SIZE = 64 * 1024 * 1024
+// 按文字键入粘贴时每条消息的字符数（服务端单条上限 1024）与总字符上限
+const PASTE_CHUNK_CHARS = 256
+const MAX_PASTE_CHARS = 16384
+// 输入法处理中的按键，keyCode 固定为 229
+const IME_PROCESS_KEY_CODE = 229
+// 服务端同时注入的触控点上限，触控点 id 从 0 开始分配
+const MAX_TOUCH_CONTACTS = 10
+// PointerEvent.buttons 中的笔尖、笔杆按钮与橡皮擦位
+const PEN_BUTTON_TIP = 1
+const PEN_BUTTON_BARREL = 2
+const PEN_BUTTON_ERASER = 32
+const MODIFIER_CODES = new Set([
+  'ControlLeft',
+  'ControlRight',
+  'ShiftLeft',
+  'ShiftRight',
+  'AltLeft',
+  'AltRight',
+  'MetaLeft',
+  'MetaRight',
+])
 
 const CODEC_PRESETS = Object.freeze([
   {
@@ -35,6 +59,7 @@ const ENCODING_DEFAULTS = Object.freeze({
   fps: 60,
   bitrateMbps: 20,
   keyframeInterval: 2,
+  simulcast: false,
 })
 
 const HIGH_FPS_HINT_THRESHOLD = 72
@@ -53,16 +78,41 @@ const FRAME_TYPE = {
   MOUSE_INPUT: 0x06,
   KEYBOARD_INPUT: 0x07,
   ENCODING_SETTINGS: 0x08,
+  SESSION_ROLE: 0x09,
+  NOTICE: 0x0A,
+  FLOOR_CONTROL: 0x0B,
+  INPUT_RELEASE: 0x0C,
+  TEXT_INPUT: 0x0D,
+  LOCK_KEYS: 0x0E,
+  MOUSE_MODE: 0x0F,
+  PING: 0x10,
+  PONG: 0x11,
+  TOUCH_INPUT: 0x12,
+  PEN_INPUT: 0x13,
 }
 
 const FRAME_FLAGS = {
   KEYFRAME: 0x01,
 }
 
-const WEBRTC_OFFER_PATH = '/webrtc/offer'
+const WEBRTC_OFFER_PATH = `${BASE_PATH}/webrtc/offer`
+const AUTH_STATUS_PATH = `${BASE_PATH}/api/auth/status`
+const AUTH_LOGIN_PATH = `${BASE_PATH}/api/auth/login`
+const AUTH_TOKEN_STORAGE_KEY = 'webdisplay.authToken'
 
 const PLAYER_GLOBAL_KEY = '__webdisplayPlayer'
 
+// 服务端主动结束会话的提示类型，收到后不再自动重连
+const TERMINAL_NOTICE_KINDS = Object.freeze([
+  'access_denied',
+  'consent_rejected',
+  'disconnected',
+  'share_expired',
+])
+
+// 透传给服务端的页面 URL 参数（如 ?role=viewer）
+const CONNECT_PARAM_KEYS = Object.freeze(['role'])
+
 export const createUiState = () =>
   reactive({
     availableCodecs: [],
@@ -79,6 +129,14 @@ export const createUiState = () =>
     controlHintVisible: true,
     controlHintText: BASE_CONTROL_HINT,
     showLocalCursor: false,
+    sessionId: null,
+    sessionRole: null,
+    sessions: [],
+    floorMode: null,
+    floorHolder: null,
+    loginVisible: false,
+    loginPending: false,
+    loginError: '',
     stats: {
       latency: '--',
       fps: '--',
@@ -131,18 +189,27 @@ export class UltraLowLatencyPlayer {
       bitrateMbps: 0,
     }
 
+    this.sessionRole = null
+    this.sessionEndedReason = null
     this.controlActive = false
     this.pressedKeys = new Map()
     this.pressedButtons = new Set()
     this.lastPointerPos = { x: 0.5, y: 0.5 }
     this.pendingMouseMoveEvent = null
+    this.pendingRelativeMove = null
     this.mouseMoveScheduled = false
+    this.relativeMouse = false
     this.showLocalCursor = false
     this.lockPointerToVideo = false
+    this.imeInput = null
+    this.pendingPasteText = null
+    this.touchSlots = new Map()
 
     this.textEncoder = new TextEncoder()
     this.textDecoder = new TextDecoder()
     this.canvas.tabIndex = 0
+    // 触控与笔交给远端处理，不触发浏览器的滚动与缩放手势
+    this.canvas.style.touchAction = 'none'
 
     this.supportedCodecConfigs = new Map()
     this.activeDecoderCodecId = null
@@ -207,6 +274,11 @@ export class UltraLowLatencyPlayer {
 
     this._deactivateControl()
 
+    if (this.imeInput) {
+      this.imeInput.remove()
+      this.imeInput = null
+    }
+
     const scopedWindow = window
     if (scopedWindow[PLAYER_GLOBAL_KEY] === this) {
       delete scopedWindow[PLAYER_GLOBAL_KEY]
@@ -330,9 +402,10 @@ export class UltraLowLatencyPlayer {
       this._setConnectionState({
         visible: true,
         connected: false,
-        text: '会话已退出，按 Ctrl+Alt+Shift+Q 重连',
-        detail: '',
+        text: this.sessionEndedReason || '会话已退出，按 Ctrl+Alt+Shift+Q 重连',
+        detail: this.sessionEndedReason ? '按 Ctrl+Alt+Shift+Q 重连' : '',
       })
+      this.sessionEndedReason = null
     }
   }
 
@@ -519,6 +592,10 @@ export class UltraLowLatencyPlayer {
       detail: '',
     })
 
+    if (!(await this._ensureAuthenticated())) {
+      return
+    }
+
     const canUseWebRtc = typeof RTCPeerConnection !== 'undefined'
     if (canUseWebRtc) {
       const webrtcConnected = await this._connectWebRTC()
@@ -552,8 +629,118 @@ export class UltraLowLatencyPlayer {
     this._connectWebSocket()
   }
 
+  _connectParams() {
+    const pageParams = new URLSearchParams(location.search)
+    const params = {}
+    for (const key of CONNECT_PARAM_KEYS) {
+      const value = pageParams.get(key)
+      if (value) {
+        params[key] = value
+      }
+    }
+
+    // 分享链接令牌优先于登录令牌
+    const token = this._shareToken() || this._authToken()
+    if (token) {
+      params.token = token
+    }
+    return params
+  }
+
+  _shareToken() {
+    return new URLSearchParams(location.search).get('share')
+  }
+
+  _authToken() {
+    try {
+      return sessionStorage.getItem(AUTH_TOKEN_STORAGE_KEY)
+    } catch (_) {
+      return null
+    }
+  }
+
+  _storeAuthToken(token) {
+    try {
+      if (token) {
+        sessionStorage.setItem(AUTH_TOKEN_STORAGE_KEY, token)
+      } else {
+        sessionStorage.removeItem(AUTH_TOKEN_STORAGE_KEY)
+      }
+    } catch (_) {
+    }
+  }
+
+  // 服务端启用认证且本地令牌无效时显示登录框，返回是否可以继续连接
+  async _ensureAuthenticated() {
+    if (this._shareToken()) {
+      return true
+    }
+
+    const token = this._authToken()
+    let status = null
+    try {
+      const response = await fetch(AUTH_STATUS_PATH, {
+        method: 'GET',
+        cache: 'no-store',
+        headers: token ? { Authorization: `Bearer ${token}` } : {},
+      })
+      status = response.ok ? await response.json() : null
+    } catch (_) {
+      status = null
+    }
+
+    if (!status || !status.required || status.authenticated) {
+      this.ui.loginVisible = false
+      return true
+    }
+
+    this._storeAuthToken(null)
+    this.ui.loginVisible = true
+    this._setConnectionState({
+      visible: true,
+      connected: false,
+      text: '需要登录',
+      detail: '',
+    })
+    return false
+  }
+
+  async login(secret) {
+    if (!secret || this.ui.loginPending) {
+      return
+    }
+
+    this.ui.loginPending = true
+    this.ui.loginError = ''
+    try {
+      const response = await fetch(AUTH_LOGIN_PATH, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ secret }),
+      })
+      if (!response.ok) {
+        this.ui.loginError = response.status === 429 ? '失败次数过多，请稍后再试' : '凭据无效'
+        return
+      }
+
+      const payload = await response.json()
+      this._storeAuthToken(payload.token)
+      this.ui.loginVisible = false
+      void this._connect()
+    } catch (_) {
+      this.ui.loginError = '登录请求失败'
+    } finally {
+      this.ui.loginPending = false
+    }
+  }
+
+  _connectQueryString() {
+    const query = new URLSearchParams(this._connectParams()).toString()
+    return query ? `?${query}` : ''
+  }
+
   async _connectWebTransport() {
-    const wtUrl = `https://${location.host}${WEBTRANSPORT_PATH}`
+    const wtUrl = `https://${location.host}${WEBTRANSPORT_PATH}${this._connectQueryString()}`
     console.log('尝试 WebTransport 连接:', wtUrl)
 
     const withTimeout = (promise, timeoutMs, timeoutMsg) =>
@@ -566,13 +753,13 @@ export class UltraLowLatencyPlayer {
 
     let transport = null
     try {
-      const certHash = await this._fetchWebTransportServerCertificateHash()
+      const certHashes = await this._fetchWebTransportServerCertificateHashes()
       const transportOptions = {
         allowPooling: false,
       }
 
-      if (certHash) {
-        transportOptions.serverCertificateHashes = [certHash]
+      if (certHashes.length > 0) {
+        transportOptions.serverCertificateHashes = certHashes
       }
 
       transport = new WebTransport(wtUrl, transportOptions)
@@ -613,6 +800,7 @@ export class UltraLowLatencyPlayer {
         detail: '传输: WebTransport',
       })
       this._syncEncodingSettings(false)
+      this._syncMouseMode()
       this._requestKeyframe()
       void this._webTransportReadLoop()
       return true
@@ -678,6 +866,7 @@ export class UltraLowLatencyPlayer {
             detail: '传输: WebRTC',
           })
           this._syncEncodingSettings(false)
+          this._syncMouseMode()
           this._requestKeyframe()
           resolve(true)
         }
@@ -703,7 +892,7 @@ export class UltraLowLatencyPlayer {
             fetch(WEBRTC_OFFER_PATH, {
               method: 'POST',
               headers: { 'Content-Type': 'application/json' },
-              body: JSON.stringify({ sdp: this.webrtc.localDescription.sdp })
+              body: JSON.stringify({ ...this._connectParams(), sdp: this.webrtc.localDescription.sdp })
             })
               .then(response => {
                 if (!response.ok) throw new Error('WebRTC signaling failed')
@@ -749,7 +938,7 @@ export class UltraLowLatencyPlayer {
     }
   }
 
-  async _fetchWebTransportServerCertificateHash(timeoutMs = 1200) {
+  async _fetchWebTransportServerCertificateHashes(timeoutMs = 1200) {
     const endpoint = `${location.origin}${WEBTRANSPORT_HASH_PATH}`
     const controller = new AbortController()
     const timer = setTimeout(() => {
@@ -763,38 +952,44 @@ export class UltraLowLatencyPlayer {
         signal: controller.signal,
       })
       if (!response.ok) {
-        return null
+        return []
       }
 
+      // 服务端会同时公布即将轮换到的证书哈希，旧服务端只返回单个哈希
       const payload = await response.json()
-      const bytes = payload && Array.isArray(payload.value) ? payload.value : null
-      if (!bytes || bytes.length !== 32) {
-        return null
-      }
-
-      const normalizedBytes = []
-      for (const raw of bytes) {
-        const value = Number.parseInt(raw, 10)
-        if (!Number.isFinite(value) || value < 0 || value > 255) {
-          return null
-        }
-        normalizedBytes.push(value)
-      }
-
-      return {
-        algorithm: typeof payload.algorithm === 'string' ? payload.algorithm.toLowerCase() : 'sha-256',
-        value: new Uint8Array(normalizedBytes),
-      }
+      const entries = payload && Array.isArray(payload.hashes) ? payload.hashes : [payload]
+      return entries.map((entry) => this._parseCertificateHash(entry)).filter(Boolean)
     } catch (_) {
-      return null
+      return []
     } finally {
       clearTimeout(timer)
     }
   }
 
+  _parseCertificateHash(entry) {
+    const bytes = entry && Array.isArray(entry.value) ? entry.value : null
+    if (!bytes || bytes.length !== 32) {
+      return null
+    }
+
+    const normalizedBytes = []
+    for (const raw of bytes) {
+      const value = Number.parseInt(raw, 10)
+      if (!Number.isFinite(value) || value < 0 || value > 255) {
+        return null
+      }
+      normalizedBytes.push(value)
+    }
+
+    return {
+      algorithm: typeof entry.algorithm === 'string' ? entry.algorithm.toLowerCase() : 'sha-256',
+      value: new Uint8Array(normalizedBytes),
+    }
+  }
+
   _connectWebSocket() {
     const wsProtocol = location.protocol === 'https:' ? 'wss' : 'ws'
-    const wsUrl = `${wsProtocol}://${location.host}/ws`
+    const wsUrl = `${wsProtocol}://${location.host}${WEBSOCKET_PATH}${this._connectQueryString()}`
     console.log('连接到:', wsUrl)
 
     this.transportKind = 'websocket'
@@ -816,6 +1011,7 @@ export class UltraLowLatencyPlayer {
         detail: '传输: WebSocket',
       })
       this._syncEncodingSettings(false)
+      this._syncMouseMode()
       this._requestKeyframe()
     }
 
@@ -940,6 +1136,7 @@ export class UltraLowLatencyPlayer {
         ENCODING_LIMITS.keyframeInterval.max,
         fallback.keyframeInterval,
       ),
+      simulcast: typeof source.simulcast === 'boolean' ? source.simulcast : !!fallback.simulcast,
     }
   }
 
@@ -969,6 +1166,7 @@ export class UltraLowLatencyPlayer {
       fps: this.encodingSettings.fps,
       bitrate: this.encodingSettings.bitrateMbps * 1_000_000,
       keyframe_interval: this.encodingSettings.keyframeInterval,
+      simulcast: this.encodingSettings.simulcast,
     })
 
     if (requestKeyframe) {
@@ -1013,6 +1211,7 @@ export class UltraLowLatencyPlayer {
         fps: payload?.fps,
         bitrateMbps,
         keyframeInterval: payload?.keyframe_interval,
+        simulcast: payload?.simulcast,
       },
       this.encodingSettings,
     )
@@ -1025,6 +1224,76 @@ export class UltraLowLatencyPlayer {
     }
   }
 
+  _applySessionRoleState(payload) {
+    const previousRole = this.sessionRole
+    this.sessionRole = typeof payload?.role === 'string' ? payload.role : null
+    this.ui.sessionId = Number.isFinite(payload?.session_id) ? payload.session_id : null
+    this.ui.sessionRole = this.sessionRole
+    this.ui.sessions = Array.isArray(payload?.sessions) ? payload.sessions : []
+
+    if (previousRole && previousRole !== this.sessionRole) {
+      this._flashHint(`会话角色已变更为 ${this._roleLabel(this.sessionRole)}`)
+    }
+
+    if (!this._canSendInput()) {
+      // 服务端已拒绝本会话的输入，按键状态由服务端负责释放
+      this.pressedKeys.clear()
+      this.pressedButtons.clear()
+    }
+  }
+
+  _roleLabel(role) {
+    switch (role) {
+      case 'viewer':
+        return '观看者'
+      case 'controller':
+        return '控制者'
+      case 'admin':
+        return '管理员'
+      default:
+        return '--'
+    }
+  }
+
+  _canSendInput() {
+    return this.sessionRole !== 'viewer'
+  }
+
+  _applyFloorState(payload) {
+    const previousHolder = this.ui.floorHolder
+    this.ui.floorMode = typeof payload?.mode === 'string' ? payload.mode : null
+    this.ui.floorHolder = Number.isFinite(payload?.holder) ? payload.holder : null
+
+    if (this.ui.floorMode !== 'exclusive' || previousHolder === this.ui.floorHolder) {
+      return
+    }
+
+    if (this.ui.floorHolder === null) {
+      this._flashHint('输入控制权已释放')
+    } else if (this.ui.floorHolder === this.ui.sessionId) {
+      this._flashHint('已获得输入控制权')
+    } else {
+      this._flashHint(`输入控制权由会话 #${this.ui.floorHolder} 持有`)
+    }
+  }
+
+  requestFloor() {
+    this._sendJsonControlPacket(FRAME_TYPE.FLOOR_CONTROL, { action: 'request' })
+  }
+
+  releaseFloor() {
+    this._releaseAllInputs()
+    this._sendJsonControlPacket(FRAME_TYPE.FLOOR_CONTROL, { action: 'release' })
+  }
+
+  handOffControl(targetId) {
+    this._sendJsonControlPacket(FRAME_TYPE.SESSION_ROLE, { action: 'hand_off', target: targetId })
+  }
+
+  setSessionRole(targetId, role) {
+    this._sendJsonControlPacket(FRAME_TYPE.SESSION_ROLE, { action: 'set', target: targetId, role })
+  }
+
   _toggleEncodingPanel() {
     const willOpen = !this.ui.encodingPanelVisible
     if (willOpen) {
@@ -1052,7 +1321,8 @@ export class UltraLowLatencyPlayer {
           return
         }
 
-        if (event.isComposing) {
+        // 输入法组字中的按键交给隐藏输入框，提交的文字通过 compositionend 发送
+        if (event.isComposing || event.keyCode === IME_PROCESS_KEY_CODE) {
           return
         }
 
@@ -1069,19 +1339,30 @@ export class UltraLowLatencyPlayer {
           return
         }
 
-        if (!this.controlActive || event.key === 'Escape' || event.isComposing) {
+        if (
+          !this.controlActive ||
+          event.key === 'Escape' ||
+          event.isComposing ||
+          event.keyCode === IME_PROCESS_KEY_CODE
+        ) {
           return
         }
 
         event.preventDefault()
         this._sendKeyboardInput(event, false)
+        this._flushPendingPaste()
       },
       true,
     )
 
+    this._bindImeInput()
+
     this.canvas.addEventListener('mousedown', (event) => {
       event.preventDefault()
-      this._activateControl()
+      this._activateControl(event)
+      if (this.relativeMouse) {
+        this._requestPointerLock()
+      }
 
       const pos = this._capturePointerPositionFromMouse(event)
       this.pressedButtons.add(event.button)
@@ -1099,7 +1380,17 @@ export class UltraLowLatencyPlayer {
         return
       }
 
-      if (this._isPointerLocked()) {
+      if (this.relativeMouse) {
+        // 相对模式只在鼠标锁定时发送位移，按画面缩放换算为主机像素
+        if (!this._isPointerLocked()) {
+          return
+        }
+        const scale = this._hostPixelScale()
+        const pending = this.pendingRelativeMove || { dx: 0, dy: 0 }
+        pending.dx += event.movementX * scale.x
+        pending.dy += event.movementY * scale.y
+        this.pendingRelativeMove = pending
+      } else if (this._isPointerLocked()) {
         this.pendingMouseMoveEvent = {
           mode: 'relative',
           movementX: event.movementX,
@@ -1169,6 +1460,8 @@ export class UltraLowLatencyPlayer {
       { passive: false },
     )
 
+    this._bindPointerInput()
+
     this.canvas.addEventListener('contextmenu', (event) => {
       if (this.controlActive) {
         event.preventDefault()
@@ -1186,7 +1479,12 @@ export class UltraLowLatencyPlayer {
     })
 
     document.addEventListener('pointerlockchange', () => {
-      if (this.lockPointerToVideo && this.controlActive && !this._isPointerLocked()) {
+      if (!this.controlActive || this._isPointerLocked()) {
+        return
+      }
+      if (this.relativeMouse) {
+        this._flashHint('相对鼠标模式需要锁定鼠标，点击画面重新锁定')
+      } else if (this.lockPointerToVideo) {
         this._flashHint('鼠标锁定已解除，按 Ctrl+Alt+Shift+L 重新锁定')
       }
     })
@@ -1236,6 +1534,8 @@ export class UltraLowLatencyPlayer {
         return 'clipboard'
       case 'KeyD':
         return 'minimize'
+      case 'KeyR':
+        return 'relative'
       default:
         return null
     }
@@ -1268,7 +1568,7 @@ export class UltraLowLatencyPlayer {
           this._deactivateControl()
           this._flashHint('输入控制已释放')
         } else {
-          this._activateControl()
+          this._activateControl(event)
           this._flashHint('输入控制已激活')
         }
         break
@@ -1309,7 +1609,10 @@ export class UltraLowLatencyPlayer {
         this._flashHint(`本地光标${this.showLocalCursor ? '显示' : '隐藏'}`)
         break
       case 'clipboard':
-        this._flashHint('浏览器版暂不支持 Ctrl+Alt+Shift+V 粘贴')
+        void this._pasteClipboardAsText()
+        break
+      case 'relative':
+        this._setRelativeMouse(!this.relativeMouse)
         break
       case 'minimize':
         this._flashHint('浏览器版暂不支持 Ctrl+Alt+Shift+D 最小化')
@@ -1425,19 +1728,58 @@ export class UltraLowLatencyPlayer {
   }
 
   _applyCursorVisibility() {
-    this.ui.showLocalCursor = this.showLocalCursor
+    this.ui.showLocalCursor = this.showLocalCursor && !this.relativeMouse
+  }
+
+  // 相对鼠标模式：锁定并隐藏本地光标，只发送位移，主机光标交给应用（如游戏）控制
+  _setRelativeMouse(enabled) {
+    this.relativeMouse = enabled
+    this.pendingRelativeMove = null
+    this._applyCursorVisibility()
+    this._sendJsonControlPacket(FRAME_TYPE.MOUSE_MODE, { relative: enabled })
+
+    if (enabled && this.controlActive) {
+      this._requestPointerLock()
+    } else if (!enabled && (!this.lockPointerToVideo || this.showLocalCursor)) {
+      this._exitPointerLock()
+    }
   }
 
-  _activateControl() {
+  // 重连后的新会话默认为绝对模式，需要重新开启
+  _syncMouseMode() {
+    if (this.relativeMouse) {
+      this._sendJsonControlPacket(FRAME_TYPE.MOUSE_MODE, { relative: true })
+    }
+  }
+
+  _applyMouseMode(payload) {
+    const relative = payload?.relative === true
+    if (this.relativeMouse && !relative) {
+      this.relativeMouse = false
+      this.pendingRelativeMove = null
+      this._applyCursorVisibility()
+      if (!this.lockPointerToVideo || this.showLocalCursor) {
+        this._exitPointerLock()
+      }
+      this._flashHint('主机不支持相对鼠标输入')
+      return
+    }
+    this._flashHint(`相对鼠标模式${relative ? '已开启' : '已关闭'}`)
+  }
+
+  _activateControl(event) {
     if (this.controlActive) {
       return
     }
 
     this.controlActive = true
-    this.canvas.focus({ preventScroll: true })
+    this._reportLockKeys(event)
+    // 焦点放在隐藏输入框上，输入法才能组字
+    const focusTarget = this.imeInput || this.canvas
+    focusTarget.focus({ preventScroll: true })
     this._applyCursorVisibility()
 
-    if (this.lockPointerToVideo && !this.showLocalCursor) {
+    if (this.relativeMouse || (this.lockPointerToVideo && !this.showLocalCursor)) {
       this._requestPointerLock()
     }
   }
@@ -1450,35 +1792,27 @@ export class UltraLowLatencyPlayer {
     this._releaseAllInputs()
     this.controlActive = false
     this.pendingMouseMoveEvent = null
+    this.pendingRelativeMove = null
     this.mouseMoveScheduled = false
     this._exitPointerLock()
     this._applyCursorVisibility()
+    this.pendingPasteText = null
+    this.imeInput?.blur()
     this.canvas.blur()
   }
 
+  // 服务端记录了本会话按下的键与按钮，一条消息即可全部松开
   _releaseAllInputs() {
     if (this._isTransportOpen()) {
-      for (const keyInfo of this.pressedKeys.values()) {
-        this._sendJsonControlPacket(FRAME_TYPE.KEYBOARD_INPUT, {
-          key_code: keyInfo.keyCode,
-          code: keyInfo.code,
-          down: false,
-        })
-      }
-
-      for (const button of this.pressedButtons.values()) {
-        this._sendMouseInput({
-          kind: 'button',
-          x: this.lastPointerPos.x,
-          y: this.lastPointerPos.y,
-          button,
-          down: false,
-        })
-      }
+      const header = new ArrayBuffer(HEADER_SIZE)
+      const view = new DataView(header)
+      view.setUint8(0, FRAME_TYPE.INPUT_RELEASE)
+      this._sendBinaryPacket(header)
     }
 
     this.pressedKeys.clear()
     this.pressedButtons.clear()
+    this.touchSlots.clear()
   }
 
   _scheduleMouseMove() {
@@ -1490,7 +1824,17 @@ export class UltraLowLatencyPlayer {
     requestAnimationFrame(() => {
       this.mouseMoveScheduled = false
 
-      if (!this.controlActive || !this.pendingMouseMoveEvent) {
+      if (!this.controlActive) {
+        return
+      }
+
+      if (this.pendingRelativeMove) {
+        const { dx, dy } = this.pendingRelativeMove
+        this.pendingRelativeMove = null
+        this._sendMouseInput({ kind: 'move_relative', dx, dy })
+      }
+
+      if (!this.pendingMouseMoveEvent) {
         return
       }
 
@@ -1564,6 +1908,16 @@ export class UltraLowLatencyPlayer {
     return { x, y }
   }
 
+  // 画面中一个 CSS 像素对应的主机像素数
+  _hostPixelScale() {
+    const rect = this._getRenderRect()
+    if (rect.width <= 0 || rect.height <= 0 || this.canvas.width <= 0 || this.canvas.height <= 0) {
+      return { x: 1, y: 1 }
+    }
+
+    return { x: this.canvas.width / rect.width, y: this.canvas.height / rect.height }
+  }
+
   _normalizePointerDelta(movementX, movementY) {
     const rect = this._getRenderRect()
     if (rect.width <= 0 || rect.height <= 0) {
@@ -1576,10 +1930,17 @@ export class UltraLowLatencyPlayer {
   }
 
   _sendMouseInput(payload) {
+    if (!this._canSendInput()) {
+      return
+    }
     this._sendJsonControlPacket(FRAME_TYPE.MOUSE_INPUT, payload)
   }
 
   _sendKeyboardInput(event, down) {
+    if (!this._canSendInput()) {
+      return
+    }
+
     const keyCode = event.keyCode || event.which || 0
     const code = event.code || null
     if (keyCode === 0 && !code) {
@@ -1600,6 +1961,226 @@ export class UltraLowLatencyPlayer {
     })
   }
 
+  // 视频画面上方不可见的输入框，用于承接输入法组字与移动端软键盘输入
+  _bindImeInput() {
+    const input = document.createElement('textarea')
+    input.setAttribute('aria-hidden', 'true')
+    input.setAttribute('autocomplete', 'off')
+    input.setAttribute('autocapitalize', 'off')
+    input.spellcheck = false
+    input.tabIndex = -1
+    Object.assign(input.style, {
+      position: 'fixed',
+      left: '0',
+      top: '0',
+      width: '1px',
+      height: '1px',
+      opacity: '0',
+      pointerEvents: 'none',
+      resize: 'none',
+    })
+    document.body.appendChild(input)
+    this.imeInput = input
+
+    input.addEventListener('compositionend', (event) => {
+      if (this.controlActive && event.data) {
+        this._sendTextInput(event.data, false)
+      }
+      input.value = ''
+    })
+
+    // 不经过组字直接插入的文字（如移动端软键盘）
+    input.addEventListener('input', (event) => {
+      if (event.isComposing) {
+        return
+      }
+      if (this.controlActive && event.inputType === 'insertText' && event.data) {
+        this._sendTextInput(event.data, false)
+      }
+      input.value = ''
+    })
+  }
+
+  // 触控与笔通过 Pointer Events 转发，鼠标仍走 mouse 事件
+  _bindPointerInput() {
+    const handle = (event, state) => {
+      if (event.pointerType === 'touch') {
+        this._handleTouchPointer(event, state)
+      } else if (event.pointerType === 'pen') {
+        this._handlePenPointer(event, state)
+      }
+    }
+
+    this.canvas.addEventListener('pointerdown', (event) => {
+      if (event.pointerType !== 'touch' && event.pointerType !== 'pen') {
+        return
+      }
+      // 阻止浏览器随后合成的鼠标事件
+      event.preventDefault()
+      this._activateControl(event)
+      handle(event, 'down')
+    })
+    this.canvas.addEventListener('pointermove', (event) => handle(event, 'move'))
+    this.canvas.addEventListener('pointerup', (event) => handle(event, 'up'))
+    this.canvas.addEventListener('pointercancel', (event) => handle(event, 'cancel'))
+    this.canvas.addEventListener('pointerleave', (event) => {
+      if (event.pointerType === 'pen') {
+        this._handlePenPointer(event, 'leave')
+      }
+    })
+  }
+
+  _handleTouchPointer(event, state) {
+    if (!this.controlActive || !this._canSendInput()) {
+      return
+    }
+
+    let id = this.touchSlots.get(event.pointerId)
+    if (state === 'down') {
+      if (id !== undefined) {
+        return
+      }
+      const used = new Set(this.touchSlots.values())
+      id = [...Array(MAX_TOUCH_CONTACTS).keys()].find((slot) => !used.has(slot))
+      if (id === undefined) {
+        return
+      }
+      this.touchSlots.set(event.pointerId, id)
+      this.canvas.setPointerCapture?.(event.pointerId)
+    } else if (id === undefined) {
+      return
+    }
+    if (state === 'up' || state === 'cancel') {
+      this.touchSlots.delete(event.pointerId)
+    }
+
+    event.preventDefault()
+    const pos = this._normalizePointer(event.clientX, event.clientY)
+    const rect = this._getRenderRect()
+    this._sendJsonControlPacket(FRAME_TYPE.TOUCH_INPUT, {
+      contacts: [
+        {
+          id,
+          x: pos.x,
+          y: pos.y,
+          state,
+          primary: event.isPrimary,
+          pressure: event.pressure > 0 ? event.pressure : null,
+          width: rect.width > 0 && event.width > 1 ? event.width / rect.width : null,
+          height: rect.height > 0 && event.height > 1 ? event.height / rect.height : null,
+        },
+      ],
+    })
+  }
+
+  _handlePenPointer(event, state) {
+    if (!this.controlActive || !this._canSendInput()) {
+      return
+    }
+
+    let penState = state
+    if (state === 'move') {
+      penState = event.buttons & (PEN_BUTTON_TIP | PEN_BUTTON_ERASER) ? 'move' : 'hover'
+    } else if (state === 'cancel') {
+      penState = 'up'
+    }
+    if (penState === 'down') {
+      this.canvas.setPointerCapture?.(event.pointerId)
+    }
+
+    event.preventDefault()
+    const pos = this._normalizePointer(event.clientX, event.clientY)
+    this._sendJsonControlPacket(FRAME_TYPE.PEN_INPUT, {
+      x: pos.x,
+      y: pos.y,
+      state: penState,
+      pressure: event.pressure,
+      tilt_x: Math.round(event.tiltX || 0),
+      tilt_y: Math.round(event.tiltY || 0),
+      eraser: (event.buttons & PEN_BUTTON_ERASER) !== 0,
+      barrel: (event.buttons & PEN_BUTTON_BARREL) !== 0,
+    })
+  }
+
+  // 接管输入时上报本地锁定键状态，服务端据此切换主机锁定键并回报主机状态
+  _reportLockKeys(event) {
+    if (!this._canSendInput() || typeof event?.getModifierState !== 'function') {
+      return
+    }
+
+    // macOS 键盘没有 NumLock/ScrollLock，浏览器总是报告关闭，此时不同步这两个键
+    const hasNumLock = !/Mac|iPhone|iPad/.test(navigator.platform)
+    this._sendJsonControlPacket(FRAME_TYPE.LOCK_KEYS, {
+      caps_lock: event.getModifierState('CapsLock'),
+      num_lock: hasNumLock ? event.getModifierState('NumLock') : null,
+      scroll_lock: hasNumLock ? event.getModifierState('ScrollLock') : null,
+    })
+  }
+
+  _sendTextInput(text, paste) {
+    if (!this._canSendInput() || !text) {
+      return
+    }
+
+    this._sendJsonControlPacket(FRAME_TYPE.TEXT_INPUT, { text, paste })
+  }
+
+  // 按文字逐字键入剪贴板内容，用于禁止粘贴的输入框
+  async _pasteClipboardAsText() {
+    if (!this.controlActive) {
+      this._flashHint('请先接管输入控制再粘贴')
+      return
+    }
+    if (!this._canSendInput()) {
+      this._flashHint('观看者不能输入')
+      return
+    }
+    if (!navigator.clipboard?.readText) {
+      this._flashHint('浏览器不支持读取剪贴板（需要 HTTPS 并授予权限）')
+      return
+    }
+
+    let text
+    try {
+      text = await navigator.clipboard.readText()
+    } catch (error) {
+      this._flashHint(`无法读取剪贴板：${error?.message || error}`)
+      return
+    }
+
+    const chars = Array.from(text.replace(/\r\n?/g, '\n'))
+    if (chars.length === 0) {
+      this._flashHint('剪贴板中没有文字')
+      return
+    }
+    if (chars.length > MAX_PASTE_CHARS) {
+      this._flashHint(`剪贴板内容过长，只键入前 ${MAX_PASTE_CHARS} 个字符`)
+      chars.length = MAX_PASTE_CHARS
+    }
+
+    this.pendingPasteText = chars
+    this._flushPendingPaste()
+  }
+
+  // 等快捷键的修饰键全部松开后再键入，避免主机把文字当作 Ctrl/Alt 组合键
+  _flushPendingPaste() {
+    const chars = this.pendingPasteText
+    if (!chars) {
+      return
+    }
+    for (const keyId of this.pressedKeys.keys()) {
+      if (MODIFIER_CODES.has(keyId)) {
+        return
+      }
+    }
+
+    this.pendingPasteText = null
+    for (let offset = 0; offset < chars.length; offset += PASTE_CHUNK_CHARS) {
+      this._sendTextInput(chars.slice(offset, offset + PASTE_CHUNK_CHARS).join(''), true)
+    }
+    this._flashHint(`已按文字键入 ${chars.length} 个字符`)
+  }
+
   _sendBinaryPacket(buffer) {
     if (!this._isTransportOpen()) {
       return false
@@ -1714,6 +2295,11 @@ export class UltraLowLatencyPlayer {
 
     const payload = new Uint8Array(data.buffer, data.byteOffset + HEADER_SIZE, payloadLen)
 
+    if (frameType === FRAME_TYPE.PING) {
+      this._sendPong(sequence)
+      return
+    }
+
     if (frameType === FRAME_TYPE.MONITOR_LIST) {
       try {
         const jsonStr = this.textDecoder.decode(payload)
@@ -1736,6 +2322,65 @@ export class UltraLowLatencyPlayer {
       return
     }
 
+    if (frameType === FRAME_TYPE.SESSION_ROLE) {
+      try {
+        const jsonStr = this.textDecoder.decode(payload)
+        this._applySessionRoleState(JSON.parse(jsonStr))
+      } catch (error) {
+        console.error('解析会话角色失败', error)
+      }
+      return
+    }
+
+    if (frameType === FRAME_TYPE.MOUSE_MODE) {
+      try {
+        const jsonStr = this.textDecoder.decode(payload)
+        this._applyMouseMode(JSON.parse(jsonStr))
+      } catch (error) {
+        console.error('解析鼠标模式失败', error)
+      }
+      return
+    }
+
+    if (frameType === FRAME_TYPE.FLOOR_CONTROL) {
+      try {
+        const jsonStr = this.textDecoder.decode(payload)
+        this._applyFloorState(JSON.parse(jsonStr))
+      } catch (error) {
+        console.error('解析输入控制权失败', error)
+      }
+      return
+    }
+
+    if (frameType === FRAME_TYPE.NOTICE) {
+      try {
+        const jsonStr = this.textDecoder.decode(payload)
+        const notice = JSON.parse(jsonStr)
+        if (notice?.kind === 'consent_pending') {
+          this._setConnectionState({
+            visible: true,
+            connected: false,
+            text: notice.message,
+            detail: '',
+          })
+          return
+        }
+
+        if (TERMINAL_NOTICE_KINDS.includes(notice?.kind)) {
+          this.autoReconnect = false
+          this.sessionEndedReason = notice.message || '连接已结束'
+          return
+        }
+
+        if (notice?.message) {
+          this._flashHint(notice.message)
+        }
+      } catch (error) {
+        console.error('解析服务端提示失败', error)
+      }
+      return
+    }
+
     if (frameType !== FRAME_TYPE.VIDEO) {
       return
     }
@@ -1839,6 +2484,19 @@ export class UltraLowLatencyPlayer {
     this._sendBinaryPacket(header)
   }
 
+  // 原样回送服务端的时延探测序号
+  _sendPong(sequence) {
+    if (!this._isTransportOpen()) {
+      return
+    }
+
+    const header = new ArrayBuffer(HEADER_SIZE)
+    const view = new DataView(header)
+    view.setUint8(0, FRAME_TYPE.PONG)
+    view.setUint32(2, sequence, true)
+    this._sendBinaryPacket(header)
+  }
+
   _updateMonitorList(monitors) {
     this.ui.monitors = monitors
 
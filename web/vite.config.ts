@@ -1,16 +1,45 @@
+import { readFileSync, writeFileSync } from 'node:fs'
+import { join } from 'node:path'
 import { fileURLToPath, URL } from 'node:url'
+import { brotliCompressSync, constants as zlibConstants, gzipSync } from 'node:zlib'
 
-import { defineConfig } from 'vite'
+import { defineConfig, type Plugin } from 'vite'
 import vue from '@vitejs/plugin-vue'
 import vueJsx from '@vitejs/plugin-vue-jsx'
 import vueDevTools from 'vite-plugin-vue-devtools'
 
+// 为文本类构建产物生成 .br / .gz 预压缩版本，服务端按 Accept-Encoding 选用
+function precompress(): Plugin {
+  return {
+    name: 'webdisplay-precompress',
+    apply: 'build',
+    enforce: 'post',
+    writeBundle(options, bundle) {
+      const outDir = options.dir ?? 'dist'
+      for (const fileName of Object.keys(bundle)) {
+        if (!/\.(html|js|css|svg|json)$/.test(fileName)) continue
+        const path = join(outDir, fileName)
+        const source = readFileSync(path)
+        // 太小的文件压缩收益有限
+        if (source.length < 1024) continue
+        writeFileSync(`${path}.br`, brotliCompressSync(source, {
+          params: { [zlibConstants.BROTLI_PARAM_QUALITY]: zlibConstants.BROTLI_MAX_QUALITY },
+        }))
+        writeFileSync(`${path}.gz`, gzipSync(source, { level: 9 }))
+      }
+    },
+  }
+}
+
 // https://vite.dev/config/
 export default defineConfig({
+  // 使用相对资源路径，服务端可挂载在任意路径前缀下
+  base: './',
   plugins: [
     vue(),
     vueJsx(),
     vueDevTools(),
+    precompress(),
   ],
   server: {
     proxy: {